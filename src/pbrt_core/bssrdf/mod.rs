@@ -1,6 +1,211 @@
-use super::tool::SurfaceInteraction;
+use std::f32::consts::PI;
 
-pub struct BSSRDF<'a>{
-    po:&'a SurfaceInteraction<'a>,
+use glam::{Vec2, Vec3};
+
+use super::{
+    bxdf::{func::fr_dielectric, BxDF, BxDFAble, BxDFType, TransportMode},
+    material::BSDF,
+    primitive::Primitive,
+    tool::{color::Color, func, sence::Sence, InteractionCommon, Ray, RayDiff, SurfaceInteraction},
+};
+
+//可分离BSSRDF，空间项使用Christensen-Burley归一化扩散剖面
+pub struct BSSRDF {
+    //入射点
+    po: InteractionCommon,
+    //以阴影法线为z轴的局部坐标系
+    ss: Vec3,
+    ts: Vec3,
+    ns: Vec3,
+    eta: f32,
+    mode: TransportMode,
+    //剖面宽度，由平均自由程与反照率换算
+    d: Color,
+    //多次散射后的表面反照率
+    albedo: Color,
+    //所属材质，探测光线只接受同一材质的交点
+    material: usize,
+}
+impl BSSRDF {
+    //探测光线最多求交的次数
+    const MAX_PROBE: usize = 16;
+    pub fn new(
+        po: &InteractionCommon,
+        ns: Vec3,
+        eta: f32,
+        mode: TransportMode,
+        mfp: Color,
+        albedo: Color,
+        material: usize,
+    ) -> Self {
+        let (mut ss, mut ts) = (Vec3::ZERO, Vec3::ZERO);
+        func::vec3_coordinate_system(ns, &mut ss, &mut ts);
+        //Burley的经验拟合，剖面积分恰为反照率
+        let s = Vec3::splat(1.85) - albedo + 7.0 * (albedo - 0.8).abs().powf(3.0);
+        Self {
+            po: *po,
+            ss,
+            ts,
+            ns,
+            eta,
+            mode,
+            d: (mfp / s).max(Vec3::splat(1e-4)),
+            albedo,
+            material,
+        }
+    }
+    //距离r处的空间剖面，在平面上的积分为albedo
+    pub fn sr(&self, r: f32) -> Color {
+        let r = r.max(1e-6);
+        let d = self.d;
+        let e = (-r / d).exp() + (-r / (3.0 * d)).exp();
+        self.albedo * e / (8.0 * PI * d * r)
+    }
+    //按剖面采样通道ch的半径，两个指数分量分别占1/4与3/4
+    pub fn sample_sr(&self, ch: usize, u: f32) -> f32 {
+        let d = self.d[ch];
+        if u < 0.25 {
+            -d * (1.0 - u * 4.0).ln()
+        } else {
+            -3.0 * d * (1.0 - (u - 0.25) / 0.75).ln()
+        }
+    }
+    //sample_sr对应的面积pdf
+    pub fn pdf_sr(&self, ch: usize, r: f32) -> f32 {
+        let r = r.max(1e-6);
+        let d = self.d[ch];
+        ((-r / d).exp() + (-r / (3.0 * d)).exp()) / (4.0 * d * 2.0 * PI * r)
+    }
+    //所有投影轴与通道的pdf之和
+    fn pdf_sp(&self, pi: &InteractionCommon) -> f32 {
+        let d = self.po.p - pi.p;
+        let d_local = Vec3::new(self.ss.dot(d), self.ts.dot(d), self.ns.dot(d));
+        let n = pi.normal.normalize();
+        let n_local = Vec3::new(self.ss.dot(n), self.ts.dot(n), self.ns.dot(n));
+        let r_proj = [
+            (d_local.y * d_local.y + d_local.z * d_local.z).sqrt(),
+            (d_local.z * d_local.z + d_local.x * d_local.x).sqrt(),
+            (d_local.x * d_local.x + d_local.y * d_local.y).sqrt(),
+        ];
+        let axis_prob = [0.25, 0.25, 0.5];
+        let mut pdf = 0.0;
+        for axis in 0..3 {
+            for ch in 0..3 {
+                pdf += self.pdf_sr(ch, r_proj[axis]) * n_local[axis].abs() * axis_prob[axis] / 3.0;
+            }
+        }
+        pdf
+    }
+    //沿投影轴发射探测光线寻找出射点，返回空间项与出射点，出射点的BSDF为方向项
+    pub fn sample_s<'a>(
+        &self,
+        sence: &'a Sence,
+        u1: f32,
+        u2: Vec2,
+        pdf: &mut f32,
+    ) -> Option<(Color, SurfaceInteraction<'a>)> {
+        //一半概率沿法线投影，其余沿两条切线
+        let (vx, vy, vz, u1) = if u1 < 0.5 {
+            (self.ss, self.ts, self.ns, u1 * 2.0)
+        } else if u1 < 0.75 {
+            (self.ts, self.ns, self.ss, (u1 - 0.5) * 4.0)
+        } else {
+            (self.ns, self.ss, self.ts, (u1 - 0.75) * 4.0)
+        };
+        let ch = ((u1 * 3.0) as usize).min(2);
+        let u1 = u1 * 3.0 - ch as f32;
+        let r = self.sample_sr(ch, u2.x);
+        let r_max = self.sample_sr(ch, 0.999);
+        if r < 0.0 || r >= r_max {
+            return None;
+        }
+        let phi = 2.0 * PI * u2.y;
+        let l = 2.0 * (r_max * r_max - r * r).sqrt();
+        let start = self.po.p + r * (vx * phi.cos() + vy * phi.sin()) - l * vz * 0.5;
+        //收集探测线段上同一材质的所有交点
+        let mut found = vec![];
+        let mut ray = RayDiff::new(Ray::from_with_t(start, vz, 0.0, l));
+        for _ in 0..Self::MAX_PROBE {
+            let Some(mut hit) = sence.interacect(ray) else {
+                break;
+            };
+            let remain = ray.o.t_max - hit.common.time;
+            hit.compute_scattering(ray, self.mode);
+            let next = hit.spawn_ray(&vz);
+            if hit
+                .bssrdf
+                .as_ref()
+                .is_some_and(|b| b.material == self.material)
+            {
+                found.push(hit);
+            }
+            if remain <= 0.0 {
+                break;
+            }
+            ray = next;
+            ray.o.t_max = remain;
+        }
+        if found.is_empty() {
+            return None;
+        }
+        let n = found.len();
+        let selected = ((u1 * n as f32) as usize).min(n - 1);
+        let mut pi = found.swap_remove(selected);
+        *pdf = self.pdf_sp(&pi.common) / n as f32;
+        let sp = self.sr(self.po.p.distance(pi.common.p));
+        //出射点只保留方向项，wo取法线方向
+        let mut bsdf = BSDF::new(&pi, self.eta);
+        bsdf.add(BxDF::Subsurface(SubsurfaceAdapter::new(
+            self.eta, self.mode,
+        )));
+        pi.bsdf = Some(bsdf);
+        pi.bssrdf = None;
+        pi.common.w0 = -pi.shading.n;
+        Some((sp, pi))
+    }
+}
+
+//Fresnel反射率的一阶矩
+pub fn fresnel_moment1(eta: f32) -> f32 {
+    let (eta2, eta3) = (eta * eta, eta * eta * eta);
+    let (eta4, eta5) = (eta3 * eta, eta3 * eta2);
+    if eta < 1.0 {
+        0.45966 - 1.73965 * eta + 3.37668 * eta2 - 3.904945 * eta3 + 2.49277 * eta4 - 0.68441 * eta5
+    } else {
+        -4.61686 + 11.1136 * eta - 10.4646 * eta2 + 5.11455 * eta3 - 1.27198 * eta4 + 0.12746 * eta5
+    }
+}
+
+//BSSRDF的方向项，出射点处按菲涅尔透射率离开表面
+pub struct SubsurfaceAdapter {
     eta: f32,
-}
\ No newline at end of file
+    //归一化系数，使余弦加权积分为1
+    c: f32,
+    mode: TransportMode,
+}
+impl SubsurfaceAdapter {
+    pub fn new(eta: f32, mode: TransportMode) -> Self {
+        Self {
+            eta,
+            c: 1.0 - 2.0 * fresnel_moment1(1.0 / eta),
+            mode,
+        }
+    }
+}
+impl BxDFAble for SubsurfaceAdapter {
+    fn match_type(&self, flag: u32) -> bool {
+        ((BxDFType::Reflection | BxDFType::Diffuse) & flag) != 0
+    }
+    fn f(&self, w_in: &Vec3, _w_out: &Vec3) -> Vec3 {
+        let sw = (1.0 - fr_dielectric(w_in.z, 1.0, self.eta)) / (self.c * PI);
+        //入射时的透射项除以了折射率平方，出射时补回
+        if self.mode == TransportMode::Radiance {
+            Vec3::splat(sw * self.eta * self.eta)
+        } else {
+            Vec3::splat(sw)
+        }
+    }
+    fn get_type(&self) -> u32 {
+        BxDFType::Reflection | BxDFType::Diffuse
+    }
+}
@@ -1,8 +1,11 @@
-use std::f32::consts::FRAC_1_PI;
+use std::f32::consts::{FRAC_1_PI, PI};
 
-use glam::Vec3;
+use glam::{Vec2, Vec3};
 
-use crate::pbrt_core::tool::color::Color;
+use crate::pbrt_core::tool::{
+    color::Color,
+    func::{lerp, spherical_direction},
+};
 
 use super::{
     func::{self, cos_theta},
@@ -19,6 +22,10 @@ impl DisneyDiffuse {
 }
 impl BxDFAble for DisneyDiffuse {
     fn f(&self, w_in: &Vec3, w_out: &Vec3) -> Vec3 {
+        //只有反射分量，透射方向为0
+        if !func::vec3_same_hemisphere_vec3(w_in, w_out) {
+            return Vec3::ZERO;
+        }
         let fo = func::schlick_weight(cos_theta(w_out).abs());
         let fi = func::schlick_weight(cos_theta(w_in).abs());
 
@@ -44,6 +51,9 @@ impl DisneyRetro {
 
 impl BxDFAble for DisneyRetro{
     fn f(&self, w_in: &Vec3, w_out: &Vec3) -> Vec3 {
+        if !func::vec3_same_hemisphere_vec3(w_in, w_out) {
+            return Vec3::ZERO;
+        }
         let mut wh=*w_in+*w_out;
         if wh==Vec3::ZERO{
             return Vec3::ZERO;
@@ -64,3 +74,146 @@ impl BxDFAble for DisneyRetro{
         BxDFType::Reflection | BxDFType::Diffuse
     }
 }
+
+//次表面散射近似，用于薄表面
+pub struct DisneyFakeSS {
+    r: Color,
+    roughness: f32,
+}
+impl DisneyFakeSS {
+    pub fn new(r: Color, roughness: f32) -> Self {
+        Self { r, roughness }
+    }
+}
+impl BxDFAble for DisneyFakeSS {
+    fn f(&self, w_in: &Vec3, w_out: &Vec3) -> Vec3 {
+        if !func::vec3_same_hemisphere_vec3(w_in, w_out) {
+            return Vec3::ZERO;
+        }
+        let mut wh = *w_in + *w_out;
+        if wh == Vec3::ZERO {
+            return Vec3::ZERO;
+        }
+        wh = wh.normalize();
+        let cos = w_in.dot(wh);
+        let fss90 = cos * cos * self.roughness;
+        let fo = func::schlick_weight(cos_theta(w_out).abs());
+        let fi = func::schlick_weight(cos_theta(w_in).abs());
+        let fss = lerp(fo, 1.0, fss90) * lerp(fi, 1.0, fss90);
+        let ss = 1.25
+            * (fss * (1.0 / (cos_theta(w_out).abs() + cos_theta(w_in).abs()) - 0.5) + 0.5);
+        self.r * FRAC_1_PI * ss
+    }
+    fn match_type(&self, flag: u32) -> bool {
+        (BxDFType::Reflection | BxDFType::Diffuse) & flag > 0
+    }
+    fn get_type(&self) -> u32 {
+        BxDFType::Reflection | BxDFType::Diffuse
+    }
+}
+//光泽层，布料边缘的反光
+pub struct DisneySheen {
+    r: Color,
+}
+impl DisneySheen {
+    pub fn new(r: Color) -> Self {
+        Self { r }
+    }
+}
+impl BxDFAble for DisneySheen {
+    fn f(&self, w_in: &Vec3, w_out: &Vec3) -> Vec3 {
+        if !func::vec3_same_hemisphere_vec3(w_in, w_out) {
+            return Vec3::ZERO;
+        }
+        let mut wh = *w_in + *w_out;
+        if wh == Vec3::ZERO {
+            return Vec3::ZERO;
+        }
+        wh = wh.normalize();
+        let cos = w_in.dot(wh);
+        self.r * func::schlick_weight(cos)
+    }
+    fn match_type(&self, flag: u32) -> bool {
+        (BxDFType::Reflection | BxDFType::Diffuse) & flag > 0
+    }
+    fn get_type(&self) -> u32 {
+        BxDFType::Reflection | BxDFType::Diffuse
+    }
+}
+//清漆层
+pub struct DisneyClearcoat {
+    weight: f32,
+    gloss: f32,
+}
+impl DisneyClearcoat {
+    pub fn new(weight: f32, gloss: f32) -> Self {
+        Self { weight, gloss }
+    }
+}
+#[inline]
+fn gtr1(cos_theta: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    (alpha2 - 1.0) / (PI * alpha2.ln() * (1.0 + (alpha2 - 1.0) * cos_theta * cos_theta))
+}
+#[inline]
+fn smith_g_ggx(cos_theta: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let cos_theta2 = cos_theta * cos_theta;
+    1.0 / (cos_theta + (alpha2 + cos_theta2 - alpha2 * cos_theta2).sqrt())
+}
+impl BxDFAble for DisneyClearcoat {
+    fn f(&self, w_in: &Vec3, w_out: &Vec3) -> Vec3 {
+        if !func::vec3_same_hemisphere_vec3(w_in, w_out) {
+            return Vec3::ZERO;
+        }
+        let mut wh = *w_in + *w_out;
+        if wh == Vec3::ZERO {
+            return Vec3::ZERO;
+        }
+        wh = wh.normalize();
+        let dr = gtr1(cos_theta(&wh).abs(), self.gloss);
+        let fr = func::fr_schlick(0.04, w_out.dot(wh));
+        let gr = smith_g_ggx(cos_theta(w_out).abs(), 0.25)
+            * smith_g_ggx(cos_theta(w_in).abs(), 0.25);
+        Vec3::splat(self.weight * gr * fr * dr / 4.0)
+    }
+    fn sample_f(&self, w_in: &mut Vec3, w_out: &Vec3, sample_point: Vec2, pdf: &mut f32) -> Vec3 {
+        if w_out.z == 0.0 {
+            return Vec3::ZERO;
+        }
+        //按GTR1分布采样半角向量
+        let alpha2 = self.gloss * self.gloss;
+        let cos_theta = (0.0_f32.max((1.0 - alpha2.powf(1.0 - sample_point.x)) / (1.0 - alpha2)))
+            .sqrt();
+        let sin_theta = 0.0_f32.max(1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * PI * sample_point.y;
+        let mut wh = spherical_direction(sin_theta, cos_theta, phi);
+        if !func::vec3_same_hemisphere_vec3(w_out, &wh) {
+            wh = -wh;
+        }
+        *w_in = func::reflect(w_out, &wh);
+        if !func::vec3_same_hemisphere_vec3(w_out, w_in) {
+            return Vec3::ZERO;
+        }
+        *pdf = self.pdf(*w_out, *w_in);
+        self.f(w_in, w_out)
+    }
+    fn pdf(&self, w_out: Vec3, w_in: Vec3) -> f32 {
+        if !func::vec3_same_hemisphere_vec3(&w_out, &w_in) {
+            return 0.0;
+        }
+        let wh = w_in + w_out;
+        if wh == Vec3::ZERO {
+            return 0.0;
+        }
+        let wh = wh.normalize();
+        let dr = gtr1(cos_theta(&wh).abs(), self.gloss);
+        dr * cos_theta(&wh).abs() / (4.0 * w_out.dot(wh))
+    }
+    fn match_type(&self, flag: u32) -> bool {
+        (BxDFType::Reflection | BxDFType::Glossy) & flag > 0
+    }
+    fn get_type(&self) -> u32 {
+        BxDFType::Reflection | BxDFType::Glossy
+    }
+}
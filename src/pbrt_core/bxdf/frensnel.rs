@@ -1,8 +1,8 @@
 use glam::f32::Vec3;
 
 use super::{
-    func::{fr_conductor, fr_dielectric, fr_schlick_spectrum},
-    TransportMode,
+    func::{self, cos_theta, fr_conductor, fr_dielectric, fr_schlick_spectrum},
+    BxDFAble, BxDFType, TransportMode,
 };
 
 //菲涅尔反射模型
@@ -133,3 +133,50 @@ impl FrensnelSpecular {
         }
     }
 }
+impl BxDFAble for FrensnelSpecular {
+    fn match_type(&self, flag: u32) -> bool {
+        ((BxDFType::Specular | BxDFType::Reflection) & flag) != 0
+            || ((BxDFType::Specular | BxDFType::Transmission) & flag) != 0
+    }
+    fn f(&self, _w_in: &Vec3, _w_out: &Vec3) -> Vec3 {
+        Vec3::ZERO
+    }
+    //依据菲涅尔反射率在反射与折射之间随机选择
+    fn sample_f(
+        &self,
+        w_in: &mut Vec3,
+        w_out: &Vec3,
+        sample_point: glam::Vec2,
+        pdf: &mut f32,
+    ) -> Vec3 {
+        let f = fr_dielectric(cos_theta(w_out), self.eta_a, self.eta_b);
+        if sample_point.x < f {
+            *w_in = Vec3::new(-w_out.x, -w_out.y, w_out.z);
+            *pdf = f;
+            f * self.r / cos_theta(w_in).abs()
+        } else {
+            let entering = cos_theta(w_out) > 0.0;
+            let (eta_i, eta_t) = if entering {
+                (self.eta_a, self.eta_b)
+            } else {
+                (self.eta_b, self.eta_a)
+            };
+            let n = func::face_forward(Vec3::Z, *w_out);
+            if !func::refract(w_out, &n, eta_i / eta_t, w_in) {
+                return Vec3::ZERO;
+            }
+            let mut ft = self.t * (1.0 - f);
+            if self.mode == TransportMode::Radiance {
+                ft *= (eta_i * eta_i) / (eta_t * eta_t);
+            }
+            *pdf = 1.0 - f;
+            ft / cos_theta(w_in).abs()
+        }
+    }
+    fn pdf(&self, _w_out: Vec3, _w_in: Vec3) -> f32 {
+        0.0
+    }
+    fn get_type(&self) -> u32 {
+        BxDFType::Specular as u32 | BxDFType::Reflection as u32 | BxDFType::Transmission as u32
+    }
+}
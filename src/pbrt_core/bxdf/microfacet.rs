@@ -0,0 +1,123 @@
+use glam::{Vec2, Vec3};
+
+use crate::pbrt_core::tool::color::Color;
+
+use super::{
+    frensnel::DielectricFresnel,
+    func::{self, cos_theta},
+    BxDFAble, BxDFType, MicrofacetDistribution, TransportMode,
+};
+
+//微表面透射，粗糙电介质
+pub struct MicrofacetTransmission {
+    t: Color,
+    distribution: Box<dyn MicrofacetDistribution>,
+    //上方折射率
+    eta_a: f32,
+    //下方折射率
+    eta_b: f32,
+    fresnel: DielectricFresnel,
+    mode: TransportMode,
+}
+impl MicrofacetTransmission {
+    pub fn new(
+        t: Color,
+        distribution: Box<dyn MicrofacetDistribution>,
+        eta_a: f32,
+        eta_b: f32,
+        mode: TransportMode,
+    ) -> Self {
+        Self {
+            t,
+            distribution,
+            eta_a,
+            eta_b,
+            fresnel: DielectricFresnel::new(eta_a, eta_b),
+            mode,
+        }
+    }
+    //w_out所在一侧相对另一侧的折射率
+    #[inline]
+    fn eta(&self, w_out: &Vec3) -> f32 {
+        if cos_theta(w_out) > 0.0 {
+            self.eta_b / self.eta_a
+        } else {
+            self.eta_a / self.eta_b
+        }
+    }
+}
+impl BxDFAble for MicrofacetTransmission {
+    fn match_type(&self, flag: u32) -> bool {
+        (BxDFType::Transmission | BxDFType::Glossy) & flag > 0
+    }
+    fn f(&self, w_in: &Vec3, w_out: &Vec3) -> Vec3 {
+        if func::vec3_same_hemisphere_vec3(w_out, w_in) {
+            return Vec3::ZERO;
+        }
+        let cos_o = cos_theta(w_out);
+        let cos_i = cos_theta(w_in);
+        if cos_i == 0.0 || cos_o == 0.0 {
+            return Vec3::ZERO;
+        }
+        //广义半角向量
+        let eta = self.eta(w_out);
+        let mut wh = (*w_out + *w_in * eta).normalize();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+        if w_out.dot(wh) * w_in.dot(wh) > 0.0 {
+            return Vec3::ZERO;
+        }
+        let f = self.fresnel.evaluate(w_out.dot(wh));
+        let sqrt_denom = w_out.dot(wh) + eta * w_in.dot(wh);
+        let factor = if self.mode == TransportMode::Radiance {
+            1.0 / eta
+        } else {
+            1.0
+        };
+        (Vec3::ONE - f)
+            * self.t
+            * (self.distribution.d(&wh)
+                * self.distribution.g(w_out, w_in)
+                * eta
+                * eta
+                * w_in.dot(wh).abs()
+                * w_out.dot(wh).abs()
+                * factor
+                * factor
+                / (cos_i * cos_o * sqrt_denom * sqrt_denom))
+                .abs()
+    }
+    fn sample_f(&self, w_in: &mut Vec3, w_out: &Vec3, sample_point: Vec2, pdf: &mut f32) -> Vec3 {
+        if w_out.z == 0.0 {
+            return Color::ZERO;
+        }
+        let wh = self.distribution.sample_wh(w_out, sample_point);
+        if w_out.dot(wh) < 0.0 {
+            return Color::ZERO;
+        }
+        let eta = 1.0 / self.eta(w_out);
+        if !func::refract(w_out, &wh, eta, w_in) {
+            return Color::ZERO;
+        }
+        *pdf = self.pdf(*w_out, *w_in);
+        self.f(w_in, w_out)
+    }
+    fn pdf(&self, w_out: Vec3, w_in: Vec3) -> f32 {
+        if func::vec3_same_hemisphere_vec3(&w_out, &w_in) {
+            return 0.0;
+        }
+        let eta = self.eta(&w_out);
+        let wh = (w_out + w_in * eta).normalize();
+        if w_out.dot(wh) * w_in.dot(wh) > 0.0 {
+            return 0.0;
+        }
+        //半角向量到入射方向的雅可比
+        let sqrt_denom = w_out.dot(wh) + eta * w_in.dot(wh);
+        let dwh_dwi = (eta * eta * w_in.dot(wh) / (sqrt_denom * sqrt_denom)).abs();
+        self.distribution.pdf(&w_out, &wh) * dwh_dwi
+    }
+    fn get_type(&self) -> u32 {
+        BxDFType::Transmission | BxDFType::Glossy
+    }
+}
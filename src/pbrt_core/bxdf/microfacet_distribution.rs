@@ -1,6 +1,7 @@
 use std::f32::consts::PI;
 
 use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
 
 use crate::pbrt_core::{
     bxdf::func::vec3_same_hemisphere_vec3,
@@ -12,18 +13,33 @@ use super::{
     MicrofacetDistribution,
 };
 
-//使用Beckmann概率分布，适用各项同性
+//微表面分布类型，供场景文件选择
+#[derive(Deserialize, Debug, Serialize, Clone, Copy, Default)]
+pub enum DistributionType {
+    #[default]
+    TrowbridgeReitz,
+    Beckmann,
+}
+impl DistributionType {
+    pub fn create(&self, alphax: f32, alphay: f32) -> Box<dyn MicrofacetDistribution> {
+        match self {
+            Self::TrowbridgeReitz => {
+                Box::new(TrowbridgeReitzDistribution::new(alphax, alphay, false))
+            }
+            Self::Beckmann => Box::new(BeckmannDistribution::new(alphax, alphay)),
+        }
+    }
+}
+//使用Beckmann概率分布，适用各项同性，只按完整法线分布采样
 pub struct BeckmannDistribution {
     alphax: f32,
     alphay: f32,
-    sample_visible_area: bool,
 }
 impl BeckmannDistribution {
-    pub fn new(alphax: f32, alphay: f32, sample_visible_area: bool) -> Self {
+    pub fn new(alphax: f32, alphay: f32) -> Self {
         Self {
             alphax: alphax.max(0.001),
             alphay: alphay.max(0.01),
-            sample_visible_area,
         }
     }
 }
@@ -51,13 +67,13 @@ impl MicrofacetDistribution for BeckmannDistribution {
         let cos_4_theta = cos2_theta(wh) * cos2_theta(wh);
         (-tan_2_theta
             * (cos2_phi(wh) / (self.alphax * self.alphax)
-                + sin2_theta(wh) / (self.alphay * self.alphay)))
+                + sin2_phi(wh) / (self.alphay * self.alphay)))
             .exp()
             / (PI * self.alphax * self.alphay * cos_4_theta)
     }
 
     fn lamdba(&self, w: &glam::Vec3) -> f32 {
-        let abs_tan_theta = sin_theta(w).abs() / cos_theta(w);
+        let abs_tan_theta = (sin_theta(w) / cos_theta(w)).abs();
         if abs_tan_theta.is_infinite() {
             return 0.0;
         }
@@ -72,17 +88,34 @@ impl MicrofacetDistribution for BeckmannDistribution {
         (1.0 - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
     }
 
-    fn sample_wh(&self, _w_out: &glam::Vec3, _w_in: Vec2) -> glam::Vec3 {
-        todo!()
-    }
-
-    fn pdf(&self, w_out: &glam::Vec3, wh: &glam::Vec3) -> f32 {
-        if self.sample_visible_area {
-            self.d(wh) * self.g1(w_out) * w_out.dot(*wh).abs() / cos_theta(w_out).abs()
+    fn sample_wh(&self, w_out: &glam::Vec3, u: Vec2) -> glam::Vec3 {
+        let log_sample = (1.0 - u.x).ln();
+        let (tan_theta2, phi) = if self.alphax == self.alphay {
+            (-self.alphax * self.alphax * log_sample, u.y * 2.0 * PI)
         } else {
-            self.d(wh) * cos_theta(wh).abs()
+            let mut phi =
+                (self.alphay / self.alphax * (2.0 * PI * u.y + 0.5 * PI).tan()).atan();
+            if u.y > 0.5 {
+                phi += PI;
+            }
+            let (sin, cos) = phi.sin_cos();
+            let alphax2 = self.alphax * self.alphax;
+            let alphay2 = self.alphay * self.alphay;
+            (-log_sample / (cos * cos / alphax2 + sin * sin / alphay2), phi)
+        };
+        let cos_theta = 1.0 / (1.0 + tan_theta2).sqrt();
+        let sin_theta = 0.0_f32.max(1.0 - cos_theta * cos_theta).sqrt();
+        let wh = spherical_direction(sin_theta, cos_theta, phi);
+        if vec3_same_hemisphere_vec3(w_out, &wh) {
+            wh
+        } else {
+            -wh
         }
     }
+
+    fn pdf(&self, _w_out: &glam::Vec3, wh: &glam::Vec3) -> f32 {
+        self.d(wh) * cos_theta(wh).abs()
+    }
 }
 //GGX模型
 pub struct TrowbridgeReitzDistribution {
@@ -108,13 +141,13 @@ impl MicrofacetDistribution for TrowbridgeReitzDistribution {
         }
         let cos_4_theta = cos2_theta(wh) * cos2_theta(wh);
         let e = (cos2_phi(wh) / (self.alphax * self.alphax)
-            + sin2_theta(wh) / (self.alphay * self.alphay))
+            + sin2_phi(wh) / (self.alphay * self.alphay))
             * tan_2_theta;
 
         1.0 / (PI * self.alphax * self.alphay * cos_4_theta * (1.0 + e) * (1.0 + e))
     }
     fn lamdba(&self, w: &glam::Vec3) -> f32 {
-        let abs_tan_theta = sin_theta(w).abs() / cos_theta(w);
+        let abs_tan_theta = (sin_theta(w) / cos_theta(w)).abs();
         if abs_tan_theta.is_infinite() {
             return 0.0;
         }
@@ -167,3 +200,31 @@ impl MicrofacetDistribution for TrowbridgeReitzDistribution {
         }
     }
 }
+//迪士尼模型使用的GGX分布，遮挡项采用可分离形式
+pub struct DisneyMicrofacetDistribution {
+    inner: TrowbridgeReitzDistribution,
+}
+impl DisneyMicrofacetDistribution {
+    pub fn new(alphax: f32, alphay: f32) -> Self {
+        Self {
+            inner: TrowbridgeReitzDistribution::new(alphax, alphay, false),
+        }
+    }
+}
+impl MicrofacetDistribution for DisneyMicrofacetDistribution {
+    fn d(&self, wh: &Vec3) -> f32 {
+        self.inner.d(wh)
+    }
+    fn lamdba(&self, w: &Vec3) -> f32 {
+        self.inner.lamdba(w)
+    }
+    fn g(&self, w_out: &Vec3, w_in: &Vec3) -> f32 {
+        self.g1(w_out) * self.g1(w_in)
+    }
+    fn sample_wh(&self, w_out: &Vec3, u: Vec2) -> Vec3 {
+        self.inner.sample_wh(w_out, u)
+    }
+    fn pdf(&self, w_out: &Vec3, wh: &Vec3) -> f32 {
+        self.inner.pdf(w_out, wh)
+    }
+}
@@ -3,12 +3,15 @@ use std::f32::consts::FRAC_1_PI;
 use glam::{Vec2, Vec3};
 
 use self::{
+    disney::{DisneyClearcoat, DisneyDiffuse, DisneyFakeSS, DisneyRetro, DisneySheen},
+    frensnel::FrensnelSpecular,
+    microfacet::MicrofacetTransmission,
     pbr::{PbrDiff, PbrReflection},
     reflection::{LambertianReflection, MicrofacetReflection, OrenNayar},
-    specular::SpecularReflection,
+    specular::{SpecularReflection, SpecularTransmission},
 };
 
-use super::sampler::cosine_sample_hemisphere;
+use super::{bssrdf::SubsurfaceAdapter, sampler::cosine_sample_hemisphere};
 // 菲涅尔反射率
 pub mod frensnel;
 // 高光
@@ -40,7 +43,7 @@ pub trait BxDFAble {
     }
     fn pdf(&self, w_out: Vec3, w_in: Vec3) -> f32 {
         if w_out.z * w_in.z > 0.0 {
-            w_in.z * FRAC_1_PI
+            w_in.z.abs() * FRAC_1_PI
         } else {
             0.0
         }
@@ -52,7 +55,7 @@ pub trait BxDFAble {
 }
 
 // 溦表面模型
-pub trait MicrofacetDistribution {
+pub trait MicrofacetDistribution: Send + Sync {
     //法线分布函数
     fn d(&self, wh: &Vec3) -> f32;
     //
@@ -74,6 +77,15 @@ pub enum BxDF {
     PbrDiff(PbrDiff),
     PbrReflection(PbrReflection),
     MicrofacetReflection(MicrofacetReflection),
+    SpecularTransmission(SpecularTransmission),
+    FrensnelSpecular(FrensnelSpecular),
+    MicrofacetTransmission(MicrofacetTransmission),
+    DisneyDiffuse(DisneyDiffuse),
+    DisneyFakeSS(DisneyFakeSS),
+    DisneyRetro(DisneyRetro),
+    DisneySheen(DisneySheen),
+    DisneyClearcoat(DisneyClearcoat),
+    Subsurface(SubsurfaceAdapter),
 }
 impl BxDF {
     pub fn match_type(&self, flag: u32) -> bool {
@@ -86,6 +98,15 @@ impl BxDF {
             Self::MicrofacetReflection(microfacet_reflection) => {
                 microfacet_reflection.match_type(flag)
             }
+            Self::SpecularTransmission(transmission) => transmission.match_type(flag),
+            Self::FrensnelSpecular(fresnel_specular) => fresnel_specular.match_type(flag),
+            Self::MicrofacetTransmission(transmission) => transmission.match_type(flag),
+            Self::DisneyDiffuse(disney) => disney.match_type(flag),
+            Self::DisneyFakeSS(disney) => disney.match_type(flag),
+            Self::DisneyRetro(disney) => disney.match_type(flag),
+            Self::DisneySheen(disney) => disney.match_type(flag),
+            Self::DisneyClearcoat(disney) => disney.match_type(flag),
+            Self::Subsurface(adapter) => adapter.match_type(flag),
             _ => todo!(),
         }
     }
@@ -99,6 +120,15 @@ impl BxDF {
             Self::MicrofacetReflection(microfacet_reflection) => {
                 microfacet_reflection.f(w_in, w_out)
             }
+            Self::SpecularTransmission(transmission) => transmission.f(w_in, w_out),
+            Self::FrensnelSpecular(fresnel_specular) => fresnel_specular.f(w_in, w_out),
+            Self::MicrofacetTransmission(transmission) => transmission.f(w_in, w_out),
+            Self::DisneyDiffuse(disney) => disney.f(w_in, w_out),
+            Self::DisneyFakeSS(disney) => disney.f(w_in, w_out),
+            Self::DisneyRetro(disney) => disney.f(w_in, w_out),
+            Self::DisneySheen(disney) => disney.f(w_in, w_out),
+            Self::DisneyClearcoat(disney) => disney.f(w_in, w_out),
+            Self::Subsurface(adapter) => adapter.f(w_in, w_out),
             _ => todo!(),
         }
     }
@@ -112,6 +142,19 @@ impl BxDF {
             Self::MicrofacetReflection(microfacet_reflection) => {
                 microfacet_reflection.sample_f(wi, w_out, u, pdf)
             }
+            Self::SpecularTransmission(transmission) => transmission.sample_f(wi, w_out, u, pdf),
+            Self::FrensnelSpecular(fresnel_specular) => {
+                fresnel_specular.sample_f(wi, w_out, u, pdf)
+            }
+            Self::MicrofacetTransmission(transmission) => {
+                transmission.sample_f(wi, w_out, u, pdf)
+            }
+            Self::DisneyDiffuse(disney) => disney.sample_f(wi, w_out, u, pdf),
+            Self::DisneyFakeSS(disney) => disney.sample_f(wi, w_out, u, pdf),
+            Self::DisneyRetro(disney) => disney.sample_f(wi, w_out, u, pdf),
+            Self::DisneySheen(disney) => disney.sample_f(wi, w_out, u, pdf),
+            Self::DisneyClearcoat(disney) => disney.sample_f(wi, w_out, u, pdf),
+            Self::Subsurface(adapter) => adapter.sample_f(wi, w_out, u, pdf),
             _ => todo!(),
         }
     }
@@ -123,6 +166,15 @@ impl BxDF {
             Self::PbrReflection(reflection) => reflection.get_type(),
             Self::MicrofacetReflection(microfacet_reflection) => microfacet_reflection.get_type(),
             Self::SpecularReflection(specular) => specular.get_type(),
+            Self::SpecularTransmission(transmission) => transmission.get_type(),
+            Self::FrensnelSpecular(fresnel_specular) => fresnel_specular.get_type(),
+            Self::MicrofacetTransmission(transmission) => transmission.get_type(),
+            Self::DisneyDiffuse(disney) => disney.get_type(),
+            Self::DisneyFakeSS(disney) => disney.get_type(),
+            Self::DisneyRetro(disney) => disney.get_type(),
+            Self::DisneySheen(disney) => disney.get_type(),
+            Self::DisneyClearcoat(disney) => disney.get_type(),
+            Self::Subsurface(adapter) => adapter.get_type(),
         }
     }
     pub fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f32 {
@@ -135,6 +187,15 @@ impl BxDF {
                 microfacet_reflection.pdf(*wo, *wi)
             }
             Self::SpecularReflection(specular) => specular.pdf(*wo, *wi),
+            Self::SpecularTransmission(transmission) => transmission.pdf(*wo, *wi),
+            Self::FrensnelSpecular(fresnel_specular) => fresnel_specular.pdf(*wo, *wi),
+            Self::MicrofacetTransmission(transmission) => transmission.pdf(*wo, *wi),
+            Self::DisneyDiffuse(disney) => disney.pdf(*wo, *wi),
+            Self::DisneyFakeSS(disney) => disney.pdf(*wo, *wi),
+            Self::DisneyRetro(disney) => disney.pdf(*wo, *wi),
+            Self::DisneySheen(disney) => disney.pdf(*wo, *wi),
+            Self::DisneyClearcoat(disney) => disney.pdf(*wo, *wi),
+            Self::Subsurface(adapter) => adapter.pdf(*wo, *wi),
         }
     }
 }
@@ -188,6 +249,8 @@ impl From<BxDFType> for u32 {
 
 pub(crate) mod func {
     use glam::Vec3;
+
+    use crate::pbrt_core::tool::func::lerp;
     //计算菲涅尔反射率，介电材料
     //eta_i 入射折射率
     //eta_t 出射折射率
@@ -254,7 +317,7 @@ pub(crate) mod func {
     #[allow(unused)]
     #[inline]
     pub fn fr_schlick(r0: f32, cos_theta: f32) -> f32 {
-        schlick_weight(cos_theta)
+        lerp(schlick_weight(cos_theta), r0, 1.0)
     }
     #[allow(unused)]
 
@@ -281,12 +344,13 @@ pub(crate) mod func {
         *wt = (-(*wi) * eta) + *n * (eta * cos_theta_i - cos_theta_t);
         true
     }
+    //使法线与wo位于同一半球
     #[inline]
-    pub fn _face_forward(normal: Vec3, wo: Vec3) -> Vec3 {
-        if normal.dot(wo) > 0.0 {
-            normal
+    pub fn face_forward(normal: Vec3, wo: Vec3) -> Vec3 {
+        if normal.dot(wo) < 0.0 {
+            -normal
         } else {
-            wo
+            normal
         }
     }
     #[inline]
@@ -295,7 +359,7 @@ pub(crate) mod func {
     }
     #[inline]
     pub fn sin2_theta(wi: &Vec3) -> f32 {
-        (1.0 - wi.z).max(0.0)
+        (1.0 - wi.z * wi.z).max(0.0)
     }
     #[inline]
     pub fn cos_theta(wi: &Vec3) -> f32 {
@@ -318,9 +382,9 @@ pub(crate) mod func {
     pub fn sin_phi(w: &Vec3) -> f32 {
         let sin_theta = sin_theta(w);
         if sin_theta == 0.0 {
-            1.0
+            0.0
         } else {
-            (w.x / sin_theta).clamp(-1.0, 1.0)
+            (w.y / sin_theta).clamp(-1.0, 1.0)
         }
     }
     pub fn sin2_phi(w: &Vec3) -> f32 {
@@ -25,7 +25,7 @@ impl BxDFAble for LambertianReflection {
     }
     fn pdf(&self, w_out: Vec3, w_in: Vec3) -> f32 {
         if same_hemisphere(w_out, w_in) {
-            cos_theta(&w_in).abs()
+            cos_theta(&w_in).abs() * FRAC_1_PI
         } else {
             0.0
         }
@@ -96,6 +96,9 @@ impl BxDFAble for MicrofacetReflection {
     fn match_type(&self, flag: u32) -> bool {
         (BxDFType::Reflection | BxDFType::Glossy) & flag > 0
     }
+    fn get_type(&self) -> u32 {
+        BxDFType::Reflection | BxDFType::Glossy
+    }
 
     fn f(&self, w_in: &Vec3, w_out: &Vec3) -> Vec3 {
         let cos_o = cos_theta(w_out).abs();
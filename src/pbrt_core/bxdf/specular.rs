@@ -2,7 +2,11 @@ use std::ops::Mul;
 
 use glam::f32::Vec3;
 
-use super::{frensnel::Fresnel, func::cos_theta, BxDFAble, BxDFType, TransportMode};
+use super::{
+    frensnel::{DielectricFresnel, Fresnel},
+    func::{self, cos_theta},
+    BxDFAble, BxDFType, TransportMode,
+};
 //镜面反射
 pub struct SpecularReflection {
     //光谱颜色
@@ -42,12 +46,23 @@ impl BxDFAble for SpecularReflection {
 //镜面透射
 pub struct SpecularTransmission {
     //上方折射率
-    _eta_a: f32,
+    eta_a: f32,
     //下方折射率
-    _eta_b: f32,
-    _fresnel: Fresnel,
-    _mode: TransportMode,
-    _t: Vec3,
+    eta_b: f32,
+    fresnel: DielectricFresnel,
+    mode: TransportMode,
+    t: Vec3,
+}
+impl SpecularTransmission {
+    pub fn new(t: Vec3, eta_a: f32, eta_b: f32, mode: TransportMode) -> Self {
+        Self {
+            eta_a,
+            eta_b,
+            fresnel: DielectricFresnel::new(eta_a, eta_b),
+            mode,
+            t,
+        }
+    }
 }
 impl BxDFAble for SpecularTransmission {
     fn f(&self, _w_in: &Vec3, _w_out: &Vec3) -> Vec3 {
@@ -56,6 +71,35 @@ impl BxDFAble for SpecularTransmission {
     fn match_type(&self, flag: u32) -> bool {
         ((BxDFType::Specular | BxDFType::Transmission) & flag) != 0
     }
+    fn sample_f(
+        &self,
+        w_in: &mut Vec3,
+        w_out: &Vec3,
+        _sample_point: glam::Vec2,
+        pdf: &mut f32,
+    ) -> Vec3 {
+        //判断光线是从外部进入还是从内部射出
+        let entering = cos_theta(w_out) > 0.0;
+        let (eta_i, eta_t) = if entering {
+            (self.eta_a, self.eta_b)
+        } else {
+            (self.eta_b, self.eta_a)
+        };
+        let n = func::face_forward(Vec3::Z, *w_out);
+        if !func::refract(w_out, &n, eta_i / eta_t, w_in) {
+            return Vec3::ZERO;
+        }
+        *pdf = 1.0;
+        let mut ft = self.t * (Vec3::ONE - self.fresnel.evaluate(cos_theta(w_in)));
+        //辐射度在折射时会被压缩
+        if self.mode == TransportMode::Radiance {
+            ft *= (eta_i * eta_i) / (eta_t * eta_t);
+        }
+        ft / cos_theta(w_in).abs()
+    }
+    fn pdf(&self, _w_out: Vec3, _w_in: Vec3) -> f32 {
+        0.0
+    }
     fn get_type(&self)->u32 {
         BxDFType::Specular | BxDFType::Transmission
     }
@@ -12,7 +12,16 @@ pub struct Camera {
     //相机-世界
     camera_to_world: Mat4,
     //相机模型
-    mode:CameraMode
+    mode:CameraMode,
+    //世界-观察空间
+    world_to_view: Mat4,
+    fov: f32,
+    //胶片分辨率
+    size: Vec2,
+    //相机所在的介质
+    pub medium: Option<usize>,
+    //正交相机视口的半宽与半高，None时按宽高比取±aspect×±1
+    extent: Option<Vec2>,
 }
 
 #[derive(Debug,Default,Clone, Copy)]
@@ -43,25 +52,49 @@ impl Camera {
         );
         mat.inverse()
     }
+    //投影矩阵，fov为竖直方向视角，水平方向按宽高比展开
+    fn projection(mode: CameraMode, fov: f32, size: Vec2, extent: Option<Vec2>) -> Mat4 {
+        let aspect = size.x / size.y;
+        match mode {
+            CameraMode::O => {
+                let extent = extent.unwrap_or(Vec2::new(aspect, 1.0));
+                Mat4::orthographic_lh(-extent.x, extent.x, -extent.y, extent.y, 0.01, 1000.0)
+            }
+            CameraMode::P => Mat4::perspective_lh(fov.to_radians(), aspect, 0.01, 1000.0),
+        }
+    }
     //构造
     pub fn new(eye: Vec3, center: Vec3, up: Vec3, size: Vec2, mode: CameraMode, fov: f32)->Self{
         let look_at_lh = Mat4::look_at_lh(eye, center,up);
-        let p = match mode {
-            CameraMode::O => Mat4::orthographic_lh(-1.0, 1.0, -1.0, 1.0, 0.01, 1000.0),
-            CameraMode::P => Mat4::perspective_lh(fov.to_radians(), 1.0, 0.01, 1000.0),
-        };
-        let world_to_camera = p*look_at_lh;
+        let world_to_camera = Self::projection(mode, fov, size, None)*look_at_lh;
         let screen_to_camera=Self::computer_viewport(size);
         Self{
             eye,
             screen_to_camera,
             camera_to_world:world_to_camera.inverse(),
-            mode
+            mode,
+            world_to_view: look_at_lh,
+            fov,
+            size,
+            medium: None,
+            extent: None,
         }
     }
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+    //指定正交相机视口的半宽与半高
+    pub fn set_extent(&mut self, extent: Vec2) {
+        self.extent = Some(extent);
+        self.reset_size(self.size);
+    }
     pub fn reset_size(&mut self,size:Vec2){
         let screen_to_camera=Self::computer_viewport(size);
         self.screen_to_camera=screen_to_camera;
+        let world_to_camera =
+            Self::projection(self.mode, self.fov, size, self.extent) * self.world_to_view;
+        self.camera_to_world = world_to_camera.inverse();
+        self.size = size;
     }
     pub fn generate_ray(&self,sample:CameraSample)->RayDiff{
         let p = self.screen_to_camera.transform_point3(sample.film_point.extend(0.0));
@@ -79,4 +112,59 @@ impl Camera {
         }
     
     }
+    //以下为双向路径追踪使用的重要性函数，只支持针孔透视相机
+    pub fn position(&self) -> Vec3 {
+        self.eye
+    }
+    fn forward(&self) -> Vec3 {
+        self.world_to_view.inverse().transform_vector3(Vec3::Z).normalize()
+    }
+    //距相机为1处的像平面面积
+    fn image_area(&self) -> f32 {
+        let h = (self.fov.to_radians() / 2.0).tan();
+        4.0 * h * h * self.size.x / self.size.y
+    }
+    //方向dir对应的胶片坐标，不在画面内时返回None
+    pub fn film_point(&self, dir: Vec3) -> Option<Vec2> {
+        if !matches!(self.mode, CameraMode::P) || dir.dot(self.forward()) <= 0.0 {
+            return None;
+        }
+        let ndc = self.camera_to_world.inverse().project_point3(self.eye + dir);
+        let film = self
+            .screen_to_camera
+            .inverse()
+            .transform_point3(ndc.truncate().extend(0.0))
+            .truncate();
+        if film.x < 0.0 || film.y < 0.0 || film.x >= self.size.x || film.y >= self.size.y {
+            None
+        } else {
+            Some(film)
+        }
+    }
+    //沿dir离开相机的光线的重要性与胶片坐标
+    pub fn we(&self, dir: Vec3) -> Option<(f32, Vec2)> {
+        let film = self.film_point(dir)?;
+        let cos = dir.normalize().dot(self.forward());
+        Some((1.0 / (self.image_area() * cos * cos * cos * cos), film))
+    }
+    //相机光线方向的立体角pdf，位置为delta分布
+    pub fn pdf_we(&self, dir: Vec3) -> f32 {
+        if self.film_point(dir).is_none() {
+            return 0.0;
+        }
+        let cos = dir.normalize().dot(self.forward());
+        1.0 / (self.image_area() * cos * cos * cos)
+    }
+    //从场景点p向相机采样，返回重要性、指向相机的方向、pdf与胶片坐标
+    pub fn sample_wi(&self, p: Vec3) -> Option<(f32, Vec3, f32, Vec2)> {
+        let wi = self.eye - p;
+        let dist2 = wi.length_squared();
+        if dist2 == 0.0 {
+            return None;
+        }
+        let wi = wi / dist2.sqrt();
+        let (we, film) = self.we(-wi)?;
+        let pdf = dist2 / (-wi).dot(self.forward());
+        Some((we, wi, pdf, film))
+    }
 }
@@ -0,0 +1,700 @@
+use glam::{Vec2, Vec3};
+
+use crate::pbrt_core::{
+    bxdf::{BxDFType, TransportMode},
+    light::{Light, LightAble, LightType},
+    primitive::Primitive,
+    sampler::Sampler,
+    tool::{
+        color::Color, sence::Sence, InteractionCommon, Ray, RayDiff, SurfaceInteraction, Visibility,
+    },
+};
+
+use super::IntegratorAble;
+
+//双向路径追踪积分器
+//环境光与平行光按无限远光源处理：光路径从覆盖场景的圆盘出发，相机路径逃逸时以一个无限远光源顶点结束
+pub struct BdptIntegrator {
+    //最大反弹次数
+    max_depth: usize,
+}
+
+impl BdptIntegrator {
+    pub fn new(max_depth: usize) -> Self {
+        Self { max_depth }
+    }
+    //计算一条相机光线的贡献，光线追踪(t=1)的贡献以胶片坐标记入splats
+    pub fn li(
+        &self,
+        ray: RayDiff,
+        sence: &Sence,
+        sampler: &mut Sampler,
+        splats: &mut Vec<(Vec2, Color)>,
+    ) -> Color {
+        let mut camera_path = Vec::with_capacity(self.max_depth + 2);
+        camera_path.push(Vertex::camera(ray.o.origin));
+        let pdf_dir = sence.camera.pdf_we(ray.o.dir);
+        let mut l = self.random_walk(
+            sence,
+            ray,
+            Color::ONE,
+            pdf_dir,
+            self.max_depth + 1,
+            TransportMode::Radiance,
+            sampler,
+            &mut camera_path,
+        );
+        let light_path = self.light_subpath(sence, sampler);
+        for t in 1..=camera_path.len() {
+            //s=1时连接新采样的光源点，即使光路径为空也要计算
+            for s in 0..=light_path.len().max(1) {
+                let depth = s + t;
+                if (s == 1 && t == 1) || depth < 2 || depth - 2 > self.max_depth {
+                    continue;
+                }
+                //逃逸顶点只能作为s=0的光源端点
+                if s != 0 && matches!(camera_path[t - 1].kind, VertexKind::Escaped) {
+                    continue;
+                }
+                let mut film_point = None;
+                let l_path = self.connect(
+                    sence,
+                    &light_path,
+                    &camera_path,
+                    s,
+                    t,
+                    sampler,
+                    &mut film_point,
+                );
+                if t == 1 {
+                    if let Some(film_point) = film_point {
+                        if !l_path.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+                            splats.push((film_point, l_path));
+                        }
+                    }
+                } else {
+                    l += l_path;
+                }
+            }
+        }
+        l
+    }
+    //从光源出发的子路径
+    fn light_subpath<'a>(&self, sence: &'a Sence, sampler: &mut Sampler) -> Vec<Vertex<'a>> {
+        let mut path = Vec::with_capacity(self.max_depth + 1);
+        if sence.light.is_empty() || self.max_depth == 0 {
+            return path;
+        }
+        let light = &sence.light[sampler.sample_index(sence.light.len())];
+        let light_pdf = 1.0 / sence.light.len() as f32;
+        let (u1, u2) = (sampler.sample_2d_d(), sampler.sample_2d_d());
+        let mut light_common = InteractionCommon::default();
+        let mut dir = Vec3::ZERO;
+        let (mut pdf_pos, mut pdf_dir) = (0.0, 0.0);
+        let le = light.sample_le(
+            u1,
+            u2,
+            &mut light_common,
+            &mut dir,
+            &mut pdf_pos,
+            &mut pdf_dir,
+        );
+        if pdf_pos == 0.0 || pdf_dir == 0.0 || le.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+            return path;
+        }
+        let vertex = Vertex::light(
+            light,
+            light_common.p,
+            light_common.normal,
+            le,
+            pdf_pos * light_pdf,
+        );
+        //点光源没有法线，余弦项取1
+        let cos = if vertex.is_on_surface() {
+            vertex.n.dot(dir).abs()
+        } else {
+            1.0
+        };
+        let beta = le * cos / (light_pdf * pdf_pos * pdf_dir);
+        let origin = light_common.p + vertex.n * Self::OFFSET;
+        let infinite = vertex.is_infinite_light();
+        path.push(vertex);
+        self.random_walk(
+            sence,
+            RayDiff::new(Ray::new(origin, dir)),
+            beta,
+            pdf_dir,
+            self.max_depth,
+            TransportMode::Importance,
+            sampler,
+            &mut path,
+        );
+        //无限远光源的起点在圆盘上，第一个表面顶点的面积pdf只与圆盘pdf有关，起点本身按方向计pdf
+        if infinite {
+            if let Some(next) = path.get_mut(1) {
+                next.pdf_fwd = pdf_pos;
+                if next.is_on_surface() {
+                    next.pdf_fwd *= next.n.dot(dir).abs();
+                }
+            }
+            path[0].pdf_fwd = infinite_light_density(sence, dir);
+        }
+        path
+    }
+    const OFFSET: f32 = 0.0001;
+    //随机游走生成子路径，返回相机路径上非光源材质的自发光
+    #[allow(clippy::too_many_arguments)]
+    fn random_walk<'a>(
+        &self,
+        sence: &'a Sence,
+        mut ray: RayDiff,
+        mut beta: Color,
+        pdf: f32,
+        max_depth: usize,
+        mode: TransportMode,
+        sampler: &mut Sampler,
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Color {
+        let mut l = Color::ZERO;
+        if max_depth == 0 {
+            return l;
+        }
+        let mut pdf_fwd = pdf;
+        let mut bounces = 0;
+        loop {
+            let Some(mut si) = sence.interacect(ray) else {
+                if matches!(mode, TransportMode::Radiance) {
+                    path.push(Vertex::escaped(&ray, beta, pdf_fwd));
+                }
+                break;
+            };
+            //材质自发光不属于光源，不参与多重重要性采样
+            if matches!(mode, TransportMode::Radiance) {
+                l += beta * si.emission();
+            }
+            si.compute_scattering(ray, mode);
+            let wo = -ray.o.dir;
+            let mut vertex = Vertex::surface(si, wo, beta);
+            vertex.pdf_fwd = path[path.len() - 1].convert_density(pdf_fwd, &vertex);
+            let stop = vertex.is_light() || vertex.bsdf().is_none();
+            path.push(vertex);
+            bounces += 1;
+            if stop || bounces >= max_depth {
+                break;
+            }
+            let vertex = &path[path.len() - 1];
+            let (Some(bsdf), VertexKind::Surface(si)) = (vertex.bsdf(), &vertex.kind) else {
+                break;
+            };
+            let mut wi = Vec3::ZERO;
+            let mut sampled_type = 0;
+            let f = bsdf.sample_f(
+                &wo,
+                &mut wi,
+                sampler.sample_2d_d(),
+                &mut pdf_fwd,
+                BxDFType::All.into(),
+                &mut sampled_type,
+            );
+            if pdf_fwd <= 0.0 || f.is_nan() || f.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+                break;
+            }
+            beta *= f * wi.dot(si.shading.n).abs() / pdf_fwd;
+            if matches!(mode, TransportMode::Importance) {
+                beta *= correct_shading_normal(si, wo, wi);
+            }
+            let mut pdf_rev = bsdf.pdf(&wi, &wo, BxDFType::All.into());
+            let specular = sampled_type & BxDFType::Specular as u32 != 0;
+            if specular {
+                pdf_fwd = 0.0;
+                pdf_rev = 0.0;
+            }
+            ray = si.spawn_ray(&wi);
+            let len = path.len();
+            path[len - 1].delta = specular;
+            path[len - 2].pdf_rev = path[len - 1].convert_density(pdf_rev, &path[len - 2]);
+        }
+        l
+    }
+    //连接光路径前s个顶点与相机路径前t个顶点
+    #[allow(clippy::too_many_arguments)]
+    fn connect<'a>(
+        &self,
+        sence: &'a Sence,
+        light_path: &[Vertex<'a>],
+        camera_path: &[Vertex<'a>],
+        s: usize,
+        t: usize,
+        sampler: &mut Sampler,
+        film_point: &mut Option<Vec2>,
+    ) -> Color {
+        let mut l = Color::ZERO;
+        let mut sampled = None;
+        if s == 0 {
+            //相机路径直接击中光源
+            let pt = &camera_path[t - 1];
+            if pt.is_light() {
+                l = pt.le(sence, &camera_path[t - 2]) * pt.beta;
+            }
+        } else if t == 1 {
+            //光路径顶点连接到相机
+            let qs = &light_path[s - 1];
+            if !qs.connectible() {
+                return Color::ZERO;
+            }
+            let camera = &sence.camera;
+            if let Some((we, wi, pdf, point)) = camera.sample_wi(qs.p) {
+                if pdf > 0.0 && we > 0.0 {
+                    let vertex = Vertex::camera_sampled(camera.position(), Color::splat(we / pdf));
+                    l = qs.beta * qs.f(&vertex, TransportMode::Importance) * vertex.beta;
+                    if qs.is_on_surface() {
+                        l *= wi.dot(qs.ns()).abs();
+                    }
+                    if !l.abs_diff_eq(Color::ZERO, f32::EPSILON) && !qs.visible(&vertex, sence) {
+                        l = Color::ZERO;
+                    }
+                    *film_point = Some(point);
+                    sampled = Some(vertex);
+                }
+            }
+        } else if s == 1 {
+            //相机路径顶点采样光源
+            let pt = &camera_path[t - 1];
+            if !pt.connectible() {
+                return Color::ZERO;
+            }
+            let VertexKind::Surface(si) = &pt.kind else {
+                return Color::ZERO;
+            };
+            let light = &sence.light[sampler.sample_index(sence.light.len())];
+            let light_pdf = 1.0 / sence.light.len() as f32;
+            let mut light_common = InteractionCommon::default();
+            let mut wi = Vec3::ZERO;
+            let mut pdf = 0.0;
+            let mut vis = Visibility::default();
+            let li = light.sample_li(
+                &si.common,
+                &mut light_common,
+                sampler.sample_2d_d(),
+                &mut wi,
+                &mut pdf,
+                &mut vis,
+            );
+            match light.get_type() {
+                //无限远光源的pdf保持立体角测度，法线即传播方向
+                LightType::Infinite | LightType::DeltaDirection => {}
+                LightType::Area if pdf > 0.0 => {
+                    let n = light_common.normal.normalize();
+                    let cos = n.dot(wi).abs();
+                    pdf = if cos > 0.0 {
+                        pdf * light_common.p.distance_squared(si.common.p) / cos
+                    } else {
+                        0.0
+                    };
+                    light_common.normal = n;
+                }
+                LightType::Area => {}
+                LightType::DeltaPosition => light_common.normal = Vec3::ZERO,
+            }
+            if pdf > 0.0 && !li.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+                let mut vertex = Vertex::light(
+                    light,
+                    light_common.p,
+                    light_common.normal,
+                    li / (pdf * light_pdf),
+                    0.0,
+                );
+                vertex.pdf_fwd = vertex.pdf_light_origin(sence, pt);
+                l = pt.beta * pt.f(&vertex, TransportMode::Radiance) * vertex.beta;
+                l *= wi.dot(pt.ns()).abs();
+                if !l.abs_diff_eq(Color::ZERO, f32::EPSILON) && !vis.is_vis(sence) {
+                    l = Color::ZERO;
+                }
+                sampled = Some(vertex);
+            }
+        } else {
+            let (qs, pt) = (&light_path[s - 1], &camera_path[t - 1]);
+            if qs.connectible() && pt.connectible() {
+                l = qs.beta
+                    * qs.f(pt, TransportMode::Importance)
+                    * pt.f(qs, TransportMode::Radiance)
+                    * pt.beta;
+                if !l.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+                    l *= qs.g(pt, sence);
+                }
+            }
+        }
+        if l.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+            return Color::ZERO;
+        }
+        l * mis_weight(sence, light_path, camera_path, sampled.as_ref(), s, t)
+    }
+}
+
+impl IntegratorAble for BdptIntegrator {
+    fn is_next(&self, dept: &mut usize, _beta: Color, _sampler: &mut Sampler) -> Option<f32> {
+        *dept += 1;
+        (*dept <= self.max_depth).then_some(1.0)
+    }
+    //不记录光线追踪的泼溅，只用于逐像素调试渲染
+    fn fi(
+        &self,
+        ray: RayDiff,
+        sence: &Sence,
+        sampler: &mut Sampler,
+        #[cfg(debug_assertions)] _i: &mut i32,
+    ) -> Color {
+        self.li(ray, sence, sampler, &mut vec![])
+    }
+}
+
+//路径顶点绝大多数是表面交点，不做装箱
+#[allow(clippy::large_enum_variant)]
+enum VertexKind<'a> {
+    Camera,
+    //光路径的起点
+    Light(&'a Light),
+    //相机光线逃逸，代表场景中的全部环境光
+    Escaped,
+    Surface(SurfaceInteraction<'a>),
+}
+
+struct Vertex<'a> {
+    kind: VertexKind<'a>,
+    p: Vec3,
+    //几何法线，相机与点光源为零，无限远光源为光线传播方向
+    n: Vec3,
+    //指向路径上一个顶点
+    wo: Vec3,
+    beta: Color,
+    delta: bool,
+    //面积测度下，沿路径方向与反方向生成该顶点的pdf
+    pdf_fwd: f32,
+    pdf_rev: f32,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(p: Vec3) -> Self {
+        Self::camera_sampled(p, Color::ONE)
+    }
+    fn camera_sampled(p: Vec3, beta: Color) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            p,
+            n: Vec3::ZERO,
+            wo: Vec3::ZERO,
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+    fn light(light: &'a Light, p: Vec3, n: Vec3, beta: Color, pdf_fwd: f32) -> Self {
+        Self {
+            kind: VertexKind::Light(light),
+            p,
+            n: n.normalize_or_zero(),
+            wo: Vec3::ZERO,
+            beta,
+            delta: false,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+    //逃逸顶点放在光线前方单位距离处，只用于确定方向
+    fn escaped(ray: &RayDiff, beta: Color, pdf_fwd: f32) -> Self {
+        Self {
+            kind: VertexKind::Escaped,
+            p: ray.o.origin + ray.o.dir,
+            n: -ray.o.dir,
+            wo: Vec3::ZERO,
+            beta,
+            delta: false,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+    fn surface(si: SurfaceInteraction<'a>, wo: Vec3, beta: Color) -> Self {
+        Self {
+            p: si.common.p,
+            n: si.common.normal.normalize_or_zero(),
+            kind: VertexKind::Surface(si),
+            wo,
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+    fn bsdf(&self) -> Option<&crate::pbrt_core::material::BSDF> {
+        match &self.kind {
+            VertexKind::Surface(si) => si.bsdf.as_ref(),
+            _ => None,
+        }
+    }
+    //着色法线，用于余弦项
+    fn ns(&self) -> Vec3 {
+        match &self.kind {
+            VertexKind::Surface(si) => si.shading.n,
+            _ => self.n,
+        }
+    }
+    fn is_on_surface(&self) -> bool {
+        self.n != Vec3::ZERO
+    }
+    fn is_light(&self) -> bool {
+        match &self.kind {
+            VertexKind::Light(_) | VertexKind::Escaped => true,
+            VertexKind::Surface(si) => si.light.is_some(),
+            VertexKind::Camera => false,
+        }
+    }
+    fn is_infinite_light(&self) -> bool {
+        match &self.kind {
+            VertexKind::Light(light) => matches!(
+                light.get_type(),
+                LightType::Infinite | LightType::DeltaDirection
+            ),
+            VertexKind::Escaped => true,
+            _ => false,
+        }
+    }
+    fn is_delta_light(&self) -> bool {
+        matches!(&self.kind, VertexKind::Light(light) if LightType::is_delta(light.get_type()))
+    }
+    fn get_light(&self, sence: &'a Sence) -> Option<&'a Light> {
+        match &self.kind {
+            VertexKind::Light(light) => Some(light),
+            VertexKind::Surface(si) => si.light.map(|light| &sence.light[light.get_index()]),
+            VertexKind::Camera | VertexKind::Escaped => None,
+        }
+    }
+    fn connectible(&self) -> bool {
+        match &self.kind {
+            VertexKind::Camera => true,
+            VertexKind::Light(light) => !LightType::is_direction(light.get_type()),
+            VertexKind::Surface(si) => si.bsdf.as_ref().is_some_and(|bsdf| !bsdf.is_specular()),
+            VertexKind::Escaped => false,
+        }
+    }
+    //从本顶点散射到next的BSDF值，光路径上的顶点需做着色法线修正
+    fn f(&self, next: &Vertex, mode: TransportMode) -> Color {
+        let wi = (next.p - self.p).normalize();
+        let (Some(bsdf), VertexKind::Surface(si)) = (self.bsdf(), &self.kind) else {
+            return Color::ZERO;
+        };
+        let f = bsdf.f(&self.wo, &wi, BxDFType::All.into());
+        match mode {
+            TransportMode::Importance => f * correct_shading_normal(si, self.wo, wi),
+            TransportMode::Radiance => f,
+        }
+    }
+    //发光顶点向to发出的辐射度
+    fn le(&self, sence: &Sence, to: &Vertex) -> Color {
+        match &self.kind {
+            VertexKind::Surface(si) => si.le_dir(self.p, (self.p - to.p).normalize()),
+            VertexKind::Escaped => sence.sample_env_light(&RayDiff::new(Ray::new(to.p, -self.n))),
+            _ => Color::ZERO,
+        }
+    }
+    fn g(&self, other: &Vertex, sence: &Sence) -> f32 {
+        if !self.visible(other, sence) {
+            return 0.0;
+        }
+        let d = self.p - other.p;
+        let mut g = 1.0 / d.length_squared();
+        let d = d.normalize();
+        if self.is_on_surface() {
+            g *= self.ns().dot(d).abs();
+        }
+        if other.is_on_surface() {
+            g *= other.ns().dot(d).abs();
+        }
+        g
+    }
+    fn visible(&self, other: &Vertex, sence: &Sence) -> bool {
+        let common = |v: &Vertex| InteractionCommon::new(Vec3::ZERO, v.p, v.n, 0.0, Vec2::ZERO);
+        Visibility {
+            a: common(self),
+            b: common(other),
+        }
+        .is_vis(sence)
+    }
+    //立体角pdf转换为next处的面积pdf
+    fn convert_density(&self, pdf: f32, next: &Vertex) -> f32 {
+        //无限远光源顶点保持立体角测度
+        if next.is_infinite_light() {
+            return pdf;
+        }
+        let w = next.p - self.p;
+        let dist2 = w.length_squared();
+        if dist2 == 0.0 {
+            return 0.0;
+        }
+        let mut pdf = pdf / dist2;
+        if next.is_on_surface() {
+            pdf *= next.n.dot(w / dist2.sqrt()).abs();
+        }
+        pdf
+    }
+    //从本顶点(上一个顶点为prev)采样到next的面积pdf
+    fn pdf(&self, sence: &'a Sence, prev: Option<&Vertex>, next: &Vertex) -> f32 {
+        let wn = (next.p - self.p).normalize();
+        let pdf = match &self.kind {
+            VertexKind::Light(_) | VertexKind::Escaped => return self.pdf_light(sence, next),
+            VertexKind::Camera => sence.camera.pdf_we(wn),
+            VertexKind::Surface(si) => {
+                let (Some(prev), Some(bsdf)) = (prev, &si.bsdf) else {
+                    return 0.0;
+                };
+                let wp = (prev.p - self.p).normalize();
+                bsdf.pdf(&wp, &wn, BxDFType::All.into())
+            }
+        };
+        self.convert_density(pdf, next)
+    }
+    //光源顶点发射到v的面积pdf
+    fn pdf_light(&self, sence: &'a Sence, v: &Vertex) -> f32 {
+        if self.is_infinite_light() {
+            //起点在覆盖场景的圆盘上均匀分布，各无限远光源的圆盘相同
+            let Some(light) = self.get_light(sence).or_else(|| sence.env.first().copied()) else {
+                return 0.0;
+            };
+            let common = InteractionCommon::new(Vec3::ZERO, self.p, self.n, 0.0, Vec2::ZERO);
+            let (mut pdf_pos, mut pdf_dir) = (0.0, 0.0);
+            light.pdf_le(&common, &self.n, &mut pdf_pos, &mut pdf_dir);
+            if v.is_on_surface() {
+                pdf_pos *= v.n.dot(self.n).abs();
+            }
+            return pdf_pos;
+        }
+        let Some(light) = self.get_light(sence) else {
+            return 0.0;
+        };
+        let w = v.p - self.p;
+        let dist2 = w.length_squared();
+        if dist2 == 0.0 {
+            return 0.0;
+        }
+        let w = w / dist2.sqrt();
+        let common = InteractionCommon::new(Vec3::ZERO, self.p, self.n, 0.0, Vec2::ZERO);
+        let (mut pdf_pos, mut pdf_dir) = (0.0, 0.0);
+        light.pdf_le(&common, &w, &mut pdf_pos, &mut pdf_dir);
+        let mut pdf = pdf_dir / dist2;
+        if v.is_on_surface() {
+            pdf *= v.n.dot(w).abs();
+        }
+        pdf
+    }
+    //光路径选中该光源并采样到该点的面积pdf
+    fn pdf_light_origin(&self, sence: &'a Sence, v: &Vertex) -> f32 {
+        if self.is_infinite_light() {
+            return infinite_light_density(sence, self.n);
+        }
+        let Some(light) = self.get_light(sence) else {
+            return 0.0;
+        };
+        let w = (v.p - self.p).normalize();
+        let common = InteractionCommon::new(Vec3::ZERO, self.p, self.n, 0.0, Vec2::ZERO);
+        let (mut pdf_pos, mut pdf_dir) = (0.0, 0.0);
+        light.pdf_le(&common, &w, &mut pdf_pos, &mut pdf_dir);
+        pdf_pos / sence.light.len() as f32
+    }
+}
+
+//光路径选中某个环境光并沿dir方向发射的立体角pdf之和
+fn infinite_light_density(sence: &Sence, dir: Vec3) -> f32 {
+    let si = SurfaceInteraction::default();
+    sence.env.iter().map(|light| light.pdf_li(&si, &dir)).sum::<f32>() / sence.light.len() as f32
+}
+
+//光路径使用着色法线时的伴随BSDF修正
+fn correct_shading_normal(si: &SurfaceInteraction, wo: Vec3, wi: Vec3) -> f32 {
+    let ng = si.common.normal.normalize();
+    let num = wo.dot(si.shading.n).abs() * wi.dot(ng).abs();
+    let denom = wo.dot(ng).abs() * wi.dot(si.shading.n).abs();
+    if denom == 0.0 {
+        0.0
+    } else {
+        num / denom
+    }
+}
+
+//按幂启发式(指数2)计算策略(s,t)的权重，sampled为连接时新采样的端点
+fn mis_weight<'a>(
+    sence: &'a Sence,
+    light_path: &[Vertex<'a>],
+    camera_path: &[Vertex<'a>],
+    sampled: Option<&Vertex<'a>>,
+    s: usize,
+    t: usize,
+) -> f32 {
+    if s + t == 2 {
+        return 1.0;
+    }
+    let light_at = |i: usize| match sampled {
+        Some(vertex) if s == 1 && i == 0 => vertex,
+        _ => &light_path[i],
+    };
+    let camera_at = |i: usize| match sampled {
+        Some(vertex) if t == 1 && i == 0 => vertex,
+        _ => &camera_path[i],
+    };
+    //复制pdf与delta后按连接方式修改，不改动子路径本身
+    let mut camera = (0..t)
+        .map(|i| {
+            let v = camera_at(i);
+            (v.pdf_fwd, v.pdf_rev, v.delta)
+        })
+        .collect::<Vec<_>>();
+    let mut light = (0..s)
+        .map(|i| {
+            let v = light_at(i);
+            (v.pdf_fwd, v.pdf_rev, v.delta)
+        })
+        .collect::<Vec<_>>();
+    let pt = camera_at(t - 1);
+    let qs = (s > 0).then(|| light_at(s - 1));
+    let pt_minus = (t > 1).then(|| camera_at(t - 2));
+    let qs_minus = (s > 1).then(|| light_at(s - 2));
+    camera[t - 1].2 = false;
+    camera[t - 1].1 = match qs {
+        Some(qs) => qs.pdf(sence, qs_minus, pt),
+        None => pt_minus.map_or(0.0, |pm| pt.pdf_light_origin(sence, pm)),
+    };
+    if let Some(pm) = pt_minus {
+        camera[t - 2].1 = match qs {
+            Some(qs) => pt.pdf(sence, Some(qs), pm),
+            None => pt.pdf_light(sence, pm),
+        };
+    }
+    if let Some(qs) = qs {
+        light[s - 1].2 = false;
+        light[s - 1].1 = pt.pdf(sence, pt_minus, qs);
+        if let Some(qm) = qs_minus {
+            light[s - 2].1 = qs.pdf(sence, Some(pt), qm);
+        }
+    }
+    let remap = |x: f32| if x != 0.0 { x } else { 1.0 };
+    let mut sum = 0.0;
+    let mut ri = 1.0;
+    for i in (1..t).rev() {
+        ri *= remap(camera[i].1) / remap(camera[i].0);
+        if !camera[i].2 && !camera[i - 1].2 {
+            sum += ri;
+        }
+    }
+    ri = 1.0;
+    for i in (0..s).rev() {
+        ri *= remap(light[i].1) / remap(light[i].0);
+        let delta_light = if i > 0 {
+            light[i - 1].2
+        } else {
+            light_at(0).is_delta_light()
+        };
+        if !light[i].2 && !delta_light {
+            sum += ri;
+        }
+    }
+    1.0 / (1.0 + sum)
+}
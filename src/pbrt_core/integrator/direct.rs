@@ -46,10 +46,11 @@ impl DirectIntegrator {
                 ans += beta * item.le(ray);
                 return ans;
             }
+            ans += beta * item.emission();
             item.compute_scattering(ray, mode);
             if let Some(_bsdf) = &item.bsdf {
                 // return (item.common.normal+Vec3::ONE)/2.0;
-                ans += beta * uniform_sample_all_light(&item, sence, sampler.clone(),n_sample,false);
+                ans += beta * uniform_sample_all_light(&item, sence, sampler,n_sample,false);
                 // ans+=beta *get_light(&item,sampler.sample_2d(),sence,sampler.clone(),false,false);
             }
         }
@@ -1,40 +1,53 @@
 use glam::{UVec2, Vec2, Vec3};
-use image::{Rgb, RgbImage};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use log::info;
-use rand::Rng;
+use log::{error, info, warn};
 use std::{
-    ops::Sub,
+    ops::{Range, Sub},
     path::Path,
-    sync::mpsc::{self, Receiver, Sender},
+    sync::mpsc::{self, Sender},
     thread,
     time::Instant,
 };
 
 use crate::pbrt_core::bxdf::BxDFType;
 use crate::pbrt_core::light::{Light, LightAble, LightType};
-use crate::pbrt_core::tool::tile::merage_tile;
+use crate::pbrt_core::tool::{
+    pixel_filter::PixelFilter,
+    setting::{OutputFormat, Progressive, Setting},
+    tile::Buffer,
+    tonemap::ToneMap,
+};
+use crate::pbrt_core::medium::MediumInteraction;
 use crate::pbrt_core::tool::{InteractionCommon, SurfaceInteraction, Visibility};
 
-use self::{direct::DirectIntegrator, path::PathIntegrator};
+use self::{
+    bdpt::BdptIntegrator, direct::DirectIntegrator, path::PathIntegrator, sppm::SppmIntegrator,
+    volpath::VolPathIntegrator,
+};
 
 use super::{
     camera::{Camera, CameraSample},
     primitive::Primitive,
-    sampler::Sampler,
+    sampler::{Sampler, SamplerAble},
     tool::{color::Color, film::Film, sence::Sence, tile::Tile, Ray, RayDiff},
 };
 
+pub mod bdpt;
 pub mod direct;
 pub mod path;
+pub mod sppm;
+pub mod volpath;
 
 pub enum Integrator {
     Path(Box<PathIntegrator>, usize, Sampler),
     Direct(Box<DirectIntegrator>, usize, Sampler),
+    Bdpt(Box<BdptIntegrator>, usize, Sampler),
+    Sppm(Box<SppmIntegrator>, usize, Sampler),
+    VolPath(Box<VolPathIntegrator>, usize, Sampler),
 }
 
 pub trait IntegratorAble {
-    fn is_next(&self, dept: &mut usize) -> Option<f32>;
+    fn is_next(&self, dept: &mut usize, beta: Color, sampler: &mut Sampler) -> Option<f32>;
     fn fi(&self, ray: RayDiff, sence: &Sence, sampler: &mut Sampler,
         #[cfg(debug_assertions)]
         i:&mut i32
@@ -42,9 +55,10 @@ pub trait IntegratorAble {
 }
 
 impl IntegratorAble for Integrator {
-    fn is_next(&self, dept: &mut usize) -> Option<f32> {
+    fn is_next(&self, dept: &mut usize, beta: Color, sampler: &mut Sampler) -> Option<f32> {
         match &self {
-            Integrator::Path(path, _, _) => path.is_next(dept),
+            Integrator::Path(path, _, _) => path.is_next(dept, beta, sampler),
+            Integrator::VolPath(volpath, _, _) => volpath.is_next(dept, beta, sampler),
             _ => None,
         }
     }
@@ -60,6 +74,15 @@ impl IntegratorAble for Integrator {
             Integrator::Direct(direct, _, _) => direct.fi(ray, sence, sampler,
                 #[cfg(debug_assertions)]
                 i),
+            Integrator::Bdpt(bdpt, _, _) => bdpt.fi(ray, sence, sampler,
+                #[cfg(debug_assertions)]
+                i),
+            Integrator::Sppm(sppm, _, _) => sppm.fi(ray, sence, sampler,
+                #[cfg(debug_assertions)]
+                i),
+            Integrator::VolPath(volpath, _, _) => volpath.fi(ray, sence, sampler,
+                #[cfg(debug_assertions)]
+                i),
         }
     }
 }
@@ -69,80 +92,188 @@ impl Integrator {
         match &self {
             Integrator::Path(_, index, _) => *index,
             Integrator::Direct(_, index, _) => *index,
+            Integrator::Bdpt(_, index, _) => *index,
+            Integrator::Sppm(_, index, _) => *index,
+            Integrator::VolPath(_, index, _) => *index,
         }
     }
     fn get_sample(&self) -> Sampler {
         match &self {
             Integrator::Path(_, _, sampler) => sampler.clone(),
             Integrator::Direct(_, _, sampler) => sampler.clone(),
+            Integrator::Bdpt(_, _, sampler) => sampler.clone(),
+            Integrator::Sppm(_, _, sampler) => sampler.clone(),
+            Integrator::VolPath(_, _, sampler) => sampler.clone(),
         }
     }
-    pub fn render_process(self, name: &str, sence: &Sence, size: UVec2) {
+    pub fn render_process(self, sence: &Sence, setting: &Setting) {
+        //光子映射按轮交替追踪相机与光子，不走逐样本的分块渲染
+        if let Integrator::Sppm(sppm, core, sampler) = &self {
+            sppm.render_process(sence, setting, *core, sampler);
+            return;
+        }
+        let size = setting.size;
+        let num = self.get_sample().samples_per_pixel();
+        let (progressive, adaptive) = (&setting.progressive, &setting.adaptive);
+        let (mut buffer, mut done) = Self::resume(progressive, size);
+        let pass = match (progressive.pass_spp, adaptive.enable) {
+            (0, true) => adaptive.min_spp.max(1),
+            (0, false) => num,
+            (pass, _) => pass,
+        };
+        let t1 = Instant::now();
+        let mut rendered = false;
+        while done < num {
+            let end = (done + pass).min(num);
+            //达到最少样本数后只继续采样误差仍较大的像素
+            let active = if adaptive.enable && done >= adaptive.min_spp {
+                let active = buffer.active_pixels(adaptive.threshold);
+                info!("剩余未收敛像素 {}", active.iter().filter(|x| **x).count());
+                if !active.contains(&true) {
+                    break;
+                }
+                Some(active)
+            } else {
+                None
+            };
+            let list = self.render_pass(sence, size, done..end, &setting.filter, active.as_deref());
+            buffer.merge_tiles(list);
+            rendered = true;
+            done = end;
+            info!("已完成 {}/{} spp", done, num);
+            if let Some(ref path) = progressive.checkpoint {
+                if let Err(err) = buffer.save_checkpoint(done, path) {
+                    error!("检查点保存失败 {}: {}", path, err);
+                }
+            }
+            //每轮结束写出中间结果，最后一轮即最终图像
+            Self::output(&buffer, setting, num);
+        }
+        if !rendered {
+            Self::output(&buffer, setting, num);
+        }
+        let t2 = Instant::now();
+        info!("渲染耗时:{} s", t2.sub(t1).as_secs_f32());
+    }
+    //从检查点恢复累积的图像与已完成的样本数
+    fn resume(progressive: &Progressive, size: UVec2) -> (Buffer, usize) {
+        if let (true, Some(path)) = (progressive.resume, &progressive.checkpoint) {
+            if Path::new(path).exists() {
+                match Buffer::load_checkpoint(path) {
+                    Ok((buffer, done)) if buffer.size() == size => {
+                        info!("从检查点 {} 恢复，已完成 {} spp", path, done);
+                        return (buffer, done);
+                    }
+                    Ok(_) => warn!("检查点 {} 的图像大小不一致，重新渲染", path),
+                    Err(err) => error!("检查点读取失败 {}: {}", path, err),
+                }
+            }
+        }
+        (Buffer::new(size), 0)
+    }
+    //渲染一轮，每个像素取samples范围内的样本
+    fn render_pass(
+        &self,
+        sence: &Sence,
+        size: UVec2,
+        samples: Range<usize>,
+        filter: &PixelFilter,
+        active: Option<&[bool]>,
+    ) -> Vec<Vec<Tile>> {
         let (sender, receiver) = mpsc::channel::<Vec<Tile>>();
         let film = Film::new(size);
-        let camera = sence.camera;
-        let t1 = Instant::now();
+        let pass = RenderPass {
+            film: &film,
+            filter,
+            samples,
+            active,
+        };
         let (m, style) = pbr();
         let core = self.get_num();
-        let len = film.render_size() / (core * 2) as u32;
-        let num = self.get_sample().num;
+        let len = film.render_size().div_ceil(core as u32);
         thread::scope(|scope| {
             for i in 0..core {
                 let pb = m.add(ProgressBar::new(len as u64));
                 pb.set_style(style.clone());
                 scope.spawn(self.render_core(
-                    &film,
-                    &camera,
+                    &pass,
                     sender.clone(),
                     sence,
                     self.get_sample(),
                     pb,
-                    i
+                    i,
                 ));
             }
             drop(sender);
         });
-        let t2 = Instant::now();
-
-        info!("渲染耗时:{} s", t2.sub(t1).as_secs_f32());
-        Self::output(receiver, size, name, num);
         m.clear().unwrap();
+        receiver.iter().collect()
     }
     fn render_core<'a, 'b>(
         &'b self,
-        film: &'a Film,
-        camera: &'a Camera,
+        pass: &'a RenderPass<'a>,
         send: Sender<Vec<Tile>>,
         sence: &'a Sence,
         mut sampler: Sampler,
         pb: ProgressBar,
-        index:usize
+        index:usize,
     ) -> impl FnOnce() + 'a
     where
         'b: 'a,
     {
         move || {
-            let n = sampler.num;
+            let RenderPass {
+                film,
+                filter,
+                ref samples,
+                active,
+            } = *pass;
+            let camera = sence.camera;
             let mut i=0;
             let mut tiles: Vec<Tile> = vec![];
+            let mut splats = vec![];
             while let Some(item) = film.iter() {
-                let index = item.index;
-                let mut tile = Tile::new(index);
+                let mut tile = Tile::new(
+                    item.index,
+                    item.left_up.into(),
+                    item.right_down.into(),
+                    filter.radius(),
+                    film.resolution(),
+                );
                 for (u, v) in item {
+                    let pixel = UVec2::new(u as u32, v as u32);
+                    if let Some(active) = active {
+                        if !active[(pixel.y * film.resolution().x + pixel.x) as usize] {
+                            continue;
+                        }
+                    }
                     let mut color = Color::ZERO;
                     i=0;
-                    for _ in 0..n {
+                    for s in samples.clone() {
+                        sampler.start_pixel_sample(pixel, s);
                         let camera_sample = CameraSample::new(u, v, &mut sampler);
+                        let film_point = camera_sample.film_point;
                         let ray = camera.generate_ray(camera_sample);
-                        color += self.fi(ray, sence, &mut sampler,
-                            #[cfg(debug_assertions)]
-                            &mut i
-                        );
+                        let li = match self {
+                            //双向路径追踪的光线追踪贡献泼溅到任意像素
+                            Integrator::Bdpt(bdpt, _, _) => {
+                                bdpt.li(ray, sence, &mut sampler, &mut splats)
+                            }
+                            _ => self.fi(ray, sence, &mut sampler,
+                                #[cfg(debug_assertions)]
+                                &mut i
+                            ),
+                        };
+                        for (point, color) in splats.drain(..) {
+                            tile.add_splat(point, color, film.resolution());
+                        }
+                        tile.add_sample(film_point, li, filter);
+                        tile.add_stat(pixel, li);
+                        color += li;
                     }
                     if i>0 {
                         info!("{}",color);
                     }
-                    tile.push(color);
                 }
                 
                 pb.inc(1);
@@ -153,17 +284,30 @@ impl Integrator {
             send.send(tiles).expect("send 失败");
         }
     }
-    fn output(rece: Receiver<Vec<Tile>>, size: UVec2, name: &str, num: usize) {
-        let mut list: Vec<Vec<Tile>> = vec![];
-        for iter in rece.iter() {
-            list.push(iter);
+    fn output(buffer: &Buffer, setting: &Setting, num: usize) {
+        let (size, name) = (setting.size, &setting.name);
+        for format in setting.output.iter() {
+            let path = Path::new("./image").join(format!(
+                "thread_{}_{}_{name}_{num}.{}",
+                size.x,
+                size.y,
+                format.extension()
+            ));
+            println!("{}", path.display());
+            if let Err(err) = buffer.write(*format, &setting.tone_map, &path) {
+                error!("图像输出失败 {}: {}", path.display(), err);
+            }
+        }
+        //自适应采样时输出每个像素的样本数
+        if setting.adaptive.enable {
+            let path = Path::new("./image").join(format!(
+                "thread_{}_{}_{name}_{num}_spp.png",
+                size.x, size.y
+            ));
+            if let Err(err) = buffer.write_sample_map(num, &path) {
+                error!("样本数图输出失败 {}: {}", path.display(), err);
+            }
         }
-        let buffer = merage_tile(list, size);
-        let path =
-            Path::new("./image").join(format!("thread_{}_{}_{name}_{num}.png", size.x, size.y));
-        format!("渲染完成，图像输出:{}", path.display());
-        println!("{}", path.display());
-        buffer.write(image::ImageFormat::Jpeg, num as f32, path);
     }
 
     pub fn render_process_debug(self, name: &str, num: u64, sence: &Sence, size: UVec2) {
@@ -173,12 +317,13 @@ impl Integrator {
         let mut sampler = Sampler::default();
         let camera = sence.camera;
         let bar = ProgressBar::new(bar_size as u64);
-        let mut image = RgbImage::new(size.x, size.y);
+        let mut pixels = vec![Color::ZERO; bar_size as usize];
         let mut i=0;
         while let Some(item) = film.iter() {
             for (u, v) in item {
                 let mut color = Color::ZERO;
-                for _ in 0..n {
+                for s in 0..n {
+                    sampler.start_pixel_sample(UVec2::new(u as u32, v as u32), s);
                     let camera_sample = CameraSample::new(u, v, &mut sampler);
                     let ray = camera.generate_ray(camera_sample);
                     color += self.fi(ray, sence, &mut sampler,
@@ -186,29 +331,34 @@ impl Integrator {
                         &mut i
                     );
                 }
-                image.put_pixel(u as u32, v as u32, to_color(color, num as f32));
+                pixels[(v as u32 * size.x + u as u32) as usize] = color / num as f32;
                 bar.inc(1);
             }
         }
-        let path =
-            Path::new("./image").join(format!("thread_{}_{}_{name}_{num}.png", size.x, size.y));
-        format!("渲染完成，图像输出:{}", path.display());
+        //与正式渲染使用同一输出接口，扩展名由格式决定
+        let format = OutputFormat::Png;
+        let path = Path::new("./image").join(format!(
+            "thread_{}_{}_{name}_{num}.{}",
+            size.x,
+            size.y,
+            format.extension()
+        ));
         bar.finish_with_message("渲染完成，图像输出");
-        image
-            .save_with_format(path, image::ImageFormat::Jpeg)
+        info!("渲染完成，图像输出:{}", path.display());
+        Buffer::from_pixels(size, pixels)
+            .write(format, &ToneMap::default(), &path)
             .expect("图片保存失败");
     }
 }
 
-pub fn to_color(color: Color, ssp: f32) -> Rgb<u8> {
-    let vec = (color / ssp).powf(2.0);
-    let rgb = vec * 255.0;
-    
-    Rgb([
-        rgb.x.clamp(0.0, 255.0) as u8,
-        rgb.y.clamp(0.0, 255.0) as u8,
-        rgb.z.clamp(0.0, 255.0) as u8,
-    ])
+//一轮渲染中各线程共享的输入
+struct RenderPass<'a> {
+    film: &'a Film,
+    filter: &'a PixelFilter,
+    //每个像素取该范围内的样本
+    samples: Range<usize>,
+    //自适应采样时仍需继续采样的像素
+    active: Option<&'a [bool]>,
 }
 
 pub fn pbr() -> (MultiProgress, ProgressStyle) {
@@ -224,7 +374,7 @@ pub fn pbr() -> (MultiProgress, ProgressStyle) {
 pub fn uniform_sample_all_light(
     common: &SurfaceInteraction,
     sence: &Sence,
-    mut sampler: Sampler,
+    sampler: &mut Sampler,
     n_light_sample: Vec<usize>,
     handle_media: bool,
 ) -> Color {
@@ -239,7 +389,7 @@ pub fn uniform_sample_all_light(
                 light,
                 u_light,
                 sence,
-                sampler.clone(),
+                sampler,
                 handle_media,
                 false,
             );
@@ -250,7 +400,7 @@ pub fn uniform_sample_all_light(
                     light,
                     sampler.sample_2d_d(),
                     sence,
-                    sampler.clone(),
+                    sampler,
                     handle_media,
                     false,
                 );
@@ -265,11 +415,11 @@ pub fn uniform_sample_all_light(
 pub fn unifrom_sample_one_light(
     common: &SurfaceInteraction,
     sence: &Sence,
-    mut sampler: Sampler,
+    sampler: &mut Sampler,
     handle_media: bool,
 ) -> Color {
     let len = sence.light.len();
-    let num = sampler.rand.gen_range(0..len);
+    let num = sampler.sample_index(len);
     let light = &sence.light[num];
     let mut ld = Color::default();
     let smaple = light.get_n_sample();
@@ -279,7 +429,7 @@ pub fn unifrom_sample_one_light(
             light,
             sampler.sample_2d(),
             sence,
-            sampler.clone(),
+            sampler,
             handle_media,
             false,
         );
@@ -292,8 +442,8 @@ pub fn estimate_direct(
     light: &Light,
     u_light: Vec2,
     sence: &Sence,
-    mut sampler: Sampler,
-    _handle_media: bool,
+    sampler: &mut Sampler,
+    handle_media: bool,
     specular: bool,
 ) -> Color {
     let bxdf_flags = if specular {
@@ -301,90 +451,225 @@ pub fn estimate_direct(
     } else {
         BxDFType::All as u32 & !BxDFType::Specular
     };
+    let Some(ref bsdf) = inter.bsdf else {
+        return Color::ZERO;
+    };
+    //wo、wi均背离表面，光源接口中的wi由光源指向表面
+    let wo = -inter.common.w0;
     let mut ld = Vec3::ZERO;
-    let mut wi: Vec3 = Vec3::ZERO;
+    let mut wi_light: Vec3 = Vec3::ZERO;
     let mut light_pdf: f32 = 0.0;
     let mut vis: Visibility = Default::default();
     let mut light_common: InteractionCommon = Default::default();
-    let mut li = light.sample_li(
+    let li = light.sample_li(
         &inter.common,
         &mut light_common,
         u_light,
-        &mut wi,
+        &mut wi_light,
         &mut light_pdf,
         &mut vis,
     );
-    // return li;
-    // 合理的pdf和采样出光线 
-    if light_pdf > 0.0 && !li.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
-        //计算BSDF
-        let f = if let Some(ref bsdf) = inter.bsdf {
-            bsdf.f(&inter.common.w0, &wi, bxdf_flags) * wi.dot(inter.shading.n).abs()
-        } else {
-            Vec3::ZERO
-        };
-        //计算光贡献
-        if !f.abs_diff_eq(Vec3::ZERO, f32::EPSILON) && !vis.is_vis(sence) {
-            li = Color::ZERO;
-        }
-        let scattle_pdf = if let Some(ref bsdf) = inter.bsdf {
-            bsdf.pdf(&inter.common.w0, &-wi, bxdf_flags)
+    //面光源返回面积pdf，转换为立体角pdf
+    if matches!(light.get_type(), LightType::Area) && light_pdf > 0.0 {
+        let cos = light_common.normal.normalize().dot(wi_light).abs();
+        light_pdf = if cos > 0.0 {
+            light_pdf * light_common.p.distance_squared(inter.common.p) / cos
         } else {
-            1.0
+            0.0
         };
-
-        if !li.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+    }
+    // 光源采样
+    if light_pdf > 0.0 && !li.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+        let wi = -wi_light;
+        let f = bsdf.f(&wo, &wi, bxdf_flags) * wi.dot(inter.shading.n).abs();
+        if !f.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+            //处理介质时阴影光线计入透射率
+            let tr = if handle_media {
+                vis.tr(sence, inter.get_medium(&wi), sampler)
+            } else if vis.is_vis(sence) {
+                Color::ONE
+            } else {
+                Color::ZERO
+            };
             if LightType::is_delta(light.get_type()) {
-                ld +=  li *f* vis.g(sence) / light_pdf;
-            }else if LightType::is_inf(light.get_type()){
-                ld+=li*f*vis.g_inf(sence) / light_pdf;
-            }
-             else {
-                let weight = power_heuristic(1.0, light_pdf, 1.0, scattle_pdf);
-                ld +=  li *f * vis.g(sence)*weight/light_pdf;
+                //点光源等的li已包含距离衰减
+                ld += li * f * tr / light_pdf;
+            } else {
+                let scattering_pdf = bsdf.pdf(&wo, &wi, bxdf_flags);
+                let weight = power_heuristic(1.0, light_pdf, 1.0, scattering_pdf);
+                ld += li * f * tr * weight / light_pdf;
             }
         }
     }
-    //BSDF重要性采样
+    //BSDF重要性采样，delta光源无法被采样光线击中
     if !LightType::is_delta(light.get_type()) {
-        let mut sampled_specular = false;
+        let mut wi = Vec3::ZERO;
         let mut smapled_type = BxDFType::None as u32;
         let mut bsdf_pdf = 0.0;
-        if let Some(ref bsdf) = inter.bsdf {
-            let f = bsdf.sample_f(
-                &inter.common.w0,
-                &mut wi,
-                sampler.sample_2d_d(),
-                &mut bsdf_pdf,
-                bxdf_flags,
-                &mut smapled_type,
-            ) * wi.dot(inter.shading.n).abs();
-            sampled_specular = BxDFType::Specular as u32 & smapled_type > 0;
-            if !f.abs_diff_eq(Vec3::ZERO, f32::EPSILON) && bsdf_pdf > 0.0 {
-                let weight = if !sampled_specular {
-                    let light_pdf = light.pdf_li(inter, &wi);
-                    if light_pdf.abs() < f32::EPSILON {
-                        return ld;
-                    }
-                    power_heuristic(1.0, bsdf_pdf, 1.0, light_pdf)
-                } else {
-                    1.0
-                };
-                let ray = RayDiff::new(Ray::new(inter.common.p, -wi));
-                let li =
-                if let Some(ref light_inter) = sence.interacect(ray) {
-                    light_inter.le(ray)
-                }else{
-                    Default::default()
-                };
-                if !li.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
-                    ld += li * f * weight / bsdf_pdf;
+        let f = bsdf.sample_f(
+            &wo,
+            &mut wi,
+            sampler.sample_2d_d(),
+            &mut bsdf_pdf,
+            bxdf_flags,
+            &mut smapled_type,
+        ) * wi.dot(inter.shading.n).abs();
+        let sampled_specular = BxDFType::Specular as u32 & smapled_type > 0;
+        //不处理镜面时，镜面方向上的光源由路径中的Le计入
+        if sampled_specular && !specular {
+            return ld;
+        }
+        if !f.abs_diff_eq(Vec3::ZERO, f32::EPSILON) && bsdf_pdf > 0.0 {
+            let weight = if !sampled_specular {
+                let light_pdf = light.pdf_li(inter, &-wi);
+                if light_pdf <= 0.0 {
+                    return ld;
                 }
+                power_heuristic(1.0, bsdf_pdf, 1.0, light_pdf)
+            } else {
+                1.0
+            };
+            let ray = inter.spawn_ray(&wi);
+            let (hit, tr) = if handle_media {
+                sence.intersect_tr(ray, inter.get_medium(&wi), sampler)
+            } else {
+                (sence.interacect(ray), Color::ONE)
+            };
+            let li = hit_light_le(hit, ray, light) * tr;
+            if !li.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+                ld += li * f * weight / bsdf_pdf;
             }
         }
     };
     ld
 }
+//只计入击中当前光源的贡献，避免与其他光源的采样重复
+fn hit_light_le(hit: Option<SurfaceInteraction>, ray: RayDiff, light: &Light) -> Color {
+    match hit {
+        Some(light_inter) => match light_inter.light {
+            Some(hit) if hit.get_index() == light.get_index() => light_inter.le(ray),
+            _ => Color::ZERO,
+        },
+        None if LightType::is_inf(light.get_type()) => light.le(&ray),
+        None => Color::ZERO,
+    }
+}
+
+//介质中散射点的直接光照，相函数代替BSDF，medium为散射点所在介质
+pub fn unifrom_sample_one_light_medium(
+    mi: &MediumInteraction,
+    medium: Option<usize>,
+    sence: &Sence,
+    sampler: &mut Sampler,
+) -> Color {
+    let len = sence.light.len();
+    if len == 0 {
+        return Color::ZERO;
+    }
+    let light = &sence.light[sampler.sample_index(len)];
+    let u_light = sampler.sample_2d_d();
+    estimate_direct_medium(mi, medium, light, u_light, sence, sampler) * len as f32
+}
+
+pub fn estimate_direct_medium(
+    mi: &MediumInteraction,
+    medium: Option<usize>,
+    light: &Light,
+    u_light: Vec2,
+    sence: &Sence,
+    sampler: &mut Sampler,
+) -> Color {
+    //散射点没有法线，光线起点不做偏移
+    let common = InteractionCommon::new(-mi.wo, mi.p, Vec3::ZERO, 0.0, Vec2::ZERO);
+    let inter = SurfaceInteraction::new(common, Default::default(), None, None);
+    let mut ld = Vec3::ZERO;
+    let mut wi_light: Vec3 = Vec3::ZERO;
+    let mut light_pdf: f32 = 0.0;
+    let mut vis: Visibility = Default::default();
+    let mut light_common: InteractionCommon = Default::default();
+    let li = light.sample_li(
+        &common,
+        &mut light_common,
+        u_light,
+        &mut wi_light,
+        &mut light_pdf,
+        &mut vis,
+    );
+    if matches!(light.get_type(), LightType::Area) && light_pdf > 0.0 {
+        let cos = light_common.normal.normalize().dot(wi_light).abs();
+        light_pdf = if cos > 0.0 {
+            light_pdf * light_common.p.distance_squared(mi.p) / cos
+        } else {
+            0.0
+        };
+    }
+    // 光源采样
+    if light_pdf > 0.0 && !li.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+        let wi = -wi_light;
+        let p = mi.phase.p(&mi.wo, &wi);
+        let tr = vis.tr(sence, medium, sampler);
+        if LightType::is_delta(light.get_type()) {
+            ld += li * p * tr / light_pdf;
+        } else {
+            let weight = power_heuristic(1.0, light_pdf, 1.0, p);
+            ld += li * p * tr * weight / light_pdf;
+        }
+    }
+    // 相函数采样，采样值与pdf相等
+    if !LightType::is_delta(light.get_type()) {
+        let mut wi = Vec3::ZERO;
+        let p = mi.phase.sample_p(&mi.wo, &mut wi, sampler.sample_2d_d());
+        let light_pdf = light.pdf_li(&inter, &-wi);
+        if p > 0.0 && light_pdf > 0.0 {
+            let weight = power_heuristic(1.0, p, 1.0, light_pdf);
+            let ray = inter.spawn_ray(&wi);
+            let (hit, tr) = sence.intersect_tr(ray, medium, sampler);
+            ld += hit_light_le(hit, ray, light) * tr * weight;
+        }
+    }
+    ld
+}
+
+//路径追踪与体积路径追踪共用的俄罗斯轮盘赌
+#[derive(Clone, Copy, Debug)]
+pub struct Roulette {
+    //最大存活概率
+    q: f32,
+    //最大反弹次数
+    max_path: usize,
+    //从第几次反弹开始俄罗斯轮盘赌
+    rr_depth: usize,
+}
+impl Default for Roulette {
+    fn default() -> Self {
+        Self::new(0.9, 10, 3)
+    }
+}
+impl Roulette {
+    pub fn new(q: f32, max_path: usize, rr_depth: usize) -> Self {
+        Self {
+            q,
+            max_path,
+            rr_depth,
+        }
+    }
+    //按路径吞吐量决定存活概率，返回存活概率用于重新加权
+    pub fn next(&self, dept: &mut usize, beta: Color, sampler: &mut Sampler) -> Option<f32> {
+        *dept += 1;
+        if *dept >= self.max_path {
+            None
+        } else if *dept < self.rr_depth {
+            Some(1.0)
+        } else {
+            let p = beta.max_element().min(self.q);
+            if sampler.sample_1d_d() < p {
+                Some(p)
+            } else {
+                None
+            }
+        }
+    }
+}
 
 pub fn power_heuristic(nf: f32, f_pdf: f32, ng: f32, g_pdf: f32) -> f32 {
     let f = nf * f_pdf;
@@ -395,12 +680,12 @@ pub fn power_heuristic(nf: f32, f_pdf: f32, ng: f32, g_pdf: f32) -> f32 {
 pub fn get_light(
     inter: &SurfaceInteraction,
     sence: &Sence,
-    mut sampler: Sampler,
+    sampler: &mut Sampler,
 ) -> Color {
     if sence.light.is_empty() {
         return Color::ZERO;
     }
-    let num: usize = sampler.rand.gen_range(0..sence.light.len());
+    let num: usize = sampler.sample_index(sence.light.len());
     let light = &sence.light[num];
     let mut light_common = Default::default();
     let mut wi = Vec3::default();
@@ -1,42 +1,24 @@
 use glam::Vec3;
 
 use crate::pbrt_core::{
+    bssrdf::BSSRDF,
     bxdf::BxDFType,
     primitive::Primitive,
     sampler::Sampler,
     tool::{color::Color, sence::Sence, RayDiff},
 };
 
-use super::{unifrom_sample_one_light, IntegratorAble};
+use super::{unifrom_sample_one_light, IntegratorAble, Roulette};
 
 //路径追踪积分器
+#[derive(Default)]
 pub struct PathIntegrator {
-    q: f32,
-    max_path: usize,
-}
-
-impl Default for PathIntegrator {
-    fn default() -> Self {
-        Self {
-            q: 0.9,
-            max_path: 10,
-        }
-    }
+    roulette: Roulette,
 }
 
 impl IntegratorAble for PathIntegrator {
-    fn is_next(&self, dept: &mut usize) -> Option<f32> {
-        *dept += 1;
-        if *dept > self.max_path {
-            let p = rand::random::<f32>();
-            if p > self.q {
-                None
-            } else {
-                Some(self.q)
-            }
-        } else {
-            Some(1.0)
-        }
+    fn is_next(&self, dept: &mut usize, beta: Color, sampler: &mut Sampler) -> Option<f32> {
+        self.roulette.next(dept, beta, sampler)
     }
     fn fi(
         &self,
@@ -49,56 +31,114 @@ impl IntegratorAble for PathIntegrator {
         let mut dept = 0;
         let mut beta: Vec3 = Vec3::ONE;
         let mut ray = ray;
-        let mode = crate::pbrt_core::bxdf::TransportMode::Radiance;   
-        while let Some(p) = self.is_next(&mut dept) {
-            if let Some(mut item) = sence.interacect(ray) {
-                if item.light.is_some() {
-                    ans += beta * item.le(ray);
-                    return ans;
+        let mode = crate::pbrt_core::bxdf::TransportMode::Radiance;
+        //上一次反弹是否为镜面反射
+        let mut specular_bounce = false;
+        loop {
+            let hit = sence.interacect(ray);
+            //相机光线和镜面反射光线直接计入光源，其余情况已在直接光照的MIS中计入
+            if dept == 0 || specular_bounce {
+                match &hit {
+                    Some(item) => ans += beta * item.le(ray),
+                    //环境光采样
+                    None => ans += beta * sence.sample_env_light(&ray),
                 }
-                item.compute_scattering(ray, mode);
+            }
+            let Some(mut item) = hit else {
+                break;
+            };
+            if item.light.is_some() {
+                break;
+            }
+            ans += beta * item.emission();
+            item.compute_scattering(ray, mode);
 
-                if let Some(bsdf) = &item.bsdf {
-                    //场景光源采样
-                    ans +=
-                        beta * unifrom_sample_one_light(&item, sence, sampler.clone(), false) / p;
-                    //BRDF 采样生成光线
-                    let w_out = -ray.o.dir;
-                    let mut w_in = Vec3::default();
-                    let mut pdf = 0.0;
-                    let mut samped_type: u32 = 0;
-                    let f = bsdf.sample_f(
-                        &w_out,
-                        &mut w_in,
-                        sampler.sample_2d_d(),
-                        &mut pdf,
-                        BxDFType::All.into(),
-                        &mut samped_type,
-                    ) * w_in.dot(item.shading.n).abs()
-                        / pdf;
-                    if f.is_nan() || f.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
-                        break;
-                    }
-                    beta *= f;
-                    ray = item.spawn_ray(&w_in);
-                    #[cfg(debug_assertions)]
-                    {
-                        *i += 1;
-                    }
-                }
-            } else {
-                ans += beta * sence.sample_env_light(&ray);
-                //环境光采样
+            let Some(bsdf) = &item.bsdf else {
+                break;
+            };
+            //场景光源采样
+            ans += beta * unifrom_sample_one_light(&item, sence, sampler, false);
+            //BRDF 采样生成光线
+            let w_out = -ray.o.dir;
+            let mut w_in = Vec3::default();
+            let mut pdf = 0.0;
+            let mut samped_type: u32 = 0;
+            let f = bsdf.sample_f(
+                &w_out,
+                &mut w_in,
+                sampler.sample_2d_d(),
+                &mut pdf,
+                BxDFType::All.into(),
+                &mut samped_type,
+            ) * w_in.dot(item.shading.n).abs()
+                / pdf;
+            if pdf <= 0.0 || f.is_nan() || f.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
                 break;
             }
-            // beta = beta / p;
+            beta *= f;
+            specular_bounce = samped_type & BxDFType::Specular as u32 != 0;
+            let mut next = item.spawn_ray(&w_in);
+            //折射进入次表面散射材质时，从BSSRDF采样的出射点继续路径
+            let transmitted = w_in.dot(item.common.normal) * w_out.dot(item.common.normal) < 0.0;
+            if let (Some(bssrdf), true) = (&item.bssrdf, transmitted) {
+                match self.subsurface(bssrdf, sence, sampler, &mut beta, &mut ans) {
+                    Some(ray) => next = ray,
+                    None => break,
+                }
+                specular_bounce = false;
+            }
+            match self.is_next(&mut dept, beta, sampler) {
+                Some(p) => beta /= p,
+                None => break,
+            }
+            ray = next;
+            #[cfg(debug_assertions)]
+            {
+                *i += 1;
+            }
         }
         ans
     }
 }
 
 impl PathIntegrator {
-    pub fn new(q: f32, max_path: usize) -> Self {
-        Self { q, max_path }
+    pub fn new(q: f32, max_path: usize, rr_depth: usize) -> Self {
+        Self {
+            roulette: Roulette::new(q, max_path, rr_depth),
+        }
+    }
+    //在出射点计入直接光照，并按出射点的BSDF采样下一条光线
+    fn subsurface(
+        &self,
+        bssrdf: &BSSRDF,
+        sence: &Sence,
+        sampler: &mut Sampler,
+        beta: &mut Color,
+        ans: &mut Color,
+    ) -> Option<RayDiff> {
+        let mut pdf = 0.0;
+        let (s, pi) = bssrdf.sample_s(sence, sampler.sample_1d_d(), sampler.sample_2d_d(), &mut pdf)?;
+        if pdf <= 0.0 || s.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+            return None;
+        }
+        *beta *= s / pdf;
+        *ans += *beta * unifrom_sample_one_light(&pi, sence, sampler, false);
+        let bsdf = pi.bsdf.as_ref()?;
+        let w_out = -pi.common.w0;
+        let mut w_in = Vec3::ZERO;
+        let mut samped_type = 0;
+        let f = bsdf.sample_f(
+            &w_out,
+            &mut w_in,
+            sampler.sample_2d_d(),
+            &mut pdf,
+            BxDFType::All.into(),
+            &mut samped_type,
+        );
+        if pdf <= 0.0 || f.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+            return None;
+        }
+        *beta *= f * w_in.dot(pi.shading.n).abs() / pdf;
+        Some(pi.spawn_ray(&w_in))
     }
 }
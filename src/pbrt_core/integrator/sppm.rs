@@ -0,0 +1,466 @@
+use std::{f32::consts::PI, ops::Sub, thread, time::Instant};
+
+use glam::{IVec3, UVec2, Vec3};
+use log::{info, warn};
+
+use crate::pbrt_core::{
+    bxdf::{BxDFType, TransportMode},
+    camera::CameraSample,
+    light::LightAble,
+    material::BSDF,
+    primitive::Primitive,
+    sampler::{Sampler, SamplerAble, SamplerMode},
+    tool::{
+        color::Color, sence::Sence, setting::Setting, tile::Buffer, Bound, InteractionCommon, Ray,
+        RayDiff,
+    },
+};
+
+use super::{unifrom_sample_one_light, Integrator, IntegratorAble};
+
+//随机渐进光子映射积分器，每轮相机样本数为1，轮数为sample_num
+//环境光与平行光从垂直于传播方向、覆盖场景包围球的圆盘上发射光子
+pub struct SppmIntegrator {
+    //可见点的初始搜索半径
+    radius: f32,
+    //相机路径与光子路径的最大反弹次数
+    max_depth: usize,
+    //每轮发射的光子数
+    photons: usize,
+    seed: u64,
+}
+
+//相机路径上第一个非镜面的交点，只保留光子轮需要的位置与BSDF
+struct VisiblePoint {
+    p: Vec3,
+    wo: Vec3,
+    bsdf: BSDF,
+    beta: Color,
+}
+
+//每个像素跨轮保存的统计量
+#[derive(Default)]
+struct SppmPixel {
+    radius: f32,
+    //自发光与直接光照的累加
+    ld: Color,
+    vp: Option<VisiblePoint>,
+    //本轮收集到的光子通量与光子数
+    phi: Color,
+    m: usize,
+    n: f32,
+    tau: Color,
+}
+
+//每块的光子数固定，累加顺序与线程数无关
+const PHOTON_CHUNK: usize = 1 << 14;
+
+//一块光子在各像素处收集到的通量与光子数
+struct PhotonBuffer {
+    phi: Vec<Color>,
+    m: Vec<usize>,
+}
+impl PhotonBuffer {
+    fn new(len: usize) -> Self {
+        Self {
+            phi: vec![Color::ZERO; len],
+            m: vec![0; len],
+        }
+    }
+    //累加到像素并清空
+    fn drain_into(&mut self, pixels: &mut [SppmPixel]) {
+        for (pixel, (phi, m)) in pixels.iter_mut().zip(self.phi.iter_mut().zip(&mut self.m)) {
+            pixel.phi += std::mem::take(phi);
+            pixel.m += std::mem::take(m);
+        }
+    }
+}
+
+//本轮收集到m个光子后，按比例alpha保留光子数并收缩半径，返回新的光子数与半径
+pub fn shrink_radius(n: f32, m: usize, radius: f32) -> (f32, f32) {
+    const ALPHA: f32 = 2.0 / 3.0;
+    let n_new = n + ALPHA * m as f32;
+    (n_new, radius * (n_new / (n + m as f32)).sqrt())
+}
+
+impl SppmIntegrator {
+    pub fn new(radius: f32, max_depth: usize, photons: usize, seed: u64) -> Self {
+        Self {
+            radius,
+            max_depth,
+            photons,
+            seed,
+        }
+    }
+    pub fn render_process(&self, sence: &Sence, setting: &Setting, core: usize, sampler: &Sampler) {
+        let size = setting.size;
+        let iterations = sampler.samples_per_pixel();
+        let (progressive, adaptive) = (&setting.progressive, &setting.adaptive);
+        if progressive.checkpoint.is_some() || adaptive.enable {
+            warn!("SPPM不支持检查点与自适应采样，按全部轮数渲染");
+        }
+        let core = core.max(1);
+        let t1 = Instant::now();
+        let mut pixels = (0..size.x * size.y)
+            .map(|_| SppmPixel {
+                radius: self.radius,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        for iteration in 0..iterations {
+            self.camera_pass(sence, size, iteration, core, sampler, &mut pixels);
+            let grid = Grid::new(&pixels);
+            self.photon_pass(sence, iteration, core, &grid, &mut pixels);
+            Self::update(&mut pixels);
+            let done = iteration + 1;
+            info!("已完成 {}/{} 轮光子映射", done, iterations);
+            //按渐进式渲染的间隔写出中间结果，最后一轮即最终图像
+            let pass = progressive.pass_spp;
+            if done == iterations || (pass > 0 && done % pass == 0) {
+                let buffer = self.buffer(&pixels, size, done);
+                Integrator::output(&buffer, setting, iterations);
+            }
+        }
+        let t2 = Instant::now();
+        info!("渲染耗时:{} s", t2.sub(t1).as_secs_f32());
+    }
+    //每个像素追踪一条相机光线，记录直接光照与可见点
+    fn camera_pass(
+        &self,
+        sence: &Sence,
+        size: UVec2,
+        iteration: usize,
+        core: usize,
+        sampler: &Sampler,
+        pixels: &mut [SppmPixel],
+    ) {
+        let chunk = pixels.len().div_ceil(core).max(1);
+        thread::scope(|scope| {
+            for (i, chunk_pixels) in pixels.chunks_mut(chunk).enumerate() {
+                let mut sampler = sampler.clone();
+                scope.spawn(move || {
+                    for (j, pixel) in chunk_pixels.iter_mut().enumerate() {
+                        let index = (i * chunk + j) as u32;
+                        let (u, v) = (index % size.x, index / size.x);
+                        sampler.start_pixel_sample(UVec2::new(u, v), iteration);
+                        let camera_sample = CameraSample::new(u as f32, v as f32, &mut sampler);
+                        let ray = sence.camera.generate_ray(camera_sample);
+                        let (ld, vp) = self.trace_camera(ray, sence, &mut sampler);
+                        pixel.ld += ld;
+                        pixel.vp = vp;
+                    }
+                });
+            }
+        });
+    }
+    //沿镜面反射与折射前进，直到第一个非镜面交点
+    fn trace_camera(
+        &self,
+        mut ray: RayDiff,
+        sence: &Sence,
+        sampler: &mut Sampler,
+    ) -> (Color, Option<VisiblePoint>) {
+        let mut ld = Color::ZERO;
+        let mut beta = Color::ONE;
+        let mut specular_bounce = false;
+        for depth in 0..self.max_depth.max(1) {
+            let hit = sence.interacect(ray);
+            if depth == 0 || specular_bounce {
+                match &hit {
+                    Some(item) => ld += beta * item.le(ray),
+                    None => ld += beta * sence.sample_env_light(&ray),
+                }
+            }
+            let Some(mut si) = hit else {
+                break;
+            };
+            if si.light.is_some() {
+                break;
+            }
+            ld += beta * si.emission();
+            si.compute_scattering(ray, TransportMode::Radiance);
+            let Some(bsdf) = &si.bsdf else {
+                break;
+            };
+            ld += beta * unifrom_sample_one_light(&si, sence, sampler, false);
+            let wo = -ray.o.dir;
+            if !bsdf.is_specular() || depth + 1 == self.max_depth {
+                let vp = si.bsdf.map(|bsdf| VisiblePoint {
+                    p: si.common.p,
+                    wo,
+                    bsdf,
+                    beta,
+                });
+                return (ld, vp);
+            }
+            let mut wi = Vec3::ZERO;
+            let mut pdf = 0.0;
+            let mut sampled_type = 0;
+            let f = bsdf.sample_f(
+                &wo,
+                &mut wi,
+                sampler.sample_2d_d(),
+                &mut pdf,
+                BxDFType::All.into(),
+                &mut sampled_type,
+            );
+            if pdf <= 0.0 || f.is_nan() || f.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+                break;
+            }
+            beta *= f * wi.dot(si.shading.n).abs() / pdf;
+            specular_bounce = sampled_type & BxDFType::Specular as u32 != 0;
+            ray = si.spawn_ray(&wi);
+        }
+        (ld, None)
+    }
+    //从光源发射光子，每块光子先记录在独立的缓冲中，再按块的顺序累加到像素
+    fn photon_pass(
+        &self,
+        sence: &Sence,
+        iteration: usize,
+        core: usize,
+        grid: &Grid,
+        pixels: &mut [SppmPixel],
+    ) {
+        if sence.light.is_empty() || grid.cells.is_empty() {
+            return;
+        }
+        let chunks = self.photons.div_ceil(PHOTON_CHUNK);
+        let mut buffers = (0..core.min(chunks))
+            .map(|_| PhotonBuffer::new(pixels.len()))
+            .collect::<Vec<_>>();
+        for wave in (0..chunks).step_by(buffers.len()) {
+            let count = buffers.len().min(chunks - wave);
+            let shared = &*pixels;
+            thread::scope(|scope| {
+                for (i, buffer) in buffers.iter_mut().take(count).enumerate() {
+                    let chunk = wave + i;
+                    scope.spawn(move || {
+                        //光子使用独立采样器，纵坐标取相机像素不会用到的值
+                        let mut sampler = Sampler::new(SamplerMode::Independent, 1, self.seed);
+                        let end = ((chunk + 1) * PHOTON_CHUNK).min(self.photons);
+                        for photon in chunk * PHOTON_CHUNK..end {
+                            sampler
+                                .start_pixel_sample(UVec2::new(photon as u32, u32::MAX), iteration);
+                            self.trace_photon(sence, &mut sampler, grid, shared, buffer);
+                        }
+                    });
+                }
+            });
+            for buffer in buffers.iter_mut().take(count) {
+                buffer.drain_into(pixels);
+            }
+        }
+    }
+    fn trace_photon(
+        &self,
+        sence: &Sence,
+        sampler: &mut Sampler,
+        grid: &Grid,
+        pixels: &[SppmPixel],
+        buffer: &mut PhotonBuffer,
+    ) {
+        let light = &sence.light[sampler.sample_index(sence.light.len())];
+        let light_pdf = 1.0 / sence.light.len() as f32;
+        let (u1, u2) = (sampler.sample_2d_d(), sampler.sample_2d_d());
+        let mut light_common = InteractionCommon::default();
+        let mut dir = Vec3::ZERO;
+        let (mut pdf_pos, mut pdf_dir) = (0.0, 0.0);
+        let le = light.sample_le(
+            u1,
+            u2,
+            &mut light_common,
+            &mut dir,
+            &mut pdf_pos,
+            &mut pdf_dir,
+        );
+        if pdf_pos == 0.0 || pdf_dir == 0.0 || le.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+            return;
+        }
+        let n = light_common.normal.normalize_or_zero();
+        //点光源没有法线，余弦项取1
+        let cos = if n == Vec3::ZERO {
+            1.0
+        } else {
+            n.dot(dir).abs()
+        };
+        let mut beta = le * cos / (light_pdf * pdf_pos * pdf_dir);
+        let mut ray = RayDiff::new(Ray::new(light_common.p + n * 0.0001, dir));
+        for depth in 0..self.max_depth {
+            let Some(mut si) = sence.interacect(ray) else {
+                break;
+            };
+            if si.light.is_some() {
+                break;
+            }
+            let wi = -ray.o.dir;
+            //第一次相交属于直接光照，已在可见点处计算
+            if depth > 0 {
+                let p = si.common.p;
+                for &index in grid.lookup(p) {
+                    let pixel = &pixels[index];
+                    let Some(vp) = &pixel.vp else {
+                        continue;
+                    };
+                    if vp.p.distance_squared(p) > pixel.radius * pixel.radius {
+                        continue;
+                    }
+                    buffer.phi[index] += beta * vp.bsdf.f(&vp.wo, &wi, BxDFType::All.into());
+                    buffer.m[index] += 1;
+                }
+            }
+            si.compute_scattering(ray, TransportMode::Importance);
+            let Some(bsdf) = &si.bsdf else {
+                break;
+            };
+            let mut w_in = Vec3::ZERO;
+            let mut pdf = 0.0;
+            let mut sampled_type = 0;
+            let f = bsdf.sample_f(
+                &wi,
+                &mut w_in,
+                sampler.sample_2d_d(),
+                &mut pdf,
+                BxDFType::All.into(),
+                &mut sampled_type,
+            );
+            if pdf <= 0.0 || f.is_nan() || f.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+                break;
+            }
+            let beta_new = beta * f * w_in.dot(si.shading.n).abs() / pdf;
+            //按吞吐量变化做俄罗斯轮盘赌
+            let q = (1.0 - beta_new.max_element() / beta.max_element()).max(0.0);
+            if sampler.sample_1d_d() < q {
+                break;
+            }
+            beta = beta_new / (1.0 - q);
+            ray = si.spawn_ray(&w_in);
+        }
+    }
+    //按本轮光子数收缩半径并累加通量
+    fn update(pixels: &mut [SppmPixel]) {
+        for pixel in pixels.iter_mut() {
+            if let (Some(vp), true) = (&pixel.vp, pixel.m > 0) {
+                let (n, radius) = shrink_radius(pixel.n, pixel.m, pixel.radius);
+                pixel.tau = (pixel.tau + vp.beta * pixel.phi) * (radius * radius)
+                    / (pixel.radius * pixel.radius);
+                pixel.n = n;
+                pixel.radius = radius;
+            }
+            pixel.phi = Color::ZERO;
+            pixel.m = 0;
+            pixel.vp = None;
+        }
+    }
+    //直接光照取平均，间接光照按已发射的光子总数归一化
+    fn buffer(&self, pixels: &[SppmPixel], size: UVec2, done: usize) -> Buffer {
+        let photons = (done * self.photons) as f32;
+        let colors = pixels
+            .iter()
+            .map(|pixel| {
+                let area = PI * pixel.radius * pixel.radius;
+                pixel.ld / done as f32 + pixel.tau / (photons * area)
+            })
+            .collect();
+        Buffer::from_pixels(size, colors)
+    }
+}
+
+impl IntegratorAble for SppmIntegrator {
+    fn is_next(&self, dept: &mut usize, _beta: Color, _sampler: &mut Sampler) -> Option<f32> {
+        *dept += 1;
+        (*dept < self.max_depth).then_some(1.0)
+    }
+    //没有光子时只有相机路径上的直接光照，用于逐像素调试渲染
+    fn fi(
+        &self,
+        ray: RayDiff,
+        sence: &Sence,
+        sampler: &mut Sampler,
+        #[cfg(debug_assertions)] _i: &mut i32,
+    ) -> Color {
+        self.trace_camera(ray, sence, sampler).0
+    }
+}
+
+//可见点的空间哈希网格，格子大小不小于最大搜索半径
+struct Grid {
+    bound: Bound<3>,
+    res: IVec3,
+    cells: Vec<Vec<usize>>,
+}
+
+impl Grid {
+    fn new(pixels: &[SppmPixel]) -> Self {
+        let mut bound: Option<Bound<3>> = None;
+        let mut max_radius = 0.0_f32;
+        for pixel in pixels.iter() {
+            let Some(vp) = &pixel.vp else {
+                continue;
+            };
+            if vp.beta.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+                continue;
+            }
+            let r = Vec3::splat(pixel.radius);
+            let b = Bound::<3>::new(vp.p - r, vp.p + r);
+            bound = Some(bound.map_or(b, |bound| bound.merage(b)));
+            max_radius = max_radius.max(pixel.radius);
+        }
+        let Some(bound) = bound else {
+            return Self {
+                bound: Bound::default(),
+                res: IVec3::ONE,
+                cells: vec![],
+            };
+        };
+        let diag = bound.max - bound.min;
+        let base = diag.max_element() / max_radius;
+        let res = (base * diag / diag.max_element())
+            .as_ivec3()
+            .max(IVec3::ONE);
+        let mut grid = Self {
+            bound,
+            res,
+            cells: vec![vec![]; pixels.len()],
+        };
+        for (index, pixel) in pixels.iter().enumerate() {
+            let Some(vp) = &pixel.vp else {
+                continue;
+            };
+            if vp.beta.abs_diff_eq(Color::ZERO, f32::EPSILON) {
+                continue;
+            }
+            let r = Vec3::splat(pixel.radius);
+            let min = grid.cell(vp.p - r);
+            let max = grid.cell(vp.p + r);
+            for z in min.z..=max.z {
+                for y in min.y..=max.y {
+                    for x in min.x..=max.x {
+                        let h = grid.hash(IVec3::new(x, y, z));
+                        grid.cells[h].push(index);
+                    }
+                }
+            }
+        }
+        grid
+    }
+    fn cell(&self, p: Vec3) -> IVec3 {
+        let offset = (p - self.bound.min) / (self.bound.max - self.bound.min);
+        (offset * self.res.as_vec3())
+            .as_ivec3()
+            .clamp(IVec3::ZERO, self.res - 1)
+    }
+    fn hash(&self, p: IVec3) -> usize {
+        let h = (p.x as u32).wrapping_mul(73856093)
+            ^ (p.y as u32).wrapping_mul(19349663)
+            ^ (p.z as u32).wrapping_mul(83492791);
+        h as usize % self.cells.len()
+    }
+    //p所在格子中的可见点，p在网格外时为空
+    fn lookup(&self, p: Vec3) -> &[usize] {
+        if self.cells.is_empty() || p.cmplt(self.bound.min).any() || p.cmpgt(self.bound.max).any() {
+            return &[];
+        }
+        &self.cells[self.hash(self.cell(p))]
+    }
+}
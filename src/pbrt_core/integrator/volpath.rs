@@ -0,0 +1,127 @@
+use glam::Vec3;
+
+use crate::pbrt_core::{
+    bxdf::BxDFType,
+    medium::MediumAble,
+    primitive::Primitive,
+    sampler::Sampler,
+    tool::{color::Color, sence::Sence, Ray, RayDiff},
+};
+
+use super::{
+    unifrom_sample_one_light, unifrom_sample_one_light_medium, IntegratorAble, Roulette,
+};
+
+//体积路径追踪积分器，在路径追踪的基础上处理参与介质的吸收与散射
+#[derive(Default)]
+pub struct VolPathIntegrator {
+    roulette: Roulette,
+}
+
+impl IntegratorAble for VolPathIntegrator {
+    fn is_next(&self, dept: &mut usize, beta: Color, sampler: &mut Sampler) -> Option<f32> {
+        self.roulette.next(dept, beta, sampler)
+    }
+    fn fi(
+        &self,
+        ray: RayDiff,
+        sence: &Sence,
+        sampler: &mut Sampler,
+        #[cfg(debug_assertions)] i: &mut i32,
+    ) -> Color {
+        let mut ans = Color::ZERO;
+        let mut dept = 0;
+        let mut beta: Vec3 = Vec3::ONE;
+        let mut ray = ray;
+        let mode = crate::pbrt_core::bxdf::TransportMode::Radiance;
+        let mut specular_bounce = false;
+        //当前光线所在的介质
+        let mut medium = sence.camera.medium;
+        loop {
+            let hit = sence.interacect(ray);
+            //在光线到交点之间采样介质散射点
+            let mut mi = None;
+            if let Some(m) = medium.and_then(|index| sence.media.get(index)) {
+                let mut o = ray.o;
+                o.t_max = hit.as_ref().map_or(f32::MAX, |item| item.common.time);
+                ans += beta * m.le(&o, sampler);
+                beta *= m.sample(&o, sampler, &mut mi);
+            }
+            if beta.max_element() <= 0.0 || beta.is_nan() {
+                break;
+            }
+            if let Some(mi) = mi {
+                ans += beta * unifrom_sample_one_light_medium(&mi, medium, sence, sampler);
+                //按相函数采样新方向，权重为1
+                let mut w_in = Vec3::ZERO;
+                mi.phase.sample_p(&mi.wo, &mut w_in, sampler.sample_2d_d());
+                specular_bounce = false;
+                match self.is_next(&mut dept, beta, sampler) {
+                    Some(p) => beta /= p,
+                    None => break,
+                }
+                ray = RayDiff::new(Ray::new(mi.p, w_in));
+                continue;
+            }
+            if dept == 0 || specular_bounce {
+                match &hit {
+                    Some(item) => ans += beta * item.le(ray),
+                    None => ans += beta * sence.sample_env_light(&ray),
+                }
+            }
+            let Some(mut item) = hit else {
+                break;
+            };
+            if item.light.is_some() {
+                break;
+            }
+            ans += beta * item.emission();
+            item.compute_scattering(ray, mode);
+            item.set_medium(medium);
+            //无材质的表面只是介质边界，直接穿过且不计反弹次数
+            let Some(bsdf) = &item.bsdf else {
+                medium = item.get_medium(&ray.o.dir);
+                ray = item.spawn_ray(&ray.o.dir);
+                continue;
+            };
+            ans += beta * unifrom_sample_one_light(&item, sence, sampler, true);
+            let w_out = -ray.o.dir;
+            let mut w_in = Vec3::default();
+            let mut pdf = 0.0;
+            let mut samped_type: u32 = 0;
+            let f = bsdf.sample_f(
+                &w_out,
+                &mut w_in,
+                sampler.sample_2d_d(),
+                &mut pdf,
+                BxDFType::All.into(),
+                &mut samped_type,
+            ) * w_in.dot(item.shading.n).abs()
+                / pdf;
+            if pdf <= 0.0 || f.is_nan() || f.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+                break;
+            }
+            beta *= f;
+            specular_bounce = samped_type & BxDFType::Specular as u32 != 0;
+            match self.is_next(&mut dept, beta, sampler) {
+                Some(p) => beta /= p,
+                None => break,
+            }
+            medium = item.get_medium(&w_in);
+            ray = item.spawn_ray(&w_in);
+            #[cfg(debug_assertions)]
+            {
+                *i += 1;
+            }
+        }
+        ans
+    }
+}
+
+impl VolPathIntegrator {
+    pub fn new(q: f32, max_path: usize, rr_depth: usize) -> Self {
+        Self {
+            roulette: Roulette::new(q, max_path, rr_depth),
+        }
+    }
+}
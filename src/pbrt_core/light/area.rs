@@ -1,7 +1,11 @@
+use std::f32::consts::FRAC_1_PI;
+
 use glam::{Vec2, Vec3};
 use log::info;
 
 use crate::pbrt_core::light::LightType;
+use crate::pbrt_core::sampler::cosine_sample_hemisphere;
+use crate::pbrt_core::tool::func::vec3_coordinate_system;
 use crate::pbrt_core::tool::color::Color;
 use crate::pbrt_core::{
     primitive::{shape::Shape, Primitive},
@@ -82,8 +86,46 @@ impl<'a> LightAble for DiffuseAreaLight<'a> {
             Vec3::ZERO
         }
     }
+    //返回立体角pdf，wi由光源指向表面
     fn pdf_li(&self, surface: &SurfaceInteraction, wi: &Vec3) -> f32 {
-        self.shape.pdf(&surface.common, wi)
+        let ray = surface.spawn_ray(&-*wi);
+        match self.shape.interacect(ray) {
+            Some(hit) => {
+                let cos = hit.common.normal.normalize().dot(*wi).abs();
+                if cos > 0.0 {
+                    self.shape.pdf(&surface.common, wi)
+                        * hit.common.p.distance_squared(surface.common.p)
+                        / cos
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        }
+    }
+    //在形状上均匀取点，沿法线一侧按余弦分布发射
+    fn sample_le(
+        &self,
+        u1: Vec2,
+        u2: Vec2,
+        light_common: &mut InteractionCommon,
+        dir: &mut Vec3,
+        pdf_pos: &mut f32,
+        pdf_dir: &mut f32,
+    ) -> Color {
+        self.shape.sample(u1, light_common, pdf_pos);
+        let n = light_common.normal.normalize();
+        light_common.normal = n;
+        let local = cosine_sample_hemisphere(u2);
+        let (mut x, mut y) = (Vec3::ZERO, Vec3::ZERO);
+        vec3_coordinate_system(n, &mut x, &mut y);
+        *dir = (x * local.x + y * local.y + n * local.z).normalize();
+        *pdf_dir = local.z * FRAC_1_PI;
+        self.l(light_common, dir)
+    }
+    fn pdf_le(&self, light_common: &InteractionCommon, dir: &Vec3, pdf_pos: &mut f32, pdf_dir: &mut f32) {
+        *pdf_pos = self.shape.pdf(light_common, dir);
+        *pdf_dir = light_common.normal.normalize().dot(*dir).max(0.0) * FRAC_1_PI;
     }
     fn get_type(&self) -> LightType {
         LightType::Area
@@ -0,0 +1,125 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3};
+
+use crate::pbrt_core::light::LightType;
+use crate::pbrt_core::sampler::concentric_sample_disk;
+use crate::pbrt_core::tool::func::vec3_coordinate_system;
+use crate::pbrt_core::tool::color::Color;
+use crate::pbrt_core::{
+    primitive::Primitive,
+    tool::{Bound, InteractionCommon, RayDiff, Visibility},
+};
+
+use super::LightAble;
+
+//平行光，位于无穷远处
+#[derive(Debug, Clone, Copy)]
+pub struct DistantLight {
+    //光线传播方向
+    dir: Vec3,
+    lemit: Vec3,
+    //场景包围球，由preprocess设置
+    world_center: Vec3,
+    world_radius: f32,
+    index: usize,
+}
+
+impl DistantLight {
+    pub fn new(lemit: Vec3, dir: Vec3, index: usize) -> Self {
+        Self {
+            dir: dir.normalize(),
+            lemit,
+            world_center: Vec3::ZERO,
+            world_radius: 0.0,
+            index,
+        }
+    }
+}
+
+impl LightAble for DistantLight {
+    #[inline]
+    fn pdf_li(&self, _surface: &crate::pbrt_core::tool::SurfaceInteraction, _wi: &Vec3) -> f32 {
+        0.0
+    }
+    #[inline]
+    fn sample_li(
+        &self,
+        surface_common: &InteractionCommon,
+        light_common: &mut InteractionCommon,
+        _u: Vec2,
+        wi: &mut Vec3,
+        pdf: &mut f32,
+        vis: &mut Visibility,
+    ) -> Vec3 {
+        *wi = self.dir;
+        *pdf = 1.0;
+        //阴影光线终点放在场景包围球之外
+        light_common.p = surface_common.p - self.dir * 2.0 * self.world_radius;
+        light_common.time = surface_common.time;
+        light_common.normal = self.dir;
+        *vis = Visibility {
+            a: *light_common,
+            b: *surface_common,
+        };
+        self.lemit
+    }
+    //在垂直于传播方向、覆盖场景包围球的圆盘上均匀采样起点
+    fn sample_le(
+        &self,
+        u1: Vec2,
+        _u2: Vec2,
+        light_common: &mut InteractionCommon,
+        dir: &mut Vec3,
+        pdf_pos: &mut f32,
+        pdf_dir: &mut f32,
+    ) -> Color {
+        let (mut v1, mut v2) = (Vec3::ZERO, Vec3::ZERO);
+        vec3_coordinate_system(self.dir, &mut v1, &mut v2);
+        let cd = concentric_sample_disk(u1);
+        let p_disk = self.world_center + self.world_radius * (cd.x * v1 + cd.y * v2);
+        light_common.p = p_disk - self.dir * self.world_radius;
+        light_common.normal = self.dir;
+        *dir = self.dir;
+        *pdf_pos = 1.0 / (PI * self.world_radius * self.world_radius);
+        *pdf_dir = 1.0;
+        self.lemit
+    }
+    fn pdf_le(&self, _light_common: &InteractionCommon, _dir: &Vec3, pdf_pos: &mut f32, pdf_dir: &mut f32) {
+        *pdf_pos = 1.0 / (PI * self.world_radius * self.world_radius);
+        *pdf_dir = 0.0;
+    }
+    fn preprocess(&mut self, bound: &Bound<3>) {
+        self.world_center = bound.center();
+        self.world_radius = (bound.max - self.world_center).length();
+    }
+    #[inline]
+    fn get_type(&self) -> LightType {
+        LightType::DeltaDirection
+    }
+    #[inline]
+    fn get_n_sample(&self) -> usize {
+        1
+    }
+    #[inline]
+    fn li(&self, _inter: &InteractionCommon, _w: &Vec3) -> Color {
+        self.lemit
+    }
+    fn get_index(&self) -> usize {
+        self.index
+    }
+}
+impl Primitive for DistantLight {
+    fn get_light(&self) -> Option<&dyn LightAble> {
+        Some(self)
+    }
+    fn world_bound(&self) -> Bound<3> {
+        Bound::<3>::new(
+            self.world_center - Vec3::splat(self.world_radius),
+            self.world_center + Vec3::splat(self.world_radius),
+        )
+    }
+    fn hit_p(&self, _ray: &RayDiff) -> bool {
+        false
+    }
+}
@@ -1,10 +1,14 @@
-use glam::{Mat4, Vec2, Vec3};
+use glam::{Mat4, UVec2, Vec2, Vec3};
 use std::f32::consts::PI;
 use std::sync::Arc;
 
 use crate::pbrt_core::light::LightAble;
 use crate::pbrt_core::primitive::Primitive;
+use crate::pbrt_core::sampler::concentric_sample_disk;
+use crate::pbrt_core::sampler::distribution_2d::Distribution2D;
 use crate::pbrt_core::texture::Texture;
+use crate::pbrt_core::tool::color::Color;
+use crate::pbrt_core::tool::func::vec3_coordinate_system;
 use crate::pbrt_core::tool::{Bound, InteractionCommon, RayDiff, SurfaceInteraction, Visibility};
 
 #[derive(Debug)]
@@ -13,28 +17,103 @@ pub struct InfiniteLight {
     center: Vec3,
     color: Arc<dyn Texture>,
     obj_to_world: Mat4,
+    world_to_obj: Mat4,
     lemit: Vec3,
-    index:usize
+    //按亮度构建的采样分布
+    distribution: Distribution2D,
+    //场景几何包围球，由preprocess设置，用于发射光线
+    world_center: Vec3,
+    world_radius: f32,
+    index: usize,
 }
 
 impl InfiniteLight {
+    //非图像纹理时使用的分布分辨率
+    const DEFAULT_RESOLUTION: UVec2 = UVec2::new(64, 32);
     pub fn new(
         r: f32,
         center: Vec3,
         color: Arc<dyn Texture>,
         obj_to_world: Mat4,
         lemit: Vec3,
-        index:usize
+        index: usize,
     ) -> Self {
+        let distribution = Self::compute_distribution(color.as_ref());
         Self {
             r,
             color,
             center,
             obj_to_world,
+            world_to_obj: obj_to_world.inverse(),
             lemit,
-            index
+            distribution,
+            world_center: center,
+            world_radius: r,
+            index,
         }
     }
+    //按纹素亮度乘以sin(theta)构建分布，抵消极点处的面积压缩
+    fn compute_distribution(color: &dyn Texture) -> Distribution2D {
+        let resolution = color.resolution().unwrap_or(Self::DEFAULT_RESOLUTION);
+        let (nu, nv) = (resolution.x.max(1) as usize, resolution.y.max(1) as usize);
+        let mut func = Vec::with_capacity(nu * nv);
+        for v in 0..nv {
+            let fv = (v as f32 + 0.5) / nv as f32;
+            let sin_theta = (PI * fv).sin();
+            for u in 0..nu {
+                let fu = (u as f32 + 0.5) / nu as f32;
+                let common = InteractionCommon {
+                    uv: Vec2::new(fu, fv),
+                    ..Default::default()
+                };
+                let color = color.evaluate(&common);
+                let y = color.dot(Vec3::new(0.212_671, 0.715_160, 0.072_169));
+                func.push(y.max(0.0) * sin_theta);
+            }
+        }
+        Distribution2D::new(&func, nu, nv)
+    }
+    //世界空间方向对应的贴图坐标
+    #[inline]
+    fn dir_to_uv(&self, dir: Vec3) -> Vec2 {
+        let w = self.world_to_obj.transform_vector3(dir).normalize();
+        let mut phi = (w.y).atan2(w.x);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        let theta = w.z.clamp(-1.0, 1.0).acos();
+        Vec2::new(phi / (2.0 * PI), theta / PI)
+    }
+    //贴图坐标对应的世界空间方向(指向环境)
+    #[inline]
+    fn uv_to_dir(&self, uv: Vec2) -> Vec3 {
+        let theta = uv.y * PI;
+        let phi = uv.x * 2.0 * PI;
+        let (sin_t, cos_t) = theta.sin_cos();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        self.obj_to_world
+            .transform_vector3(Vec3::new(sin_t * cos_phi, sin_t * sin_phi, cos_t))
+            .normalize()
+    }
+    //贴图空间pdf换算为立体角pdf
+    #[inline]
+    fn solid_angle_pdf(map_pdf: f32, uv: Vec2) -> f32 {
+        let sin_theta = (uv.y * PI).sin();
+        if sin_theta == 0.0 {
+            0.0
+        } else {
+            map_pdf / (2.0 * PI * PI * sin_theta)
+        }
+    }
+    //沿dir方向看到的环境辐射
+    #[inline]
+    fn lookup(&self, dir: Vec3) -> Color {
+        let common = InteractionCommon {
+            uv: self.dir_to_uv(dir),
+            ..Default::default()
+        };
+        self.color.evaluate(&common) * self.lemit
+    }
 }
 
 impl Primitive for InfiniteLight {
@@ -48,58 +127,29 @@ impl Primitive for InfiniteLight {
     }
     fn interacect(&self, _ray: RayDiff) -> Option<SurfaceInteraction> {
         None
-        // let mut interaction = SurfaceInteraction::default();
-        // interaction.light=self.get_light();
-        // interaction.common.normal=-ray.o.dir+Vec3::ONE*0.002;
-        // interaction.common.time=f32::MAX;
-        // Some(interaction)
     }
-    fn hit_p(&self,_ray:&RayDiff)->bool {
+    fn hit_p(&self, _ray: &RayDiff) -> bool {
         false
     }
 }
 
 impl LightAble for InfiniteLight {
-
-    fn pdf_li(&self, _surface: &SurfaceInteraction<'_>, _w_in: &Vec3) -> f32 {
-        1.0
+    //wi与sample_li一致，由光源指向表面
+    fn pdf_li(&self, _surface: &SurfaceInteraction<'_>, wi: &Vec3) -> f32 {
+        let uv = self.dir_to_uv(-*wi);
+        Self::solid_angle_pdf(self.distribution.pdf(uv), uv)
     }
 
     fn le(&self, ray: &RayDiff) -> Vec3 {
-        let w = self.obj_to_world.inverse().transform_vector3(ray.o.dir).normalize();
-        let mut phi = (w.y).atan2(w.x);
-        //uv计算
-        if phi < 0.0 {
-            phi += 2.0 * PI;
-        }
-        let theta = w.z.clamp(-1.0, 1.0).acos();
-        let v = theta / PI;
-        let u = phi / (2.0 * PI);
-        let uv = Vec2::new(u, v);
-        let mut common = InteractionCommon::default();
-        common.uv = uv;
-        self.color.evaluate(&common) * self.lemit
-        //dpdu,dpdv计算
+        self.lookup(ray.o.dir)
     }
 
     fn get_type(&self) -> super::LightType {
         super::LightType::Infinite
     }
 
-    fn li(&self, inter: &InteractionCommon, wi: &Vec3) -> crate::pbrt_core::tool::color::Color {
-        let mut phi = wi.y.atan2(wi.x);
-        let theta = wi.z;
-        if phi < 0.0 {
-            phi += 2.0 * PI;
-        }
-        let (sin_t, cos_t) = theta.sin_cos();
-        let (sin_phi, cos_phi) = phi.sin_cos();
-        let w_in =
-            self.obj_to_world
-                .transform_vector3(Vec3::new(sin_t * cos_phi, sin_t * sin_phi, cos_t));
-        let hit_p = inter.p + w_in * 2.0 * self.r;
-        let common = InteractionCommon::new(*wi, hit_p, *wi, 01.0, Vec2::new(phi / 2.0 * PI, theta / PI));
-        self.color.evaluate(&common)
+    fn li(&self, _inter: &InteractionCommon, wi: &Vec3) -> Color {
+        self.lookup(-*wi)
     }
 
     fn get_n_sample(&self) -> usize {
@@ -115,22 +165,64 @@ impl LightAble for InfiniteLight {
         pdf: &mut f32,
         vis: &mut Visibility,
     ) -> Vec3 {
-        let phi=u.x*2.0*PI;
-        let theta=u.y*PI;
-        let (sin_t, cos_t) = theta.sin_cos();
-        let (sin_phi, cos_phi) = phi.sin_cos();
-        *wi=Vec3::new(sin_t*cos_phi, sin_t*sin_phi, cos_t);
-        let p=surface_common.p+*wi*self.r*2.0;
-        *light_common=InteractionCommon::new(*wi, p, -*wi, 0.01, u);
+        let mut map_pdf = 0.0;
+        let uv = self.distribution.sample_continuous(u, &mut map_pdf);
+        if map_pdf == 0.0 {
+            *pdf = 0.0;
+            return Color::ZERO;
+        }
+        //贴图坐标转换为球面方向，并计算立体角上的pdf
+        let dir = self.uv_to_dir(uv);
+        *pdf = Self::solid_angle_pdf(map_pdf, uv);
+        *wi = -dir;
+        let p = surface_common.p + dir * self.r * 2.0;
+        *light_common = InteractionCommon::new(*wi, p, *wi, surface_common.time, uv);
         *vis = Visibility {
             a: *light_common,
             b: *surface_common,
         };
-        *pdf = 1.0;
-        self.color.evaluate(light_common)*self.lemit
+        self.color.evaluate(light_common) * self.lemit
     }
-    fn get_index(&self)->usize {
-        self.index   
+    //按环境贴图采样方向，再在垂直于该方向、覆盖场景包围球的圆盘上采样起点
+    fn sample_le(
+        &self,
+        u1: Vec2,
+        u2: Vec2,
+        light_common: &mut InteractionCommon,
+        dir: &mut Vec3,
+        pdf_pos: &mut f32,
+        pdf_dir: &mut f32,
+    ) -> Color {
+        let mut map_pdf = 0.0;
+        let uv = self.distribution.sample_continuous(u1, &mut map_pdf);
+        if map_pdf == 0.0 {
+            *pdf_pos = 0.0;
+            *pdf_dir = 0.0;
+            return Color::ZERO;
+        }
+        let d = -self.uv_to_dir(uv);
+        let (mut v1, mut v2) = (Vec3::ZERO, Vec3::ZERO);
+        vec3_coordinate_system(d, &mut v1, &mut v2);
+        let cd = concentric_sample_disk(u2);
+        let p_disk = self.world_center + self.world_radius * (cd.x * v1 + cd.y * v2);
+        light_common.p = p_disk - d * self.world_radius;
+        light_common.normal = d;
+        light_common.uv = uv;
+        *dir = d;
+        *pdf_dir = Self::solid_angle_pdf(map_pdf, uv);
+        *pdf_pos = 1.0 / (PI * self.world_radius * self.world_radius);
+        self.color.evaluate(light_common) * self.lemit
+    }
+    fn pdf_le(&self, _light_common: &InteractionCommon, dir: &Vec3, pdf_pos: &mut f32, pdf_dir: &mut f32) {
+        let uv = self.dir_to_uv(-*dir);
+        *pdf_dir = Self::solid_angle_pdf(self.distribution.pdf(uv), uv);
+        *pdf_pos = 1.0 / (PI * self.world_radius * self.world_radius);
+    }
+    fn preprocess(&mut self, bound: &Bound<3>) {
+        self.world_center = bound.center();
+        self.world_radius = (bound.max - self.world_center).length();
+    }
+    fn get_index(&self) -> usize {
+        self.index
     }
-
 }
@@ -8,14 +8,17 @@ use crate::pbrt_core::tool::color::Color;
 use crate::pbrt_core::tool::RayDiff;
 use glam::{Vec2, Vec3};
 
-use self::{area::AreaLight, inf::InfiniteLight, point::Point};
+use self::{
+    area::AreaLight, distant::DistantLight, inf::InfiniteLight, point::Point, spot::SpotLight,
+};
 
 use super::{
     primitive::{shape::Shape, Primitive},
-    tool::{InteractionCommon, SurfaceInteraction, Visibility},
+    tool::{Bound, InteractionCommon, SurfaceInteraction, Visibility},
 };
 
 pub mod area;
+pub mod distant;
 pub mod inf;
 pub mod point;
 pub mod spot;
@@ -25,6 +28,8 @@ pub enum Light {
     AreaLight(Box<dyn AreaLight>),
     PointLight(Box<Point>),
     Infinite(Box<InfiniteLight>),
+    Spot(Box<SpotLight>),
+    Distant(Box<DistantLight>),
 }
 
 impl Primitive for Light {
@@ -33,6 +38,8 @@ impl Primitive for Light {
             Light::AreaLight(ref area) => area.get_light(),
             Light::PointLight(ref point) => point.get_light(),
             Light::Infinite(ref inf) => inf.get_light(),
+            Light::Spot(spot) => spot.get_light(),
+            Light::Distant(distant) => distant.get_light(),
         }
     }
     fn compute_scattering(&self, isct: &mut SurfaceInteraction, mode: super::bxdf::TransportMode) {
@@ -40,6 +47,8 @@ impl Primitive for Light {
             Light::AreaLight(area) => area.compute_scattering(isct, mode),
             Light::PointLight(point) => point.compute_scattering(isct, mode),
             Light::Infinite(ref infinite) => infinite.compute_scattering(isct, mode),
+            Light::Spot(spot) => spot.compute_scattering(isct, mode),
+            Light::Distant(distant) => distant.compute_scattering(isct, mode),
         }
     }
     fn interacect(&self, ray: super::tool::RayDiff) -> Option<SurfaceInteraction> {
@@ -47,6 +56,8 @@ impl Primitive for Light {
             Light::AreaLight(area) => area.interacect(ray),
             Light::PointLight(point) => point.interacect(ray),
             Light::Infinite(ref infinite) => infinite.interacect(ray),
+            Light::Spot(spot) => spot.interacect(ray),
+            Light::Distant(distant) => distant.interacect(ray),
         }
     }
     fn world_bound(&self) -> super::tool::Bound<3> {
@@ -54,6 +65,8 @@ impl Primitive for Light {
             Light::AreaLight(area) => area.world_bound(),
             Light::PointLight(point) => point.world_bound(),
             Light::Infinite(ref infinite) => infinite.world_bound(),
+            Light::Spot(spot) => spot.world_bound(),
+            Light::Distant(distant) => distant.world_bound(),
         }
     }
     fn hit_p(&self, ray: &RayDiff) -> bool {
@@ -61,6 +74,8 @@ impl Primitive for Light {
             Light::AreaLight(area) => area.hit_p(ray),
             Light::PointLight(point) => point.hit_p(ray),
             Light::Infinite(ref infinite) => infinite.hit_p(ray),
+            Light::Spot(spot) => spot.hit_p(ray),
+            Light::Distant(distant) => distant.hit_p(ray),
         }
     }
 }
@@ -87,6 +102,8 @@ impl LightAble for Light {
             Light::AreaLight(area) => area.sample_li(surface_common, light_common, u, wi, pdf, vis),
             Light::PointLight(p) => p.sample_li(surface_common, light_common, u, wi, pdf, vis),
             Light::Infinite(inf) => inf.sample_li(surface_common, light_common, u, wi, pdf, vis),
+            Light::Spot(spot) => spot.sample_li(surface_common, light_common, u, wi, pdf, vis),
+            Light::Distant(distant) => distant.sample_li(surface_common, light_common, u, wi, pdf, vis),
         }
     }
     fn pdf_li(&self, surface: &SurfaceInteraction, wi: &Vec3) -> f32 {
@@ -94,6 +111,8 @@ impl LightAble for Light {
             Light::AreaLight(area) => area.pdf_li(surface, wi),
             Light::PointLight(p) => p.pdf_li(surface, wi),
             Light::Infinite(inf) => inf.pdf_li(surface, wi),
+            Light::Spot(spot) => spot.pdf_li(surface, wi),
+            Light::Distant(distant) => distant.pdf_li(surface, wi),
         }
     }
 
@@ -102,6 +121,8 @@ impl LightAble for Light {
             Light::AreaLight(_) => LightType::Area,
             Light::PointLight(_) => LightType::DeltaPosition,
             Light::Infinite(_) => LightType::Infinite,
+            Light::Spot(_) => LightType::DeltaPosition,
+            Light::Distant(_) => LightType::DeltaDirection,
         }
     }
 
@@ -114,6 +135,8 @@ impl LightAble for Light {
             Light::AreaLight(area) => area.get_n_sample(),
             Light::PointLight(point) => point.get_n_sample(),
             Light::Infinite(inf) => inf.get_n_sample(),
+            Light::Spot(spot) => spot.get_n_sample(),
+            Light::Distant(distant) => distant.get_n_sample(),
         }
     }
 
@@ -122,6 +145,43 @@ impl LightAble for Light {
             Light::AreaLight(area) => area.get_index(),
             Light::PointLight(p) => p.get_index(),
             Light::Infinite(inf) => inf.get_index(),
+            Light::Spot(spot) => spot.get_index(),
+            Light::Distant(distant) => distant.get_index(),
+        }
+    }
+    fn preprocess(&mut self, bound: &Bound<3>) {
+        match self {
+            Self::AreaLight(area) => area.preprocess(bound),
+            Self::PointLight(point) => point.preprocess(bound),
+            Self::Infinite(inf) => inf.preprocess(bound),
+            Self::Spot(spot) => spot.preprocess(bound),
+            Self::Distant(distant) => distant.preprocess(bound),
+        }
+    }
+    fn sample_le(
+        &self,
+        u1: Vec2,
+        u2: Vec2,
+        light_common: &mut InteractionCommon,
+        dir: &mut Vec3,
+        pdf_pos: &mut f32,
+        pdf_dir: &mut f32,
+    ) -> Color {
+        match self {
+            Self::AreaLight(area) => area.sample_le(u1, u2, light_common, dir, pdf_pos, pdf_dir),
+            Self::PointLight(point) => point.sample_le(u1, u2, light_common, dir, pdf_pos, pdf_dir),
+            Self::Infinite(inf) => inf.sample_le(u1, u2, light_common, dir, pdf_pos, pdf_dir),
+            Self::Spot(spot) => spot.sample_le(u1, u2, light_common, dir, pdf_pos, pdf_dir),
+            Self::Distant(distant) => distant.sample_le(u1, u2, light_common, dir, pdf_pos, pdf_dir),
+        }
+    }
+    fn pdf_le(&self, light_common: &InteractionCommon, dir: &Vec3, pdf_pos: &mut f32, pdf_dir: &mut f32) {
+        match self {
+            Self::AreaLight(area) => area.pdf_le(light_common, dir, pdf_pos, pdf_dir),
+            Self::PointLight(point) => point.pdf_le(light_common, dir, pdf_pos, pdf_dir),
+            Self::Infinite(inf) => inf.pdf_le(light_common, dir, pdf_pos, pdf_dir),
+            Self::Spot(spot) => spot.pdf_le(light_common, dir, pdf_pos, pdf_dir),
+            Self::Distant(distant) => distant.pdf_le(light_common, dir, pdf_pos, pdf_dir),
         }
     }
     fn le(&self, ray: &RayDiff) -> Color {
@@ -129,6 +189,8 @@ impl LightAble for Light {
             Self::Infinite(inf) => inf.le(ray),
             Self::AreaLight(area) => area.le(ray),
             Self::PointLight(point) => point.le(ray),
+            Self::Spot(spot) => spot.le(ray),
+            Self::Distant(distant) => distant.le(ray),
 
         }
     }
@@ -151,7 +213,29 @@ pub trait LightAble: Debug + Primitive {
     fn le(&self, _ray: &RayDiff) -> Color {
         Color::ZERO
     }
+    ///从光源发射一条光线用于光路径，返回发射的辐射度，位置与方向的pdf分开给出
+    ///点光源的法线为零，无法发射光线的光源pdf为零
+    fn sample_le(
+        &self,
+        _u1: Vec2,
+        _u2: Vec2,
+        _light_common: &mut InteractionCommon,
+        _dir: &mut Vec3,
+        pdf_pos: &mut f32,
+        pdf_dir: &mut f32,
+    ) -> Color {
+        *pdf_pos = 0.0;
+        *pdf_dir = 0.0;
+        Color::ZERO
+    }
+    //从光源上一点沿dir发射光线的pdf
+    fn pdf_le(&self, _light_common: &InteractionCommon, _dir: &Vec3, pdf_pos: &mut f32, pdf_dir: &mut f32) {
+        *pdf_pos = 0.0;
+        *pdf_dir = 0.0;
+    }
     fn get_type(&self) -> LightType;
+    //场景构建完成后，根据场景包围盒初始化
+    fn preprocess(&mut self, _bound: &Bound<3>) {}
     fn get_n_sample(&self) -> usize;
     fn get_index(&self) -> usize;
 }
@@ -171,6 +255,9 @@ impl LightType {
             _ => false,
         }
     }
+    pub fn is_direction(flag: LightType) -> bool {
+        matches!(flag, LightType::DeltaDirection)
+    }
     pub fn is_inf(flag: LightType)->bool{
         match flag {
             LightType::Infinite=>true,
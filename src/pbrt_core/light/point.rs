@@ -1,10 +1,12 @@
 
 
+use std::f32::consts::FRAC_1_PI;
+
 use glam::{Vec2, Vec3};
 
 use crate::pbrt_core::{
     primitive::Primitive,
-    tool::{Bound, InteractionCommon, Visibility, RayDiff},
+    tool::{func::unifrom_sample_sphere, Bound, InteractionCommon, Visibility, RayDiff},
 };
 use crate::pbrt_core::light::LightType;
 use crate::pbrt_core::tool::color::Color;
@@ -62,6 +64,19 @@ impl LightAble for Point {
     fn get_index(&self)->usize {
         self.index   
     }
+    //向球面均匀发射
+    fn sample_le(&self, u1: Vec2, _u2: Vec2, light_common: &mut InteractionCommon, dir: &mut Vec3, pdf_pos: &mut f32, pdf_dir: &mut f32) -> Color {
+        *dir = unifrom_sample_sphere(u1);
+        light_common.p = self.p;
+        light_common.normal = Vec3::ZERO;
+        *pdf_pos = 1.0;
+        *pdf_dir = FRAC_1_PI / 4.0;
+        self.lemit
+    }
+    fn pdf_le(&self, _light_common: &InteractionCommon, _dir: &Vec3, pdf_pos: &mut f32, pdf_dir: &mut f32) {
+        *pdf_pos = 0.0;
+        *pdf_dir = FRAC_1_PI / 4.0;
+    }
 }
 impl Primitive for Point {
     fn get_area(&self) -> f32 {
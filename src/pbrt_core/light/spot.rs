@@ -0,0 +1,153 @@
+use std::f32::consts::PI;
+
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::pbrt_core::light::LightType;
+use crate::pbrt_core::tool::color::Color;
+use crate::pbrt_core::{
+    primitive::Primitive,
+    tool::{
+        func::{smoothstep, spherical_direction, vec3_coordinate_system},
+        Bound, InteractionCommon, RayDiff, Visibility,
+    },
+};
+
+use super::LightAble;
+
+//聚光灯，沿局部+z轴发光
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLight {
+    p: Vec3,
+    dir: Vec3,
+    lemit: Vec3,
+    //光锥边缘角余弦
+    cos_total_width: f32,
+    //开始衰减角余弦
+    cos_falloff_start: f32,
+    index: usize,
+}
+
+impl SpotLight {
+    //角度单位为度
+    pub fn new(
+        lemit: Vec3,
+        light_to_world: Mat4,
+        total_width: f32,
+        falloff_start: f32,
+        index: usize,
+    ) -> Self {
+        let total_width = total_width.to_radians();
+        let falloff_start = falloff_start.to_radians().min(total_width);
+        Self {
+            p: light_to_world.transform_point3(Vec3::ZERO),
+            dir: light_to_world.transform_vector3(Vec3::Z).normalize(),
+            lemit,
+            cos_total_width: total_width.cos(),
+            cos_falloff_start: falloff_start.cos(),
+            index,
+        }
+    }
+    fn cone_pdf(&self) -> f32 {
+        1.0 / (2.0 * PI * (1.0 - self.cos_total_width))
+    }
+    //光锥内的强度衰减
+    #[inline]
+    fn falloff(&self, w: Vec3) -> f32 {
+        smoothstep(
+            w.dot(self.dir),
+            self.cos_total_width,
+            self.cos_falloff_start,
+        )
+    }
+}
+
+impl LightAble for SpotLight {
+    #[inline]
+    fn pdf_li(&self, _surface: &crate::pbrt_core::tool::SurfaceInteraction, _wi: &Vec3) -> f32 {
+        0.0
+    }
+    #[inline]
+    fn sample_li(
+        &self,
+        surface_common: &InteractionCommon,
+        light_common: &mut InteractionCommon,
+        _u: Vec2,
+        wi: &mut Vec3,
+        pdf: &mut f32,
+        vis: &mut Visibility,
+    ) -> Vec3 {
+        *wi = (surface_common.p - self.p).normalize();
+        *pdf = 1.0;
+        light_common.p = self.p;
+        light_common.time = surface_common.time;
+        light_common.normal = -*wi;
+        *vis = Visibility {
+            a: *light_common,
+            b: *surface_common,
+        };
+        self.lemit * self.falloff(*wi) / self.p.distance_squared(surface_common.p)
+    }
+    #[inline]
+    fn get_type(&self) -> LightType {
+        LightType::DeltaPosition
+    }
+    #[inline]
+    fn get_n_sample(&self) -> usize {
+        1
+    }
+    #[inline]
+    fn li(&self, inter: &InteractionCommon, _w: &Vec3) -> Color {
+        let w = (inter.p - self.p).normalize();
+        self.lemit * self.falloff(w) / self.p.distance_squared(inter.p)
+    }
+    fn get_index(&self) -> usize {
+        self.index
+    }
+    //在光锥内均匀发射
+    fn sample_le(
+        &self,
+        u1: Vec2,
+        _u2: Vec2,
+        light_common: &mut InteractionCommon,
+        dir: &mut Vec3,
+        pdf_pos: &mut f32,
+        pdf_dir: &mut f32,
+    ) -> Color {
+        let cos_theta = 1.0 - u1.x + u1.x * self.cos_total_width;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let local = spherical_direction(sin_theta, cos_theta, 2.0 * PI * u1.y);
+        let (mut x, mut y) = (Vec3::ZERO, Vec3::ZERO);
+        vec3_coordinate_system(self.dir, &mut x, &mut y);
+        *dir = (x * local.x + y * local.y + self.dir * local.z).normalize();
+        light_common.p = self.p;
+        light_common.normal = Vec3::ZERO;
+        *pdf_pos = 1.0;
+        *pdf_dir = self.cone_pdf();
+        self.lemit * self.falloff(*dir)
+    }
+    fn pdf_le(
+        &self,
+        _light_common: &InteractionCommon,
+        dir: &Vec3,
+        pdf_pos: &mut f32,
+        pdf_dir: &mut f32,
+    ) {
+        *pdf_pos = 0.0;
+        *pdf_dir = if dir.normalize().dot(self.dir) >= self.cos_total_width {
+            self.cone_pdf()
+        } else {
+            0.0
+        };
+    }
+}
+impl Primitive for SpotLight {
+    fn get_light(&self) -> Option<&dyn LightAble> {
+        Some(self)
+    }
+    fn world_bound(&self) -> Bound<3> {
+        Bound::<3>::new(Vec3::splat(-0.0003) + self.p, Vec3::splat(0.0003) + self.p)
+    }
+    fn hit_p(&self, _ray: &RayDiff) -> bool {
+        false
+    }
+}
@@ -1,279 +1,381 @@
 use std::sync::Arc;
 
-use glam::{u32::UVec3, Mat4, Quat, UVec4, Vec2, Vec3, Vec4};
-use gltf::{buffer::Data, import, Buffer};
+use glam::{u32::UVec3, Mat4, Vec2, Vec3, Vec4};
+use gltf::{buffer::Data, import, khr_lights_punctual::Kind, Buffer};
+use log::warn;
 
 use crate::pbrt_core::primitive::mesh::Mesh;
 
-use super::super::{
-    material::{matte::Matte, pbr::PbrMaterial, Material},
-    texture::{constant::ConstantTexture, image::ImageTexture, Texture},
-    tool::mipmap::{ImageData, MipMap},
+use super::{
+    super::{
+        camera::{Camera, CameraMode},
+        material::{pbr::PbrMaterial, Material},
+        texture::{constant::ConstantTexture, image::ImageTexture, scale::ScaleTexture, Texture},
+        tool::mipmap::{ImageData, MipMap},
+    },
+    tomlload::LightToml,
+    tomlload::TransformToml,
 };
 
+//glTF文件中网格以外的数据
+#[derive(Default)]
+pub struct GltfData {
+    //每个三角形对应的材质下标，None时使用场景文件指定的材质
+    pub triangle: Vec<Option<usize>>,
+    pub materials: Vec<Box<dyn Material>>,
+    //KHR_lights_punctual光源，位于glTF场景空间
+    pub lights: Vec<LightToml>,
+}
 pub struct GltfLoad;
 impl GltfLoad {
-    pub fn load<'a, 'b>(path: &str) -> anyhow::Result<(Mesh, Vec<Vec<UVec3>>)>
-    where
-        'b: 'a,
-    {
-        if let Ok((gltf, buffer, _)) = import(path) {
-            let mut pos_index = vec![];
-            let mut norm_index = vec![];
-            let mut uv_index = vec![];
-            //加载材质
-            // material = &*load_material(images, &gltf).leak();
-            //加载shape
-            let (all_point, all_normal, all_uv, index_vec, nodes, transform_vec, det_index_vec) =
-                load_node(&gltf, buffer);
-            let mesh = Mesh::new(all_point, all_normal, all_uv, vec![]);
-            // let mesh = Arc::new(mesh);
-            {
-                for i in 0..nodes {
-                    let index = index_vec.get(i).unwrap();
-                    let det_index = det_index_vec[i];
-                    // let transform = transform_vec.get(i).unwrap();
-                    for i in index {
-                        // let w = i.w as usize;
-                        // let a = material.get(w);
-                        pos_index.push(*i + det_index);
-                        norm_index.push(*i + det_index);
-                        uv_index.push(*i + det_index);
-                    }
+    pub fn load(path: &str) -> anyhow::Result<(Mesh, Vec<Vec<UVec3>>, GltfData)> {
+        let (gltf, buffer, images) = import(path)?;
+        //加载材质
+        let materials = load_material(images, &gltf);
+        //从场景根节点开始遍历节点树
+        let mut data = SceneData::default();
+        for node in root_nodes(&gltf) {
+            load_node(node, Mat4::IDENTITY, &buffer, &mut data);
+        }
+        let SceneData {
+            point,
+            normal,
+            uv,
+            index,
+            material_index,
+            lights,
+        } = data;
+        let mesh = Mesh::new(point, normal, uv, vec![]);
+        Ok((
+            mesh,
+            vec![index.clone(), index.clone(), index],
+            GltfData {
+                triangle: material_index,
+                materials,
+                lights,
+            },
+        ))
+    }
+    //节点树中遇到的第一个相机，只解析文档不加载缓冲区，transform为glTF文件作为物体时的变换
+    pub fn load_camera(path: &str, size: Vec2, transform: Mat4) -> anyhow::Result<Option<Camera>> {
+        let gltf = gltf::Gltf::open(path)?;
+        let camera = root_nodes(&gltf)
+            .into_iter()
+            .find_map(|node| find_camera(node, transform));
+        Ok(camera.map(|(camera, transform)| {
+            //glTF相机朝向局部-z轴，+y轴向上
+            let eye = transform.transform_point3(Vec3::ZERO);
+            let target = eye + transform.transform_vector3(Vec3::NEG_Z).normalize();
+            let up = transform.transform_vector3(Vec3::Y).normalize();
+            match camera.projection() {
+                gltf::camera::Projection::Perspective(p) => {
+                    Camera::new(eye, target, up, size, CameraMode::P, p.yfov().to_degrees())
                 }
-            };
-            return Ok((mesh, vec![pos_index, norm_index, uv_index]));
+                //xmag与ymag为相机局部空间中视口的半宽与半高，随节点缩放
+                gltf::camera::Projection::Orthographic(o) => {
+                    let scale = Vec2::new(
+                        transform.x_axis.truncate().length(),
+                        transform.y_axis.truncate().length(),
+                    );
+                    let mut camera = Camera::new(eye, target, up, size, CameraMode::O, 0.0);
+                    camera.set_extent(Vec2::new(o.xmag(), o.ymag()) * scale);
+                    camera
+                }
+            }
+        }))
+    }
+}
+//合并后的场景数据，已变换到glTF场景空间
+#[derive(Default)]
+struct SceneData {
+    point: Vec<Vec3>,
+    normal: Vec<Vec3>,
+    uv: Vec<Vec2>,
+    index: Vec<UVec3>,
+    material_index: Vec<Option<usize>>,
+    lights: Vec<LightToml>,
+}
+//默认场景的根节点，没有场景时取所有不作为子节点的节点
+fn root_nodes(gltf: &gltf::Document) -> Vec<gltf::Node<'_>> {
+    match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+        Some(scene) => scene.nodes().collect(),
+        None => {
+            let children = gltf
+                .nodes()
+                .flat_map(|x| x.children())
+                .map(|x| x.index())
+                .collect::<Vec<_>>();
+            gltf.nodes()
+                .filter(|x| !children.contains(&x.index()))
+                .collect()
         }
-        unimplemented!()
     }
 }
-
-fn load_mesh(
-    mesh: gltf::Mesh,
-    // material_vec: &[Box<dyn Material>],
-    buffer: &Vec<Data>,
-    index: &mut Vec<UVec3>,
-    point: &mut Vec<Vec3>,
-    normal: &mut Vec<Vec3>,
-    uv: &mut Vec<Vec2>,
-) {
+//节点的局部变换，矩阵与TRS两种形式
+fn node_transform(node: &gltf::Node) -> Mat4 {
+    match node.transform() {
+        gltf::scene::Transform::Matrix { matrix } => Mat4::from_cols_array_2d(&matrix),
+        gltf::scene::Transform::Decomposed {
+            translation,
+            rotation,
+            scale,
+        } => Mat4::from_scale_rotation_translation(
+            Vec3::from_array(scale),
+            glam::Quat::from_array(rotation),
+            Vec3::from_array(translation),
+        ),
+    }
+}
+fn find_camera(node: gltf::Node, parent: Mat4) -> Option<(gltf::Camera, Mat4)> {
+    let transform = parent * node_transform(&node);
+    match node.camera() {
+        Some(camera) => Some((camera, transform)),
+        None => node
+            .children()
+            .find_map(|child| find_camera(child, transform)),
+    }
+}
+fn load_node(node: gltf::Node, parent: Mat4, buffer: &[Data], data: &mut SceneData) {
+    let transform = parent * node_transform(&node);
+    if let Some(mesh) = node.mesh() {
+        load_mesh(mesh, transform, buffer, data);
+    }
+    if let Some(light) = node.light() {
+        load_light(light, transform, data);
+    }
+    for child in node.children() {
+        load_node(child, transform, buffer, data);
+    }
+}
+fn load_light(light: gltf::khr_lights_punctual::Light, transform: Mat4, data: &mut SceneData) {
+    let lemit = Vec3::from_array(light.color()) * light.intensity();
+    let point = transform.transform_point3(Vec3::ZERO);
+    match light.kind() {
+        Kind::Point => data.lights.push(LightToml::Point { point, lemit }),
+        //平行光沿局部-z轴传播
+        Kind::Directional => data.lights.push(LightToml::Distant {
+            lemit,
+            dir: transform.transform_vector3(Vec3::NEG_Z).normalize(),
+        }),
+        //glTF聚光灯朝向局部-z轴
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => data.lights.push(LightToml::Spot {
+            trans: TransformToml::looking_at(transform.transform_vector3(Vec3::NEG_Z)),
+            point,
+            lemit,
+            end_angle: outer_cone_angle.to_degrees(),
+            start_angle: inner_cone_angle.to_degrees(),
+        }),
+    }
+}
+fn load_mesh(mesh: gltf::Mesh, transform: Mat4, buffer: &[Data], geometry: &mut SceneData) {
     let get_buffer = |x: Buffer| Some(&*buffer[x.index()].0);
+    let normal_transform = transform.inverse().transpose();
     for primitive in mesh.primitives() {
-        let material = primitive.material();
-        // let (_, material_index) = if let Some(material_index) = material.index() {
-        //     (material_vec.get(material_index).unwrap(), material_index)
-        // } else {
-        //     //默认材质
-        //     (material_vec.last().unwrap(), material_vec.len() - 1)
-        // };
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            continue;
+        }
         let reader = primitive.reader(get_buffer);
-        *index = reader
-            .read_indices()
-            .unwrap()
-            .into_u32()
-            .collect::<Vec<_>>()
-            .chunks(3)
+        let point = match reader.read_positions() {
+            Some(point) => point
+                .map(|x| transform.transform_point3(Vec3::from_array(x)))
+                .collect::<Vec<_>>(),
+            None => continue,
+        };
+        let base = geometry.point.len() as u32;
+        //无索引时顶点按顺序组成三角形
+        let index = match reader.read_indices() {
+            Some(index) => index.into_u32().collect::<Vec<_>>(),
+            None => (0..point.len() as u32).collect(),
+        };
+        let index = index
+            .chunks_exact(3)
             .map(UVec3::from_slice)
-            // .map(|x| x.extend(material_index as u32))
-            .collect();
-
-        for (s, _) in primitive.attributes() {
-            match s {
-                gltf::Semantic::Positions => {
-                    *point = reader
-                        .read_positions()
-                        .unwrap()
-                        .map(Vec3::from_array)
-                        .collect::<Vec<_>>();
-                }
-                gltf::Semantic::Normals => {
-                    *normal = reader
-                        .read_normals()
-                        .unwrap()
-                        .map(Vec3::from_array)
-                        .collect::<Vec<_>>();
+            .collect::<Vec<_>>();
+        let normal = match reader.read_normals() {
+            Some(normal) => normal
+                .map(|x| {
+                    normal_transform
+                        .transform_vector3(Vec3::from_array(x))
+                        .normalize()
+                })
+                .collect::<Vec<_>>(),
+            //缺少法线时由面法线累加得到顶点法线
+            None => {
+                let mut normal = vec![Vec3::ZERO; point.len()];
+                for i in &index {
+                    let (a, b, c) = (
+                        point[i.x as usize],
+                        point[i.y as usize],
+                        point[i.z as usize],
+                    );
+                    let n = (b - a).cross(c - a);
+                    normal[i.x as usize] += n;
+                    normal[i.y as usize] += n;
+                    normal[i.z as usize] += n;
                 }
-                gltf::Semantic::Tangents => {}
-                gltf::Semantic::Colors(_) => {}
-                gltf::Semantic::TexCoords(coords) => {
-                    *uv = reader
-                        .read_tex_coords(coords)
-                        .unwrap()
-                        .into_f32()
-                        .map(Vec2::from_array)
-                        .collect::<Vec<_>>();
-                }
-                gltf::Semantic::Joints(_) => {}
-                gltf::Semantic::Weights(_) => {}
+                normal.iter().map(|x| x.normalize_or_zero()).collect()
             }
-        }
+        };
+        let uv = match reader.read_tex_coords(0) {
+            Some(uv) => uv.into_f32().map(Vec2::from_array).collect::<Vec<_>>(),
+            None => vec![Vec2::ZERO; point.len()],
+        };
+        //无材质的图元使用默认材质
+        geometry.material_index.extend(std::iter::repeat_n(
+            primitive.material().index(),
+            index.len(),
+        ));
+        geometry
+            .index
+            .extend(index.into_iter().map(|x| x + UVec3::splat(base)));
+        geometry.point.extend(point);
+        geometry.normal.extend(normal);
+        geometry.uv.extend(uv);
     }
 }
 
-fn load_material<'a, 'b>(
-    images: Vec<gltf::image::Data>,
-    gltf: &'b gltf::Document,
-) -> Vec<Box<dyn Material + 'a>>
-where
-    'a: 'b,
-{
-    let mut mip_vec: Vec<_> = vec![];
-    let mut material_vec = vec![];
-    for image in gltf.images() {
-        mip_vec.push(MipMap::new(ImageData::new(&images[image.index()])));
+fn load_material(images: Vec<gltf::image::Data>, gltf: &gltf::Document) -> Vec<Box<dyn Material>> {
+    //基础颜色与自发光贴图按sRGB编码，其余贴图为线性数据
+    let (mut srgb, mut linear) = (vec![false; images.len()], vec![false; images.len()]);
+    for material in gltf.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        for texture in [
+            pbr.base_color_texture()
+                .filter(|x| x.tex_coord() == 0)
+                .map(|x| x.texture()),
+            material
+                .emissive_texture()
+                .filter(|x| x.tex_coord() == 0)
+                .map(|x| x.texture()),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            srgb[texture.source().index()] = true;
+        }
+        for texture in [
+            pbr.metallic_roughness_texture()
+                .filter(|x| x.tex_coord() == 0)
+                .map(|x| x.texture()),
+            material
+                .normal_texture()
+                .filter(|x| x.tex_coord() == 0)
+                .map(|x| x.texture()),
+            material
+                .occlusion_texture()
+                .filter(|x| x.tex_coord() == 0)
+                .map(|x| x.texture()),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            linear[texture.source().index()] = true;
+        }
     }
+    let mip = |used: &[bool], decode: bool| -> Vec<Option<MipMap>> {
+        images
+            .iter()
+            .zip(used)
+            .map(|(data, used)| used.then(|| MipMap::new(ImageData::new(data, decode))))
+            .collect()
+    };
+    let (srgb_vec, linear_vec) = (mip(&srgb, true), mip(&linear, false));
+    let mut material_vec = vec![];
     for material in gltf.materials() {
-        add_material(&material, &mip_vec, &mut material_vec);
+        add_material(&material, &srgb_vec, &linear_vec, &mut material_vec);
     }
-    material_vec.push(Box::new(Matte::new(
-        Arc::new(ConstantTexture::new(Vec3::splat(0.75))),
-        0.0,
-    )));
     material_vec
 }
-fn load_node(
-    // material_vec: &[Box<dyn Material>],
-    gltf: &gltf::Document,
-    buffer: Vec<gltf::buffer::Data>,
-) -> (
-    Vec<Vec3>,
-    Vec<Vec3>,
-    Vec<Vec2>,
-    Vec<Vec<UVec3>>,
-    usize,
-    Vec<Mat4>,
-    Vec<UVec3>,
-) {
-    let mut transform_vec = vec![];
-    let mut index_vec = vec![];
-    let mut point_vec = vec![];
-    let mut normal_vec = vec![];
-    let mut uv_vec = vec![];
-    let mut nodes: usize = 0;
-    for (_, item) in gltf.nodes().enumerate() {
-        let transform = match item.transform() {
-            gltf::scene::Transform::Matrix { matrix } => Mat4::from_cols_array_2d(&matrix),
-            gltf::scene::Transform::Decomposed {
-                translation,
-                rotation,
-                scale,
-            } => Mat4::from_scale_rotation_translation(
-                Vec3::from_array(scale),
-                Quat::from_array(rotation),
-                Vec3::from(translation),
-            ),
-        };
-
-        transform_vec.push(transform);
-        let mut point = vec![];
-        let mut normal = vec![];
-        let mut uv = vec![];
-        let mut index = vec![];
-        if let Some(mesh) = item.mesh() {
-            load_mesh(
-                mesh,
-                // material_vec,
-                &buffer,
-                &mut index,
-                &mut point,
-                &mut normal,
-                &mut uv,
-            );
-        };
-        index_vec.push(index);
-        normal_vec.push(normal);
-        uv_vec.push(uv);
-        point_vec.push(point);
-        nodes += 1;
-    }
-    let mut all_point = vec![];
-    let mut all_normal = vec![];
-    let mut all_uv = vec![];
-    let mut det_point = vec![UVec3::ZERO];
-    for i in 0..nodes {
-        let point = point_vec.get_mut(i).unwrap();
-        let normal = normal_vec.get_mut(i).unwrap();
-        let uv = uv_vec.get_mut(i).unwrap();
-        det_point.push(det_point[i] + UVec3::splat(point.len() as u32));
-        all_point.append(point);
-        all_normal.append(normal);
-        all_uv.append(uv);
+//网格只读取第0套纹理坐标，使用其他坐标集的纹理被忽略
+fn uv0(texture: gltf::Texture<'_>, tex_coord: u32) -> Option<gltf::Texture<'_>> {
+    if tex_coord != 0 {
+        warn!(
+            "纹理 {} 使用第 {} 套纹理坐标，暂不支持，已忽略",
+            texture.index(),
+            tex_coord
+        );
+        return None;
     }
-    (
-        all_point,
-        all_normal,
-        all_uv,
-        index_vec,
-        nodes,
-        transform_vec,
-        det_point,
-    )
+    Some(texture)
 }
 pub fn add_material(
     material: &gltf::Material,
-    mip_map: &Vec<MipMap>,
+    srgb: &[Option<MipMap>],
+    linear: &[Option<MipMap>],
     material_vec: &mut Vec<Box<dyn Material>>,
 ) {
-    if material_vec.get(material.index().unwrap()).is_some() {
-        return;
-    }
-    //透明度，不做处理
-    match material.alpha_mode() {
-        _ => (),
-    }
-    let _ = material.alpha_cutoff();
-    //双面贴图
-    let _ = material.double_sided();
+    //纹理对应的图像，按用途选择解码方式
+    let image = |texture: gltf::Texture, mip_map: &[Option<MipMap>]| -> Arc<dyn Texture> {
+        Arc::new(ImageTexture::new(
+            mip_map[texture.source().index()].clone().unwrap(),
+        ))
+    };
+    //纹理与系数相乘
+    let scale = |texture: Option<gltf::Texture>,
+                 factor: Vec3,
+                 mip_map: &[Option<MipMap>]|
+     -> Arc<dyn Texture> {
+        let constant = Arc::new(ConstantTexture::new(factor));
+        match texture {
+            Some(texture) => Arc::new(ScaleTexture::new(image(texture, mip_map), constant)),
+            None => constant,
+        }
+    };
     //自发光
-    let _emissive_texture = material.emissive_factor();
-    //自发光贴图
-    material.emissive_texture();
+    let emissive_factor = Vec3::from_array(material.emissive_factor());
+    let emissive = if emissive_factor == Vec3::ZERO {
+        None
+    } else {
+        Some(scale(
+            material
+                .emissive_texture()
+                .and_then(|x| uv0(x.texture(), x.tex_coord())),
+            emissive_factor,
+            srgb,
+        ))
+    };
     //法线贴图
-    material.normal_texture();
+    let normal = material
+        .normal_texture()
+        .and_then(|x| uv0(x.texture(), x.tex_coord()))
+        .map(|x| image(x, linear));
     //遮挡贴图
-    material.occlusion_texture();
+    let occlusion = material
+        .occlusion_texture()
+        .and_then(|x| uv0(x.texture(), x.tex_coord()))
+        .map(|x| image(x, linear));
     //pbr材质
     let pbr = material.pbr_metallic_roughness();
-    //base_color
-    let base_color: Arc<dyn Texture> = if let Some(base_color_texture) = pbr.base_color_texture() {
-        let i = base_color_texture.texture().index();
-        Arc::new(ImageTexture::new(mip_map.get(i).unwrap().to_owned()))
-    } else {
-        Arc::new(ConstantTexture::new(
-            Vec4::from_array(pbr.base_color_factor()).truncate(),
-        ))
-    };
-    //金属度
-    let metailc: Arc<dyn Texture> =
-        if let Some(metallic_roughness_texture) = pbr.metallic_roughness_texture() {
-            Arc::new(ImageTexture::new(
-                mip_map
-                    .get(metallic_roughness_texture.texture().index())
-                    .unwrap()
-                    .to_owned(),
-            ))
-        } else {
-            Arc::new(ConstantTexture::new(Vec3::splat(pbr.metallic_factor())))
-        };
-    //粗糙度
-    let roughness: Arc<dyn Texture> =
-        if let Some(roughness_texture) = pbr.metallic_roughness_texture() {
-            Arc::new(ImageTexture::new(
-                mip_map
-                    .get(roughness_texture.texture().index())
-                    .unwrap()
-                    .to_owned(),
-            ))
-        } else {
-            Arc::new(ConstantTexture::new(Vec3::splat(pbr.roughness_factor())))
-        };
-    let pbr_material = Box::new(PbrMaterial::new(
+    //基础颜色
+    let base_color = scale(
+        pbr.base_color_texture()
+            .and_then(|x| uv0(x.texture(), x.tex_coord())),
+        Vec4::from_array(pbr.base_color_factor()).truncate(),
+        srgb,
+    );
+    //金属度与粗糙度共用一张贴图，B通道为金属度，G通道为粗糙度
+    let metallic_roughness = pbr
+        .metallic_roughness_texture()
+        .and_then(|x| uv0(x.texture(), x.tex_coord()));
+    let metailc = scale(
+        metallic_roughness.clone(),
+        Vec3::splat(pbr.metallic_factor()),
+        linear,
+    );
+    let roughness = scale(
+        metallic_roughness,
+        Vec3::splat(pbr.roughness_factor()),
+        linear,
+    );
+    material_vec.push(Box::new(PbrMaterial::new(
         Some(base_color),
         Some(metailc),
         Some(roughness),
-        None,
-        None,
-        None,
-    ));
-    material_vec.insert(material.index().unwrap(), pbr_material);
+        emissive,
+        occlusion,
+        normal,
+    )));
 }
@@ -1,19 +1,28 @@
 use std::{fs::File, io::Read};
 
+use glam::Mat4;
 use serde::{Deserialize, Serialize};
 
 use crate::pbrt_core::camera::Camera;
 
-use self::tomlload::{TomlLoader, CameraToml, IntegratorToml};
+use self::{
+    gltfload::GltfLoad,
+    tomlload::{CameraToml, IntegratorToml, TomlLoader},
+};
 
 use super::{
     camera::CameraMode,
-    integrator::{direct::DirectIntegrator, path::PathIntegrator, Integrator},
+    integrator::{
+        bdpt::BdptIntegrator, direct::DirectIntegrator, path::PathIntegrator,
+        sppm::SppmIntegrator, volpath::VolPathIntegrator, Integrator,
+    },
     sampler::Sampler,
     tool::{
         build::Context,
         sence::Sence,
-        setting::Setting,
+        setting::{Adaptive, OutputFormat, Progressive, Setting},
+        pixel_filter::PixelFilter,
+        tonemap::ToneMap,
     },
 };
 
@@ -29,6 +38,19 @@ struct LoadData {
     pub name: String,
     pub camera: CameraToml,
     pub intergator: IntegratorToml,
+    #[serde(default = "OutputFormat::default_output")]
+    pub output: Vec<OutputFormat>,
+    #[serde(default)]
+    pub tone_map: ToneMap,
+    #[serde(default)]
+    pub filter: PixelFilter,
+    //场景随机种子，相同种子的渲染结果逐位一致
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub progressive: Progressive,
+    #[serde(default)]
+    pub adaptive: Adaptive,
 }
 
 pub struct Load;
@@ -38,29 +60,44 @@ impl Load {
         let mut buf = String::new();
         file.read_to_string(&mut buf)?;
         let data: LoadData = toml::from_str(&buf)?;
-        let camera = Self::load_camera(&data.camera);
-        let integrator = Self::create_intergator(&data.intergator);
-        let sence = Self::build_sence(&data.path, camera)?;
-        let setting = Self::create_setting(&data.intergator, data.name, &data.camera);
+        let sence = Self::build_sence(&data.path, &data.camera)?;
+        let integrator = Self::create_intergator(&data.intergator, data.seed, &sence.camera)?;
+        let mut setting = Self::create_setting(&data.intergator, data.name, &data.camera);
+        setting.output = data.output;
+        setting.tone_map = data.tone_map;
+        setting.filter = data.filter;
+        setting.progressive = data.progressive;
+        setting.adaptive = data.adaptive;
         Ok(Context::new(sence, integrator, setting))
     }
-    fn build_sence(path: &str, camera: Camera) -> anyhow::Result<Sence> {
+    fn build_sence(path: &str, camera: &CameraToml) -> anyhow::Result<Sence> {
         let sence = match path.split(".").last().unwrap() {
             "toml" => Self::toml_load_sence(path, camera),
             _ => unimplemented!("文件类型暂不支持"),
         };
         sence
     }
-    fn toml_load_sence(path: &str, camera: Camera) -> anyhow::Result<Sence> {
+    fn toml_load_sence(path: &str, camera: &CameraToml) -> anyhow::Result<Sence> {
         let mut file = File::open(path)?;
         let mut buf = String::new();
         file.read_to_string(&mut buf)?;
         let loader: TomlLoader = toml::from_str(&buf)?;
-        Ok(loader.load_sence(camera))
+        //glTF相机与同一文件中的网格、光源使用相同的物体变换
+        let transform = camera
+            .gltf
+            .as_ref()
+            .and_then(|path| loader.object_transform(path))
+            .unwrap_or(Mat4::IDENTITY);
+        let camera = Self::load_camera(camera, transform)?;
+        loader.load_sence(camera)
     }
-    fn load_camera(camera: &CameraToml) -> Camera {
+    fn load_camera(camera: &CameraToml, transform: Mat4) -> anyhow::Result<Camera> {
+        if let Some(ref path) = camera.gltf {
+            return GltfLoad::load_camera(path, camera.size, transform)?
+                .ok_or_else(|| anyhow::anyhow!("{} 中没有相机", path));
+        }
         let mode = camera.mode.as_str();
-        match mode {
+        let camera = match mode {
             "P" => Camera::new(
                 camera.eye,
                 camera.target,
@@ -78,29 +115,78 @@ impl Load {
                 camera.fov,
             ),
             _ => unimplemented!("不支持其他类型Camera"),
-        }
+        };
+        Ok(camera)
     }
-    fn create_intergator(integrator: &IntegratorToml) -> Integrator {
-        match *integrator {
+    fn create_intergator(
+        integrator: &IntegratorToml,
+        seed: u64,
+        camera: &Camera,
+    ) -> anyhow::Result<Integrator> {
+        //双向路径追踪的相机重要性函数只支持透视相机
+        if matches!(integrator, IntegratorToml::Bdpt { .. })
+            && matches!(camera.mode(), CameraMode::O)
+        {
+            anyhow::bail!("Bdpt积分器不支持正交相机");
+        }
+        let integrator = match *integrator {
             IntegratorToml::Direct {
                 core_num,
                 sample_num,
                 startegy,
+                sampler,
             } => {
-                let direct = Box::new(DirectIntegrator::new(0, startegy, Sampler::new(1)));
-                Integrator::Direct(direct, core_num, Sampler::new(sample_num))
+                let direct = Box::new(DirectIntegrator::new(0, startegy, Sampler::new(sampler, 1, seed)));
+                Integrator::Direct(direct, core_num, Sampler::new(sampler, sample_num, seed))
             }
             IntegratorToml::Path {
                 core_num,
                 sample_num,
                 q,
                 max_depth,
+                rr_depth,
+                sampler,
             } => Integrator::Path(
-                Box::new(PathIntegrator::new(q, max_depth)),
+                Box::new(PathIntegrator::new(q, max_depth, rr_depth)),
                 core_num,
-                Sampler::new(sample_num),
+                Sampler::new(sampler, sample_num, seed),
             ),
-        }
+            IntegratorToml::Bdpt {
+                core_num,
+                sample_num,
+                max_depth,
+                sampler,
+            } => Integrator::Bdpt(
+                Box::new(BdptIntegrator::new(max_depth)),
+                core_num,
+                Sampler::new(sampler, sample_num, seed),
+            ),
+            IntegratorToml::Sppm {
+                core_num,
+                sample_num,
+                max_depth,
+                photons,
+                radius,
+                sampler,
+            } => Integrator::Sppm(
+                Box::new(SppmIntegrator::new(radius, max_depth, photons, seed)),
+                core_num,
+                Sampler::new(sampler, sample_num, seed),
+            ),
+            IntegratorToml::VolPath {
+                core_num,
+                sample_num,
+                q,
+                max_depth,
+                rr_depth,
+                sampler,
+            } => Integrator::VolPath(
+                Box::new(VolPathIntegrator::new(q, max_depth, rr_depth)),
+                core_num,
+                Sampler::new(sampler, sample_num, seed),
+            ),
+        };
+        Ok(integrator)
     }
     pub fn create_setting(
         integrator: &IntegratorToml,
@@ -112,6 +198,7 @@ impl Load {
                 core_num,
                 sample_num: _,
                 startegy: _,
+                sampler: _,
             } => Setting::new(
                 *core_num,
                 name.to_owned(),
@@ -123,12 +210,32 @@ impl Load {
                 sample_num: _,
                 q: _,
                 max_depth: _,
+                rr_depth: _,
+                sampler: _,
             } => Setting::new(
                 *core_num,
                 name.to_owned(),
                 camera.size.as_uvec2(),
                 "path".to_ascii_lowercase(),
             ),
+            IntegratorToml::Bdpt { core_num, .. } => Setting::new(
+                *core_num,
+                name.to_owned(),
+                camera.size.as_uvec2(),
+                "bdpt".to_ascii_lowercase(),
+            ),
+            IntegratorToml::Sppm { core_num, .. } => Setting::new(
+                *core_num,
+                name.to_owned(),
+                camera.size.as_uvec2(),
+                "sppm".to_ascii_lowercase(),
+            ),
+            IntegratorToml::VolPath { core_num, .. } => Setting::new(
+                *core_num,
+                name.to_owned(),
+                camera.size.as_uvec2(),
+                "volpath".to_ascii_lowercase(),
+            ),
         }
     }
 }
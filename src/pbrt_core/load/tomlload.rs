@@ -1,16 +1,27 @@
 use std::{ops::Add, path::Path, sync::Arc};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use glam::{Mat4, UVec3, Vec3, Vec2, Vec4, Quat};
 use obj::Object;
 use serde::{Deserialize, Serialize};
 
 use crate::pbrt_core::{
     self,
+    bxdf::microfacet_distribution::DistributionType,
     camera::Camera,
-    light::{area::DiffuseAreaLight, inf::InfiniteLight, point::Point, Light},
+    medium::{
+        grid::{DensityGrid, GridDensityMedium},
+        homogeneous::HomogeneousMedium,
+        Medium, MediumInterface,
+    },
+    light::{
+        area::DiffuseAreaLight, distant::DistantLight, inf::InfiniteLight, point::Point,
+        spot::SpotLight, Light,
+    },
     material::{
-        self, matte::Matte, metal::MetalMaterial, mirror::Mirror, plastic::Plastic, Material,
+        self, disney::DisneyMaterial, glass::GlassMaterial, matte::Matte, metal::MetalMaterial, mirror::Mirror,
+        plastic::Plastic, rough_glass::RoughGlassMaterial, subsurface::SubsurfaceMaterial,
+        Material,
     },
     primitive::{
         self,
@@ -23,10 +34,11 @@ use crate::pbrt_core::{
         mipmap::{ImageData, MipMap},
         sence::Sence,
     }, integrator::direct::LightStartegy,
+    sampler::SamplerMode,
 };
 
 use super::{
-    objload::ObjLoad, gltfload::GltfLoad,
+    objload::ObjLoad, gltfload::{GltfData, GltfLoad},
 };
 static mut SHAPE: Vec<Shape> = vec![];
 #[derive(Deserialize, Debug, Serialize, Default)]
@@ -34,7 +46,11 @@ struct ObjToml {
     pub transform: TransformToml,
     pub objtype: String,
     pub path: String,
-    pub material_index: usize,
+    //没有材质的物体只作为介质边界
+    #[serde(default)]
+    pub material_index: Option<usize>,
+    #[serde(default)]
+    pub medium_interface: Option<MediumInterface>,
 }
 
 #[derive(Deserialize, Debug, Serialize, Default)]
@@ -46,15 +62,81 @@ pub struct TomlLoader {
     light: Vec<LightToml>,
     #[serde(default)]
     shapes: Vec<ShapeToml>,
+    #[serde(default)]
+    medium: Vec<MediumToml>,
+    #[serde(default)]
+    camera_medium: Option<usize>,
 }
 impl TomlLoader {
-    pub fn load_sence(self, camera: Camera) -> Sence {
-        let textures = Self::load_texture(self.texture).unwrap().leak();
-        let materials = Self::load_material(self.material, textures).unwrap().leak();
-        let primitive = Self::load_object(self.object, materials).unwrap();
+    pub fn load_sence(self, mut camera: Camera) -> Result<Sence> {
+        self.check_medium()?;
+        let textures = Self::load_texture(self.texture)?.leak();
+        let materials = Self::load_material(self.material, textures)?.leak();
+        let mut light = self.light;
+        let primitive = Self::load_object(self.object, materials, &mut light)?;
         Self::load_shape(self.shapes);
-        let light = Self::load_light(self.light, unsafe { &SHAPE }, textures);
-        Sence::new(primitive, camera, light)
+        let light = Self::load_light(light, unsafe { &SHAPE }, textures);
+        camera.medium = self.camera_medium;
+        let mut sence = Sence::new(primitive, camera, light);
+        sence.media = Self::load_medium(self.medium)?;
+        Ok(sence)
+    }
+    //相机与物体引用的介质下标必须存在
+    fn check_medium(&self) -> Result<()> {
+        let len = self.medium.len();
+        if let Some(index) = self.camera_medium.filter(|&index| index >= len) {
+            bail!("camera_medium {} 超出介质数量 {}", index, len);
+        }
+        for obj in &self.object {
+            let Some(interface) = obj.medium_interface else {
+                continue;
+            };
+            for index in [interface.inside, interface.outside].into_iter().flatten() {
+                if index >= len {
+                    bail!("{} 的介质下标 {} 超出介质数量 {}", obj.path, index, len);
+                }
+            }
+        }
+        Ok(())
+    }
+    //以path载入的glTF物体的变换
+    pub fn object_transform(&self, path: &str) -> Option<Mat4> {
+        self.object
+            .iter()
+            .find(|x| x.objtype == "gltf" && Path::new(&x.path) == Path::new(path))
+            .map(|x| x.transform.get_mat())
+    }
+    fn load_medium(media: Vec<MediumToml>) -> Result<Vec<Medium>> {
+        let mut vec = vec![];
+        for item in media {
+            let medium = match item {
+                MediumToml::Homogeneous { sigma_a, sigma_s, g } => {
+                    Medium::Homogeneous(HomogeneousMedium::new(sigma_a, sigma_s, g))
+                }
+                MediumToml::Grid {
+                    sigma_a,
+                    sigma_s,
+                    g,
+                    path,
+                    resolution,
+                    transform,
+                    le,
+                    emission_path,
+                } => {
+                    let density = DensityGrid::load(&path, resolution)?;
+                    let mut grid =
+                        GridDensityMedium::new(sigma_a, sigma_s, g, density, transform.get_mat());
+                    let emission = match emission_path {
+                        Some(path) => Some(DensityGrid::load(&path, resolution)?),
+                        None => None,
+                    };
+                    grid.set_emission(le, emission);
+                    Medium::Grid(Box::new(grid))
+                }
+            };
+            vec.push(medium);
+        }
+        Ok(vec)
     }
     fn load_texture(textures: Vec<TextureToml>) -> Result<Vec<Arc<dyn Texture>>> {
         let mut vec = vec![];
@@ -106,6 +188,68 @@ impl TomlLoader {
                         false,
                     ))
                 }
+                MaterialToml::Glass { kr, kt, eta } => {
+                    let kr = texture.get(kr).unwrap();
+                    let kt = texture.get(kt).unwrap();
+                    let eta = texture.get(eta).unwrap();
+                    Box::new(GlassMaterial::new(kr.clone(), kt.clone(), eta.clone()))
+                }
+                MaterialToml::RoughGlass {
+                    kr,
+                    kt,
+                    eta,
+                    roughness,
+                    distribution,
+                } => {
+                    let kr = texture.get(kr).unwrap();
+                    let kt = texture.get(kt).unwrap();
+                    let eta = texture.get(eta).unwrap();
+                    let roughness = texture.get(roughness).unwrap();
+                    Box::new(RoughGlassMaterial::new(
+                        kr.clone(),
+                        kt.clone(),
+                        eta.clone(),
+                        roughness.clone(),
+                        distribution,
+                    ))
+                }
+                MaterialToml::Disney {
+                    color,
+                    metallic,
+                    eta,
+                    roughness,
+                    specular,
+                    specular_tint,
+                    anisotropic,
+                    sheen,
+                    sheen_tint,
+                    clearcoat,
+                    clearcoat_gloss,
+                    spec_trans,
+                    flatness,
+                    thin,
+                } => {
+                    let get = |index: Option<usize>| index.map(|i| texture.get(i).unwrap().clone());
+                    let mut disney = DisneyMaterial::new(texture.get(color).unwrap().clone(), thin);
+                    disney.metallic = get(metallic);
+                    disney.eta = get(eta);
+                    disney.roughness = get(roughness);
+                    disney.specular = get(specular);
+                    disney.specular_tint = get(specular_tint);
+                    disney.anisotropic = get(anisotropic);
+                    disney.sheen = get(sheen);
+                    disney.sheen_tint = get(sheen_tint);
+                    disney.clearcoat = get(clearcoat);
+                    disney.clearcoat_gloss = get(clearcoat_gloss);
+                    disney.spec_trans = get(spec_trans);
+                    disney.flatness = get(flatness);
+                    Box::new(disney)
+                }
+                MaterialToml::Subsurface { albedo, mfp, eta } => {
+                    let albedo = texture.get(albedo).unwrap();
+                    let mfp = texture.get(mfp).unwrap();
+                    Box::new(SubsurfaceMaterial::new(albedo.clone(), mfp.clone(), eta))
+                }
                 _ => todo!(),
             };
             vec.push(a)
@@ -115,6 +259,7 @@ impl TomlLoader {
     fn load_object(
         objects: Vec<ObjToml>,
         materials: &'static mut [Box<dyn material::Material>],
+        lights: &mut Vec<LightToml>,
     ) -> Result<Vec<Box<dyn Primitive>>> {
         let mut vec = vec![];
         let mut all_mesh = Default::default();
@@ -125,28 +270,47 @@ impl TomlLoader {
             vec.push(sub_vec);
         }
         let mesh = Arc::new(all_mesh);
-        for index in 0..vec.len() {
-            let obj = objects.get(index).unwrap();
+        for (obj, (sub_primitive, gltf_material)) in objects.iter().zip(vec) {
             let mat4 = obj.transform.get_mat();
-            let material = materials.get(obj.material_index);
-            let sub_primitive = vec.get(index).unwrap();
+            for mut light in gltf_material.lights {
+                light.transform(mat4);
+                lights.push(light);
+            }
+            let material = obj.material_index.and_then(|index| materials.get(index));
+            //glTF自带材质优先
+            let gltf_materials = &*gltf_material.materials.leak();
             let len = sub_primitive[0].len();
             for i in 0..len {
+                let material = match gltf_material.triangle.get(i) {
+                    Some(Some(index)) => gltf_materials.get(*index).or(material),
+                    _ => material,
+                };
                 let pos = sub_primitive[0][i];
                 let uv = sub_primitive[1][i];
                 let normal = sub_primitive[2][i];
                 let t: Box<dyn Primitive> =
-                    Box::new(Triangle::new(pos, normal, uv, mesh.clone(), mat4, material));
+                    Box::new(Triangle::new(
+                    pos,
+                    normal,
+                    uv,
+                    mesh.clone(),
+                    mat4,
+                    material,
+                    obj.medium_interface,
+                ));
                 primitives.push(t);
             }
         }
         Ok(primitives)
     }
-    fn load_sigle_object(object: &ObjToml, all_mesh: &mut Mesh) -> Result<Vec<Vec<UVec3>>> {
+    fn load_sigle_object(
+        object: &ObjToml,
+        all_mesh: &mut Mesh,
+    ) -> Result<(Vec<Vec<UVec3>>, GltfData)> {
         let object_path = &object.path;
         let objtype = &object.objtype;
-        let (mut mesh, vec) = match objtype.as_str() {
-            "obj" => ObjLoad::load(&object_path),
+        let (mut mesh, vec, material) = match objtype.as_str() {
+            "obj" => ObjLoad::load(&object_path).map(|(mesh, vec)| (mesh, vec, Default::default())),
             "gltf" => GltfLoad::load(&object_path),
             _=>unimplemented!("obj type not support")
         }?;
@@ -160,7 +324,7 @@ impl TomlLoader {
             ans_index.push(item);
         }
         all_mesh.merge(&mut mesh);
-        Ok(ans_index)
+        Ok((ans_index, material))
     }
 
     fn load_shape(shapes: Vec<ShapeToml>) {
@@ -198,6 +362,22 @@ impl TomlLoader {
                     point,
                     lemit,
                 } => Light::PointLight(Box::new(Point::new(*lemit, *point, index))),
+                LightToml::Spot {
+                    trans,
+                    point,
+                    lemit,
+                    end_angle,
+                    start_angle,
+                } => Light::Spot(Box::new(SpotLight::new(
+                    *lemit,
+                    Mat4::from_translation(*point) * trans.get_mat(),
+                    *end_angle,
+                    *start_angle,
+                    index,
+                ))),
+                LightToml::Distant { lemit, dir } => {
+                    Light::Distant(Box::new(DistantLight::new(*lemit, *dir, index)))
+                }
                 LightToml::Area { lemit, shape_index } => Light::AreaLight(Box::new(
                     DiffuseAreaLight::new(*lemit, shape.get(*shape_index).take().unwrap(), index),
                 )),
@@ -250,14 +430,24 @@ pub struct MyLoad {
 
 #[derive(Deserialize, Debug, Serialize, Default)]
 pub struct CameraToml {
+    #[serde(default)]
     pub mode: String,
     pub size: Vec2,
+    #[serde(default)]
     pub far: f32,
+    #[serde(default)]
     pub near: f32,
+    #[serde(default)]
     pub eye: Vec3,
+    #[serde(default)]
     pub target: Vec3,
+    #[serde(default)]
     pub up: Vec3,
+    #[serde(default)]
     pub fov: f32,
+    //使用glTF文件中的相机，此时只需指定size
+    #[serde(default)]
+    pub gltf: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Serialize, Default)]
@@ -273,6 +463,15 @@ impl TransformToml {
         let quat = Quat::from_axis_angle(self.r.truncate(), angle);
         Mat4::from_scale_rotation_translation(self.s, quat, self.t)
     }
+    //只含旋转，将+z轴转到dir方向
+    pub fn looking_at(dir: Vec3) -> Self {
+        let (axis, angle) = Quat::from_rotation_arc(Vec3::Z, dir.normalize()).to_axis_angle();
+        Self {
+            r: axis.extend(angle.to_degrees()),
+            s: Vec3::ONE,
+            t: Vec3::ZERO,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -309,6 +508,42 @@ pub enum MaterialToml {
         k: usize,
         roughness: usize,
     },
+    Glass {
+        kr: usize,
+        kt: usize,
+        eta: usize,
+    },
+    RoughGlass {
+        kr: usize,
+        kt: usize,
+        eta: usize,
+        roughness: usize,
+        #[serde(default)]
+        distribution: DistributionType,
+    },
+    Disney {
+        color: usize,
+        metallic: Option<usize>,
+        eta: Option<usize>,
+        roughness: Option<usize>,
+        specular: Option<usize>,
+        specular_tint: Option<usize>,
+        anisotropic: Option<usize>,
+        sheen: Option<usize>,
+        sheen_tint: Option<usize>,
+        clearcoat: Option<usize>,
+        clearcoat_gloss: Option<usize>,
+        spec_trans: Option<usize>,
+        flatness: Option<usize>,
+        #[serde(default)]
+        thin: bool,
+    },
+    //albedo为表面反照率纹理，mfp为平均自由程纹理
+    Subsurface {
+        albedo: usize,
+        mfp: usize,
+        eta: f32,
+    },
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -319,6 +554,33 @@ pub enum TextureToml {
     // Value{value:f32}
 }
 
+//sigma_a为吸收系数，sigma_s为散射系数，g为相函数的不对称参数
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(tag = "mode")]
+pub enum MediumToml {
+    Homogeneous {
+        sigma_a: Vec3,
+        sigma_s: Vec3,
+        #[serde(default)]
+        g: f32,
+    },
+    //密度网格为无文件头的小端f32数据，占据transform变换后的单位立方体
+    Grid {
+        sigma_a: Vec3,
+        sigma_s: Vec3,
+        #[serde(default)]
+        g: f32,
+        path: String,
+        resolution: UVec3,
+        transform: TransformToml,
+        //自发光，emission_path给出时按其网格数值缩放
+        #[serde(default)]
+        le: Vec3,
+        #[serde(default)]
+        emission_path: Option<String>,
+    },
+}
+
 #[derive(Deserialize, Debug, Serialize)]
 #[serde(tag = "mode")]
 pub enum LightToml {
@@ -337,11 +599,10 @@ pub enum LightToml {
         lemit: Vec3,
         texture: TextureToml,
     },
+    //dir为光线传播方向，场景范围在构建场景时计算
     Distant {
         lemit: Vec3,
         dir: Vec3,
-        world_center: Vec3,
-        world_radius: Vec3,
     },
     Area {
         lemit: Vec3,
@@ -354,19 +615,70 @@ pub enum LightToml {
     },
 }
 
+impl LightToml {
+    //glTF光源随物体变换到世界空间
+    pub fn transform(&mut self, mat: Mat4) {
+        match self {
+            LightToml::Point { point, .. } => *point = mat.transform_point3(*point),
+            LightToml::Spot { trans, point, .. } => {
+                let dir = mat.transform_vector3(trans.get_mat().transform_vector3(Vec3::Z));
+                *point = mat.transform_point3(*point);
+                *trans = TransformToml::looking_at(dir);
+            }
+            LightToml::Distant { dir, .. } => *dir = mat.transform_vector3(*dir).normalize(),
+            _ => (),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Serialize, Clone, Copy)]
 #[serde(tag = "mode")]
 pub enum IntegratorToml {
     Path {
         core_num: usize,
         sample_num: usize,
+        //俄罗斯轮盘赌存活概率的上限，实际概率取路径吞吐量的最大分量与q的较小值
         q: f32,
         max_depth: usize,
+        //从第rr_depth次反弹开始轮盘赌，需小于max_depth才会生效
+        #[serde(default = "IntegratorToml::default_rr_depth")]
+        rr_depth: usize,
+        #[serde(default)]
+        sampler: SamplerMode,
     },
     Direct {
         core_num: usize,
         sample_num: usize,
         startegy: LightStartegy,
+        #[serde(default)]
+        sampler: SamplerMode,
+    },
+    Bdpt {
+        core_num: usize,
+        sample_num: usize,
+        max_depth: usize,
+        #[serde(default)]
+        sampler: SamplerMode,
+    },
+    //sample_num为迭代轮数，photons为每轮发射的光子数
+    Sppm {
+        core_num: usize,
+        sample_num: usize,
+        max_depth: usize,
+        photons: usize,
+        radius: f32,
+        #[serde(default)]
+        sampler: SamplerMode,
+    },
+    VolPath {
+        core_num: usize,
+        sample_num: usize,
+        q: f32,
+        max_depth: usize,
+        #[serde(default = "IntegratorToml::default_rr_depth")]
+        rr_depth: usize,
+        #[serde(default)]
+        sampler: SamplerMode,
     },
 }
 
@@ -377,7 +689,14 @@ impl Default for IntegratorToml {
             sample_num: 1,
             q: 0.9,
             max_depth: 5,
+            rr_depth: Self::default_rr_depth(),
+            sampler: SamplerMode::default(),
         }
     }
 }
+impl IntegratorToml {
+    fn default_rr_depth() -> usize {
+        3
+    }
+}
 
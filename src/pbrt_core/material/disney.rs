@@ -1,78 +1,213 @@
-// use std::sync::Arc;
+use std::sync::Arc;
 
-// use glam::{Vec3, Vec4};
+use glam::Vec3;
 
-// use crate::pbrt_core::{
-//     bxdf::{reflection::LambertianReflection, BxDF},
-//     texture::Texture,
-// };
+use crate::pbrt_core::{
+    bxdf::{
+        disney::{DisneyClearcoat, DisneyDiffuse, DisneyFakeSS, DisneyRetro, DisneySheen},
+        frensnel::{DisneyFrenel, Fresnel},
+        microfacet::MicrofacetTransmission,
+        microfacet_distribution::DisneyMicrofacetDistribution,
+        reflection::MicrofacetReflection,
+        BxDF, TransportMode,
+    },
+    texture::Texture,
+    tool::{func::lerp, InteractionCommon, SurfaceInteraction},
+};
 
-// use super::{Material, BSDF};
+use super::{Material, BSDF};
 
-// #[derive(Debug)]
-// pub struct Disney {
-//     color: Option<Box<dyn Texture<Vec3>>>,
-//     metaillic: Option<Arc<dyn Texture<f32>>>,
-//     eta: Option<Arc<dyn Texture<f32>>>,
-//     roughness: Option<Arc<dyn Texture<f32>>>,
-//     specular: Option<Arc<dyn Texture<f32>>>,
-//     anisotropic_tint: Option<Arc<dyn Texture<f32>>>,
-//     sheen: Option<Arc<dyn Texture<f32>>>,
-//     clear_coat: Option<Arc<dyn Texture<f32>>>,
-//     clear_coat_gloss: Option<Arc<dyn Texture<f32>>>,
-//     spec_trans: Option<Arc<dyn Texture<f32>>>,
-//     scatter_distance: Option<Arc<dyn Texture<f32>>>,
-//     thin: bool,
-//     flat_ness: Option<Arc<dyn Texture<f32>>>,
-//     diff_trans: Option<Arc<dyn Texture<f32>>>,
-//     bump_map: Option<Arc<dyn Texture<f32>>>,
-// }
-// impl Disney {
-//     pub fn new(color: Option<Box<dyn Texture<Vec3>>>) -> Self {
-//         Self {
-//             color,
-//             metaillic: None,
-//             eta: None,
-//             roughness: None,
-//             specular: None,
-//             anisotropic_tint: None,
-//             sheen: None,
-//             clear_coat: None,
-//             clear_coat_gloss: None,
-//             spec_trans: None,
-//             scatter_distance: None,
-//             thin: false,
-//             flat_ness: None,
-//             diff_trans: None,
-//             bump_map: None,
-//         }
-//     }
-// }
-// impl Material for Disney {
-//     fn compute_scattering_functions(
-//         &self,
-//         suface: &mut crate::pbrt_core::tool::SurfaceInteraction,
-//         mode: crate::pbrt_core::bxdf::TransportMode,
-//     ) {
-//         let r = self
-//             .color
-//             .as_ref()
-//             .unwrap()
-//             .evaluate(&suface.common)
-//             .clamp(Vec3::ZERO, Vec3::splat(f32::INFINITY));
-//         suface.bsdf = Some(BSDF::new(&suface, 1.0));
-        
-//         if let Some(bsdf) = &mut suface.bsdf {
-//             if r != Vec3::ZERO {
-//                 bsdf.bxdfs
-//                     .push(BxDF::LambertianReflection(LambertianReflection::new(r)))
-//             }
-//         }
-//     }
-//     fn bump(
-//         &self,
-//         suface: &crate::pbrt_core::tool::SurfaceInteraction,
-//         texture: &dyn Texture<f32>,
-//     ) {
-//     }
-// }
+//迪士尼原则化材质，所有参数均为可选纹理，缺省时使用默认值
+#[derive(Debug)]
+pub struct DisneyMaterial<'a> {
+    pub color: Arc<dyn Texture + 'a>,
+    pub metallic: Option<Arc<dyn Texture + 'a>>,
+    pub eta: Option<Arc<dyn Texture + 'a>>,
+    pub roughness: Option<Arc<dyn Texture + 'a>>,
+    pub specular: Option<Arc<dyn Texture + 'a>>,
+    pub specular_tint: Option<Arc<dyn Texture + 'a>>,
+    pub anisotropic: Option<Arc<dyn Texture + 'a>>,
+    pub sheen: Option<Arc<dyn Texture + 'a>>,
+    pub sheen_tint: Option<Arc<dyn Texture + 'a>>,
+    pub clearcoat: Option<Arc<dyn Texture + 'a>>,
+    pub clearcoat_gloss: Option<Arc<dyn Texture + 'a>>,
+    pub spec_trans: Option<Arc<dyn Texture + 'a>>,
+    pub flatness: Option<Arc<dyn Texture + 'a>>,
+    //薄表面，无内部体积
+    pub thin: bool,
+}
+impl<'a> DisneyMaterial<'a> {
+    pub fn new(color: Arc<dyn Texture + 'a>, thin: bool) -> Self {
+        Self {
+            color,
+            metallic: None,
+            eta: None,
+            roughness: None,
+            specular: None,
+            specular_tint: None,
+            anisotropic: None,
+            sheen: None,
+            sheen_tint: None,
+            clearcoat: None,
+            clearcoat_gloss: None,
+            spec_trans: None,
+            flatness: None,
+            thin,
+        }
+    }
+}
+#[inline]
+fn evaluate_or(texture: &Option<Arc<dyn Texture + '_>>, common: &InteractionCommon, default: f32) -> f32 {
+    match texture {
+        Some(texture) => texture.evaluate(common).x,
+        None => default,
+    }
+}
+impl<'a> Material for DisneyMaterial<'a> {
+    fn compute_scattering_functions(&self, suface: &mut SurfaceInteraction, mode: TransportMode) {
+        let common = &suface.common;
+        let specular = evaluate_or(&self.specular, common, 0.5);
+        let params = DisneyParams {
+            color: self
+                .color
+                .evaluate(common)
+                .clamp(Vec3::ZERO, Vec3::splat(f32::INFINITY)),
+            metallic: evaluate_or(&self.metallic, common, 0.0),
+            eta: evaluate_or(&self.eta, common, DisneyParams::specular_to_eta(specular)),
+            roughness: evaluate_or(&self.roughness, common, 0.5),
+            specular,
+            specular_tint: evaluate_or(&self.specular_tint, common, 0.0),
+            anisotropic: evaluate_or(&self.anisotropic, common, 0.0),
+            sheen: evaluate_or(&self.sheen, common, 0.0),
+            sheen_tint: evaluate_or(&self.sheen_tint, common, 0.5),
+            clearcoat: evaluate_or(&self.clearcoat, common, 0.0),
+            clearcoat_gloss: evaluate_or(&self.clearcoat_gloss, common, 1.0),
+            spec_trans: evaluate_or(&self.spec_trans, common, 0.0),
+            flatness: evaluate_or(&self.flatness, common, 0.0),
+            thin: self.thin,
+        };
+        let mut bsdf = BSDF::new(suface, params.eta);
+        params.push_bxdfs(&mut bsdf, mode);
+        suface.bsdf = Some(bsdf);
+    }
+}
+
+//某一交点处求值后的迪士尼参数
+pub struct DisneyParams {
+    pub color: Vec3,
+    pub metallic: f32,
+    pub eta: f32,
+    pub roughness: f32,
+    pub specular: f32,
+    pub specular_tint: f32,
+    pub anisotropic: f32,
+    pub sheen: f32,
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_gloss: f32,
+    pub spec_trans: f32,
+    pub flatness: f32,
+    pub thin: bool,
+}
+impl Default for DisneyParams {
+    fn default() -> Self {
+        Self {
+            color: Vec3::splat(0.75),
+            metallic: 0.0,
+            eta: 1.5,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            anisotropic: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            spec_trans: 0.0,
+            flatness: 0.0,
+            thin: false,
+        }
+    }
+}
+impl DisneyParams {
+    //由高光强度反推折射率，specular=0.5 对应 F0=0.04，即折射率1.5
+    pub fn specular_to_eta(specular: f32) -> f32 {
+        let f0 = (0.08 * specular).clamp(0.0, 0.99).sqrt();
+        (1.0 + f0) / (1.0 - f0)
+    }
+    //按照各分量权重构建BxDF
+    pub fn push_bxdfs(&self, bsdf: &mut BSDF, mode: TransportMode) {
+        let c = self.color;
+        let metallic = self.metallic.clamp(0.0, 1.0);
+        let strans = self.spec_trans.clamp(0.0, 1.0);
+        let rough = self.roughness.clamp(0.0, 1.0);
+        let diffuse_weight = (1.0 - metallic) * (1.0 - strans);
+        //色调：颜色除以亮度
+        let lum = c.dot(Vec3::new(0.212_671, 0.715_160, 0.072_169));
+        let c_tint = if lum > 0.0 { c / lum } else { Vec3::ONE };
+        let c_sheen = if self.sheen > 0.0 {
+            Vec3::ONE.lerp(c_tint, self.sheen_tint)
+        } else {
+            Vec3::ZERO
+        };
+        if diffuse_weight > 0.0 {
+            if self.thin {
+                let flat = self.flatness.clamp(0.0, 1.0);
+                bsdf.bxdfs.push(BxDF::DisneyDiffuse(DisneyDiffuse::new(
+                    diffuse_weight * (1.0 - flat) * c,
+                )));
+                bsdf.bxdfs.push(BxDF::DisneyFakeSS(DisneyFakeSS::new(
+                    diffuse_weight * flat * c,
+                    rough,
+                )));
+            } else {
+                bsdf.bxdfs
+                    .push(BxDF::DisneyDiffuse(DisneyDiffuse::new(diffuse_weight * c)));
+            }
+            bsdf.bxdfs
+                .push(BxDF::DisneyRetro(DisneyRetro::new(diffuse_weight * c, rough)));
+            if self.sheen > 0.0 {
+                bsdf.bxdfs.push(BxDF::DisneySheen(DisneySheen::new(
+                    diffuse_weight * self.sheen * c_sheen,
+                )));
+            }
+        }
+        //各向异性粗糙度
+        let aspect = (1.0 - self.anisotropic.clamp(0.0, 1.0) * 0.9).sqrt();
+        let ax = (rough * rough / aspect).max(0.001);
+        let ay = (rough * rough * aspect).max(0.001);
+        let spec_r0 = 0.08 * self.specular * Vec3::ONE.lerp(c_tint, self.specular_tint);
+        let c_spec0 = spec_r0.lerp(c, metallic);
+        let fresnel = Fresnel::Disney(DisneyFrenel::new(c_spec0, metallic, self.eta));
+        bsdf.bxdfs.push(BxDF::MicrofacetReflection(MicrofacetReflection::new(
+            Vec3::ONE,
+            Box::new(DisneyMicrofacetDistribution::new(ax, ay)),
+            fresnel,
+        )));
+        if self.clearcoat > 0.0 {
+            bsdf.bxdfs.push(BxDF::DisneyClearcoat(DisneyClearcoat::new(
+                self.clearcoat,
+                lerp(self.clearcoat_gloss, 0.1, 0.001),
+            )));
+        }
+        if strans > 0.0 {
+            let t = strans * Vec3::new(c.x.sqrt(), c.y.sqrt(), c.z.sqrt());
+            let distribution = if self.thin {
+                //薄表面的透射粗糙度需要按折射率缩放
+                let rscaled = (0.65 * self.eta - 0.35) * rough;
+                let ax = (rscaled * rscaled / aspect).max(0.001);
+                let ay = (rscaled * rscaled * aspect).max(0.001);
+                DisneyMicrofacetDistribution::new(ax, ay)
+            } else {
+                DisneyMicrofacetDistribution::new(ax, ay)
+            };
+            bsdf.bxdfs.push(BxDF::MicrofacetTransmission(MicrofacetTransmission::new(
+                t,
+                Box::new(distribution),
+                1.0,
+                self.eta,
+                mode,
+            )));
+        }
+    }
+}
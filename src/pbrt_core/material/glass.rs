@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use glam::Vec3;
+
+use crate::pbrt_core::{
+    bxdf::{
+        frensnel::{DielectricFresnel, Fresnel, FrensnelSpecular},
+        specular::{SpecularReflection, SpecularTransmission},
+        BxDF,
+    },
+    material::BSDF,
+    texture::Texture,
+};
+
+use super::Material;
+//玻璃，光滑电介质
+#[derive(Debug)]
+pub struct GlassMaterial<'a> {
+    //反射颜色
+    kr: Arc<dyn Texture + 'a>,
+    //透射颜色
+    kt: Arc<dyn Texture + 'a>,
+    //折射率
+    eta: Arc<dyn Texture + 'a>,
+}
+impl<'a> GlassMaterial<'a> {
+    pub fn new(
+        kr: Arc<dyn Texture + 'a>,
+        kt: Arc<dyn Texture + 'a>,
+        eta: Arc<dyn Texture + 'a>,
+    ) -> Self {
+        Self { kr, kt, eta }
+    }
+}
+impl<'a> Material for GlassMaterial<'a> {
+    fn compute_scattering_functions(
+        &self,
+        surface: &mut crate::pbrt_core::tool::SurfaceInteraction,
+        mode: crate::pbrt_core::bxdf::TransportMode,
+    ) {
+        let eta = self.eta.evaluate(&surface.common).x;
+        let r = self
+            .kr
+            .evaluate(&surface.common)
+            .clamp(Vec3::ZERO, Vec3::splat(f32::INFINITY));
+        let t = self
+            .kt
+            .evaluate(&surface.common)
+            .clamp(Vec3::ZERO, Vec3::splat(f32::INFINITY));
+        let mut bsdf = BSDF::new(surface, eta);
+        if r != Vec3::ZERO && t != Vec3::ZERO {
+            //同时存在反射与透射时，按菲涅尔项选择
+            bsdf.bxdfs.push(BxDF::FrensnelSpecular(FrensnelSpecular::new(
+                r, t, 1.0, eta, mode, None,
+            )));
+        } else if r != Vec3::ZERO {
+            let fresnel = Fresnel::Dielectric(DielectricFresnel::new(1.0, eta));
+            bsdf.bxdfs
+                .push(BxDF::SpecularReflection(SpecularReflection::new(r, fresnel)));
+        } else if t != Vec3::ZERO {
+            bsdf.bxdfs.push(BxDF::SpecularTransmission(SpecularTransmission::new(
+                t, 1.0, eta, mode,
+            )));
+        }
+        surface.bsdf = Some(bsdf);
+    }
+}
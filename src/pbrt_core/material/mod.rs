@@ -5,18 +5,25 @@ use glam::{Vec2, Vec3};
 
 use super::{
     bxdf::{BxDF, TransportMode},
-    tool::SurfaceInteraction,
+    tool::{InteractionCommon, SurfaceInteraction},
 };
 
 pub mod disney;
+pub mod glass;
 pub mod matte;
 pub mod metal;
 pub mod mirror;
 pub mod pbr;
 pub mod plastic;
+pub mod rough_glass;
+pub mod subsurface;
 
 pub trait Material: Debug {
     fn compute_scattering_functions(&self, suface: &mut SurfaceInteraction, mode: TransportMode);
+    //材质自发光
+    fn emission(&self, _common: &InteractionCommon) -> Vec3 {
+        Vec3::ZERO
+    }
 }
 
 // BSDF使用局部坐标系。
@@ -52,18 +59,22 @@ impl BSDF {
         if bxdfs.is_empty() {
             return Vec3::ZERO;
         }
-        let num = (u.x * bxdfs.len() as f32).clamp(0.0, bxdfs.len() as f32) as usize;
+        let num = ((u.x * bxdfs.len() as f32) as usize).min(bxdfs.len() - 1);
+        //选择分量后重新映射随机数，保证分量内部采样仍是均匀分布
+        let u = Vec2::new(
+            (u.x * bxdfs.len() as f32 - num as f32).min(1.0 - f32::EPSILON),
+            u.y,
+        );
 
         let bxdf = bxdfs[num];
         let mut wi = Vec3::ZERO;
+        let w_out_world = *w_out;
         let w_out = self.world_to_local(*w_out);
         if w_out.z == 0.0 {
             return Vec3::ZERO;
         }
         *pdf = 0.0;
-        if *sampled_type !=0{
-            *sampled_type = bxdf.get_type();
-        }
+        *sampled_type = bxdf.get_type();
         let mut f = bxdf.sample_f(&w_out, &mut wi, u, pdf);
         *w_in = self.local_to_world(wi);
 
@@ -75,20 +86,17 @@ impl BSDF {
         if bxdf.get_type() & BxDFType::Specular as u32 == 0 {
             for (index, item) in bxdfs.iter().enumerate() {
                 if index != num && item.match_type(flag) {
-                    *pdf += item.pdf(&w_out, w_in)
+                    *pdf += item.pdf(&w_out, &wi)
                 }
             }
         };
         *pdf /= bxdfs.len() as f32;
         if bxdf.get_type() & BxDFType::Specular as u32 == 0 {
             f = Vec3::ZERO;
-            let _reflect = w_in.dot(self.ng) * w_out.dot(self.ng) > 0.0;
+            let reflect = w_in.dot(self.ng) * w_out_world.dot(self.ng) > 0.0;
             for item in bxdfs.iter() {
-                if item.match_type(flag)
-                //    && ((reflect && item.match_type(BxDFType::Reflection.into()))
-                //        || (!reflect && item.match_type(BxDFType::Transmission.into())))
-                {
-                    f += item.f(&w_out, w_in)
+                if item.match_type(flag) && Self::side_match(item, reflect) {
+                    f += item.f(&w_out, &wi)
                 }
             }
         }
@@ -132,18 +140,33 @@ impl BSDF {
     pub fn f(&self, w_out: &Vec3, w_in: &Vec3, flag: u32) -> Vec3 {
         let wi: Vec3 = self.world_to_local(*w_in);
         let wo = self.world_to_local(*w_out);
-        let _reflect = w_in.dot(self.ng) * w_out.dot(self.ng) > 0.0;
+        //按几何法线判断反射或透射，只累加对应一侧的分量
+        let reflect = w_in.dot(self.ng) * w_out.dot(self.ng) > 0.0;
         let mut f = Vec3::ZERO;
         for bxdf in &self.bxdfs {
-            if bxdf.match_type(flag)
-          //      && ((reflect && bxdf.match_type(BxDFType::Reflection as u32))
-          //          || (!reflect && bxdf.match_type(BxDFType::Transmission as u32)))
-            {
+            if bxdf.match_type(flag) && Self::side_match(bxdf, reflect) {
                 f += bxdf.f(&wo, &wi);
             }
         }
         f
     }
+    fn side_match(bxdf: &BxDF, reflect: bool) -> bool {
+        let side = if reflect {
+            BxDFType::Reflection
+        } else {
+            BxDFType::Transmission
+        };
+        bxdf.get_type() & side as u32 != 0
+    }
+    pub fn add(&mut self, bxdf: BxDF) {
+        self.bxdfs.push(bxdf);
+    }
+    //只含镜面分量时无法与其他顶点直接连接
+    pub fn is_specular(&self) -> bool {
+        self.bxdfs
+            .iter()
+            .all(|item| item.get_type() & BxDFType::Specular as u32 != 0)
+    }
     pub fn num_components(&self, flag: u32) -> u32 {
         let mut num = 0;
         for item in &self.bxdfs {
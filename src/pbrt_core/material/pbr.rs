@@ -3,16 +3,11 @@ use std::sync::Arc;
 use glam::Vec3;
 
 use crate::pbrt_core::{
-    bxdf::{
-        frensnel::{DisneyFrenel, Fresnel},
-        microfacet_distribution::{roughness_to_alpha, TrowbridgeReitzDistribution},
-        pbr::{PbrDiff, PbrReflection},
-        BxDF,
-    },
     texture::Texture,
+    tool::{InteractionCommon, SurfaceInteraction},
 };
 
-use super::{Material, BSDF};
+use super::{disney::DisneyParams, Material, BSDF};
 #[derive(Debug)]
 pub struct PbrMaterial {
     //基本颜色
@@ -22,11 +17,11 @@ pub struct PbrMaterial {
     //粗糙度
     roughness: Option<Arc<dyn Texture>>,
     //亮度
-    _emissive: Option<Arc<dyn Texture>>,
+    emissive: Option<Arc<dyn Texture>>,
     //遮挡贴图
-    _occlusion: Option<Arc<dyn Texture>>,
+    occlusion: Option<Arc<dyn Texture>>,
     //法线贴图
-    _normal: Option<Arc<dyn Texture>>,
+    normal: Option<Arc<dyn Texture>>,
 }
 impl PbrMaterial {
     pub fn new(
@@ -46,48 +41,74 @@ impl PbrMaterial {
             base_color,
             metailc,
             roughness,
-            _emissive:emissive,
-            _occlusion:occlusion,
-            _normal:normal,
+            emissive,
+            occlusion,
+            normal,
         }
     }
 }
 impl Material for PbrMaterial {
-   fn compute_scattering_functions(
+    //glTF金属度-粗糙度模型映射为迪士尼参数
+    fn compute_scattering_functions(
         &self,
         suface: &mut crate::pbrt_core::tool::SurfaceInteraction,
-        _mode: crate::pbrt_core::bxdf::TransportMode,
+        mode: crate::pbrt_core::bxdf::TransportMode,
     ) {
-        let r = self
+        if let Some(ref normal) = self.normal {
+            apply_normal_map(normal.as_ref(), suface);
+        }
+        let mut r = self
             .base_color
             .as_ref()
             .unwrap()
             .evaluate(&suface.common)
             .clamp(Vec3::ZERO, Vec3::splat(f32::INFINITY));
+        //环境光遮蔽取R通道，作用于基础颜色
+        if let Some(ref occlusion) = self.occlusion {
+            r *= occlusion.evaluate(&suface.common).x;
+        }
+        //glTF约定：B通道为金属度，G通道为粗糙度
         let metallic = if let Some(ref metallic) = self.metailc {
-            metallic.evaluate(&suface.common)
+            metallic.evaluate(&suface.common).z
         } else {
-            Vec3::ZERO
+            0.0
         };
-        let r0 = metallic * r + (Vec3::ONE - metallic) * Vec3::splat(0.04);
         let roughness = if let Some(ref roughness) = self.roughness {
-            roughness.evaluate(&suface.common)
+            roughness.evaluate(&suface.common).y
         } else {
-            Vec3::splat(0.5)
+            0.5
+        };
+        let params = DisneyParams {
+            color: r,
+            metallic,
+            roughness,
+            ..Default::default()
         };
-        let roughness = roughness_to_alpha(roughness.y);
-        suface.bsdf = Some(BSDF::new(suface, 1.0));
-        if let Some(bsdf) = &mut suface.bsdf {
-            if r != Vec3::ZERO {
-                bsdf.bxdfs.push(BxDF::PbrDiff(PbrDiff::new(r)));
-                bsdf.bxdfs.push(BxDF::PbrReflection(PbrReflection::new(
-                    r,
-                    Box::new(TrowbridgeReitzDistribution::new(
-                        roughness, roughness, false,
-                    )),
-                    Fresnel::Disney(DisneyFrenel::new(r0, metallic.y, 1.0)),
-                )))
-            }
+        let mut bsdf = BSDF::new(suface, params.eta);
+        params.push_bxdfs(&mut bsdf, mode);
+        suface.bsdf = Some(bsdf);
+    }
+    fn emission(&self, common: &InteractionCommon) -> Vec3 {
+        match self.emissive {
+            Some(ref emissive) => emissive.evaluate(common),
+            None => Vec3::ZERO,
         }
     }
 }
+//切线空间法线贴图，扰动着色法线与切线
+fn apply_normal_map(normal: &dyn Texture, suface: &mut SurfaceInteraction) {
+    let t = normal.evaluate(&suface.common) * 2.0 - Vec3::ONE;
+    let n = suface.common.normal.normalize();
+    let tangent = (suface.shading.dpdu - n * n.dot(suface.shading.dpdu)).normalize();
+    //glTF纹理坐标v轴向下，副切线取dpdv的反方向
+    let mut bitangent = n.cross(tangent);
+    if bitangent.dot(suface.shading.dpdv) > 0.0 {
+        bitangent = -bitangent;
+    }
+    let ns = (tangent * t.x + bitangent * t.y + n * t.z).normalize();
+    if !ns.is_finite() || !tangent.is_finite() {
+        return;
+    }
+    suface.shading.n = ns;
+    suface.shading.dpdu = tangent - ns * ns.dot(tangent);
+}
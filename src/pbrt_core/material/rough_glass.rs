@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use glam::Vec3;
+
+use crate::pbrt_core::{
+    bxdf::{
+        frensnel::{DielectricFresnel, Fresnel},
+        microfacet::MicrofacetTransmission,
+        microfacet_distribution::{roughness_to_alpha, DistributionType},
+        reflection::MicrofacetReflection,
+        BxDF,
+    },
+    material::BSDF,
+    texture::Texture,
+};
+
+use super::Material;
+//磨砂玻璃，粗糙电介质
+#[derive(Debug)]
+pub struct RoughGlassMaterial<'a> {
+    //反射颜色
+    kr: Arc<dyn Texture + 'a>,
+    //透射颜色
+    kt: Arc<dyn Texture + 'a>,
+    //折射率
+    eta: Arc<dyn Texture + 'a>,
+    // 粗糙度
+    roughness: Arc<dyn Texture + 'a>,
+    //微表面分布
+    distribution: DistributionType,
+}
+impl<'a> RoughGlassMaterial<'a> {
+    pub fn new(
+        kr: Arc<dyn Texture + 'a>,
+        kt: Arc<dyn Texture + 'a>,
+        eta: Arc<dyn Texture + 'a>,
+        roughness: Arc<dyn Texture + 'a>,
+        distribution: DistributionType,
+    ) -> Self {
+        Self {
+            kr,
+            kt,
+            eta,
+            roughness,
+            distribution,
+        }
+    }
+}
+impl<'a> Material for RoughGlassMaterial<'a> {
+    fn compute_scattering_functions(
+        &self,
+        surface: &mut crate::pbrt_core::tool::SurfaceInteraction,
+        mode: crate::pbrt_core::bxdf::TransportMode,
+    ) {
+        let eta = self.eta.evaluate(&surface.common).x;
+        let r = self
+            .kr
+            .evaluate(&surface.common)
+            .clamp(Vec3::ZERO, Vec3::splat(f32::INFINITY));
+        let t = self
+            .kt
+            .evaluate(&surface.common)
+            .clamp(Vec3::ZERO, Vec3::splat(f32::INFINITY));
+        let roughness = self.roughness.evaluate(&surface.common);
+        let (u_alpha, v_alpha) = (
+            roughness_to_alpha(roughness.y),
+            roughness_to_alpha(roughness.z),
+        );
+        let mut bsdf = BSDF::new(surface, eta);
+        if r != Vec3::ZERO {
+            let fresnel = Fresnel::Dielectric(DielectricFresnel::new(1.0, eta));
+            let distrib = self.distribution.create(u_alpha, v_alpha);
+            bsdf.bxdfs.push(BxDF::MicrofacetReflection(MicrofacetReflection::new(
+                r, distrib, fresnel,
+            )));
+        }
+        if t != Vec3::ZERO {
+            let distrib = self.distribution.create(u_alpha, v_alpha);
+            bsdf.bxdfs.push(BxDF::MicrofacetTransmission(MicrofacetTransmission::new(
+                t, distrib, 1.0, eta, mode,
+            )));
+        }
+        surface.bsdf = Some(bsdf);
+    }
+}
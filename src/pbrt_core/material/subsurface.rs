@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use glam::Vec3;
+
+use crate::pbrt_core::{
+    bssrdf::BSSRDF,
+    bxdf::{frensnel::FrensnelSpecular, BxDF},
+    texture::Texture,
+};
+
+use super::{Material, BSDF};
+//次表面散射材质，表面为光滑电介质，透射进入内部后由BSSRDF决定出射点
+#[derive(Debug)]
+pub struct SubsurfaceMaterial<'a> {
+    //多次散射后的表面反照率
+    albedo: Arc<dyn Texture + 'a>,
+    //平均自由程，与场景单位一致
+    mfp: Arc<dyn Texture + 'a>,
+    //折射率
+    eta: f32,
+}
+impl<'a> SubsurfaceMaterial<'a> {
+    pub fn new(albedo: Arc<dyn Texture + 'a>, mfp: Arc<dyn Texture + 'a>, eta: f32) -> Self {
+        Self { albedo, mfp, eta }
+    }
+}
+impl<'a> Material for SubsurfaceMaterial<'a> {
+    fn compute_scattering_functions(
+        &self,
+        surface: &mut crate::pbrt_core::tool::SurfaceInteraction,
+        mode: crate::pbrt_core::bxdf::TransportMode,
+    ) {
+        let albedo = self
+            .albedo
+            .evaluate(&surface.common)
+            .clamp(Vec3::ZERO, Vec3::ONE);
+        let mfp = self.mfp.evaluate(&surface.common).max(Vec3::ZERO);
+        let mut bsdf = BSDF::new(surface, self.eta);
+        bsdf.add(BxDF::FrensnelSpecular(FrensnelSpecular::new(
+            Vec3::ONE,
+            Vec3::ONE,
+            1.0,
+            self.eta,
+            mode,
+            None,
+        )));
+        //材质地址用于区分探测光线击中的物体
+        surface.bssrdf = Some(BSSRDF::new(
+            &surface.common,
+            surface.shading.n,
+            self.eta,
+            mode,
+            mfp,
+            albedo,
+            self as *const Self as usize,
+        ));
+        surface.bsdf = Some(bsdf);
+    }
+}
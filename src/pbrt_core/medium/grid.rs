@@ -0,0 +1,311 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use glam::{Mat4, UVec3, Vec3};
+
+use crate::pbrt_core::{
+    sampler::Sampler,
+    tool::{color::Color, func::lerp, Ray},
+};
+
+use super::{HenyeyGreenstein, MediumAble, MediumInteraction};
+
+//网格体数据，x变化最快，在介质空间[0,1]^3内三线性插值
+pub struct DensityGrid {
+    res: UVec3,
+    data: Vec<f32>,
+}
+impl DensityGrid {
+    pub fn new(res: UVec3, data: Vec<f32>) -> Self {
+        assert_eq!(data.len(), (res.x * res.y * res.z) as usize);
+        Self { res, data }
+    }
+    //无文件头的小端f32原始数据
+    pub fn load(path: impl AsRef<Path>, res: UVec3) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let len = (res.x * res.y * res.z) as usize;
+        if bytes.len() != len * 4 {
+            bail!(
+                "{} 大小为 {} 字节，与分辨率 {} 不符",
+                path.display(),
+                bytes.len(),
+                res
+            );
+        }
+        let data = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Ok(Self::new(res, data))
+    }
+    //网格外为零
+    fn d(&self, x: i32, y: i32, z: i32) -> f32 {
+        let res = self.res.as_ivec3();
+        if x < 0 || y < 0 || z < 0 || x >= res.x || y >= res.y || z >= res.z {
+            return 0.0;
+        }
+        self.data[((z * res.y + y) * res.x + x) as usize]
+    }
+    pub fn lookup(&self, p: Vec3) -> f32 {
+        let p = p * self.res.as_vec3() - 0.5;
+        let pi = p.floor();
+        let d = p - pi;
+        let (x, y, z) = (pi.x as i32, pi.y as i32, pi.z as i32);
+        let d00 = lerp(d.x, self.d(x, y, z), self.d(x + 1, y, z));
+        let d10 = lerp(d.x, self.d(x, y + 1, z), self.d(x + 1, y + 1, z));
+        let d01 = lerp(d.x, self.d(x, y, z + 1), self.d(x + 1, y, z + 1));
+        let d11 = lerp(d.x, self.d(x, y + 1, z + 1), self.d(x + 1, y + 1, z + 1));
+        lerp(d.z, lerp(d.y, d00, d10), lerp(d.y, d01, d11))
+    }
+}
+
+//粗网格，每格记录其覆盖范围内密度的最大值
+pub struct MajorantGrid {
+    res: UVec3,
+    voxels: Vec<f32>,
+}
+impl MajorantGrid {
+    pub fn new(density: &DensityGrid, res: UVec3) -> Self {
+        let mut voxels = Vec::with_capacity((res.x * res.y * res.z) as usize);
+        let dres = density.res.as_vec3();
+        for z in 0..res.z {
+            for y in 0..res.y {
+                for x in 0..res.x {
+                    //插值会用到相邻的采样点，范围向外取整
+                    let lo = UVec3::new(x, y, z).as_vec3() / res.as_vec3() * dres - 0.5;
+                    let hi = UVec3::new(x + 1, y + 1, z + 1).as_vec3() / res.as_vec3() * dres - 0.5;
+                    let lo = lo.floor().max(Vec3::ZERO).as_ivec3();
+                    let hi = hi.ceil().min(dres - 1.0).as_ivec3();
+                    let mut max = 0.0_f32;
+                    for k in lo.z..=hi.z {
+                        for j in lo.y..=hi.y {
+                            for i in lo.x..=hi.x {
+                                max = max.max(density.d(i, j, k));
+                            }
+                        }
+                    }
+                    voxels.push(max);
+                }
+            }
+        }
+        Self { res, voxels }
+    }
+    fn lookup(&self, v: UVec3) -> f32 {
+        self.voxels[((v.z * self.res.y + v.y) * self.res.x + v.x) as usize]
+    }
+    //沿介质空间中的光线遍历粗网格，按t从小到大返回(t0,t1,最大密度)
+    pub fn segments(&self, o: Vec3, d: Vec3, t_min: f32, t_max: f32) -> Vec<(f32, f32, f32)> {
+        let mut ans = vec![];
+        //与单位立方体求交
+        let (mut t0, mut t1) = (t_min, t_max);
+        for axis in 0..3 {
+            //平行于坐标面时只需判断是否在范围内
+            if d[axis] == 0.0 {
+                if o[axis] < 0.0 || o[axis] > 1.0 {
+                    return ans;
+                }
+                continue;
+            }
+            let (mut near, mut far) = (-o[axis] / d[axis], (1.0 - o[axis]) / d[axis]);
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+        }
+        if t0 >= t1 {
+            return ans;
+        }
+        let res = self.res.as_vec3();
+        let p = ((o + d * t0) * res).clamp(Vec3::ZERO, res - 1.0);
+        let mut voxel = p.floor().as_uvec3();
+        let mut next = Vec3::ZERO;
+        let mut delta = Vec3::ZERO;
+        for axis in 0..3 {
+            if d[axis] == 0.0 {
+                next[axis] = f32::INFINITY;
+                delta[axis] = f32::INFINITY;
+                continue;
+            }
+            let bound = if d[axis] > 0.0 {
+                (voxel[axis] + 1) as f32
+            } else {
+                voxel[axis] as f32
+            };
+            next[axis] = t0 + (bound / res[axis] - (o[axis] + d[axis] * t0)) / d[axis];
+            delta[axis] = 1.0 / (res[axis] * d[axis].abs());
+        }
+        let mut t = t0;
+        while t < t1 {
+            let axis = if next.x <= next.y && next.x <= next.z {
+                0
+            } else if next.y <= next.z {
+                1
+            } else {
+                2
+            };
+            let exit = next[axis].min(t1);
+            ans.push((t, exit, self.lookup(voxel)));
+            t = exit;
+            if d[axis] > 0.0 {
+                voxel[axis] += 1;
+                if voxel[axis] >= self.res[axis] {
+                    break;
+                }
+            } else {
+                if voxel[axis] == 0 {
+                    break;
+                }
+                voxel[axis] -= 1;
+            }
+            next[axis] += delta[axis];
+        }
+        ans
+    }
+}
+
+//非均匀网格介质，消光按灰度处理，散射率可按通道不同
+pub struct GridDensityMedium {
+    sigma_t: f32,
+    albedo: Color,
+    g: f32,
+    //自发光，存在发光网格时按其数值缩放
+    le: Color,
+    density: DensityGrid,
+    emission: Option<DensityGrid>,
+    majorant: MajorantGrid,
+    world_to_medium: Mat4,
+}
+impl GridDensityMedium {
+    //粗网格的分辨率
+    const MAJORANT_RES: u32 = 16;
+    pub fn new(
+        sigma_a: Color,
+        sigma_s: Color,
+        g: f32,
+        density: DensityGrid,
+        medium_to_world: Mat4,
+    ) -> Self {
+        let sigma = sigma_a + sigma_s;
+        //三个通道的消光系数取平均
+        let sigma_t = sigma.dot(Vec3::ONE) / 3.0;
+        let albedo = Vec3::select(sigma.cmpgt(Vec3::ZERO), sigma_s / sigma, Vec3::ZERO);
+        let majorant =
+            MajorantGrid::new(&density, density.res.min(UVec3::splat(Self::MAJORANT_RES)));
+        Self {
+            sigma_t,
+            albedo,
+            g,
+            le: Color::ZERO,
+            density,
+            emission: None,
+            majorant,
+            world_to_medium: medium_to_world.inverse(),
+        }
+    }
+    pub fn set_emission(&mut self, le: Color, emission: Option<DensityGrid>) {
+        self.le = le;
+        self.emission = emission;
+    }
+    pub fn density(&self, p: Vec3) -> f32 {
+        self.density.lookup(p)
+    }
+    //介质空间中的光线，t与世界空间一致
+    fn to_medium(&self, ray: &Ray) -> (Vec3, Vec3) {
+        (
+            self.world_to_medium.transform_point3(ray.origin),
+            self.world_to_medium.transform_vector3(ray.dir),
+        )
+    }
+}
+impl MediumAble for GridDensityMedium {
+    //比率追踪
+    fn tr(&self, ray: &Ray, sampler: &mut Sampler) -> Color {
+        let (o, d) = self.to_medium(ray);
+        let mut tr = 1.0;
+        for (t0, t1, max) in self.majorant.segments(o, d, 0.0, ray.t_max) {
+            let sigma_maj = self.sigma_t * max;
+            if sigma_maj <= 0.0 {
+                continue;
+            }
+            let mut t = t0;
+            loop {
+                t -= (1.0 - sampler.sample_1d_d()).ln() / sigma_maj;
+                if t >= t1 {
+                    break;
+                }
+                tr *= 1.0 - self.density(o + d * t) / max;
+                //透射率很小时俄罗斯轮盘赌
+                if tr < 0.1 {
+                    let q = (1.0 - tr).max(0.05);
+                    if sampler.sample_1d_d() < q {
+                        return Color::ZERO;
+                    }
+                    tr /= 1.0 - q;
+                }
+            }
+        }
+        Color::splat(tr)
+    }
+    //Delta追踪，真实碰撞即散射，吸收计入albedo权重
+    fn sample(
+        &self,
+        ray: &Ray,
+        sampler: &mut Sampler,
+        mi: &mut Option<MediumInteraction>,
+    ) -> Color {
+        let (o, d) = self.to_medium(ray);
+        *mi = None;
+        for (t0, t1, max) in self.majorant.segments(o, d, 0.0, ray.t_max) {
+            let sigma_maj = self.sigma_t * max;
+            if sigma_maj <= 0.0 {
+                continue;
+            }
+            let mut t = t0;
+            loop {
+                t -= (1.0 - sampler.sample_1d_d()).ln() / sigma_maj;
+                if t >= t1 {
+                    break;
+                }
+                if sampler.sample_1d_d() < self.density(o + d * t) / max {
+                    *mi = Some(MediumInteraction {
+                        p: ray.at(t),
+                        wo: -ray.dir,
+                        phase: HenyeyGreenstein::new(self.g),
+                    });
+                    return self.albedo;
+                }
+            }
+        }
+        Color::ONE
+    }
+    //Delta追踪到真实碰撞为止，每个候选点计入sigma_a*Le/sigma_maj
+    fn le(&self, ray: &Ray, sampler: &mut Sampler) -> Color {
+        if self.le == Color::ZERO {
+            return Color::ZERO;
+        }
+        let (o, d) = self.to_medium(ray);
+        let mut l = Color::ZERO;
+        for (t0, t1, max) in self.majorant.segments(o, d, 0.0, ray.t_max) {
+            if self.sigma_t * max <= 0.0 {
+                continue;
+            }
+            let mut t = t0;
+            loop {
+                t -= (1.0 - sampler.sample_1d_d()).ln() / (self.sigma_t * max);
+                if t >= t1 {
+                    break;
+                }
+                let p = o + d * t;
+                let density = self.density(p);
+                let scale = self.emission.as_ref().map_or(1.0, |grid| grid.lookup(p));
+                l += (Color::ONE - self.albedo) * self.le * scale * density / max;
+                if sampler.sample_1d_d() < density / max {
+                    return l;
+                }
+            }
+        }
+        l
+    }
+}
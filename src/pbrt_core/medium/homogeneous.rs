@@ -0,0 +1,56 @@
+use glam::Vec3;
+
+use crate::pbrt_core::{
+    sampler::Sampler,
+    tool::{color::Color, Ray},
+};
+
+use super::{HenyeyGreenstein, MediumAble, MediumInteraction};
+
+//均匀介质，吸收与散射系数处处相同
+pub struct HomogeneousMedium {
+    sigma_s: Color,
+    sigma_t: Color,
+    g: f32,
+}
+impl HomogeneousMedium {
+    pub fn new(sigma_a: Color, sigma_s: Color, g: f32) -> Self {
+        Self {
+            sigma_s,
+            sigma_t: sigma_a + sigma_s,
+            g,
+        }
+    }
+}
+impl MediumAble for HomogeneousMedium {
+    fn tr(&self, ray: &Ray, _sampler: &mut Sampler) -> Color {
+        (-self.sigma_t * ray.t_max).exp()
+    }
+    fn sample(
+        &self,
+        ray: &Ray,
+        sampler: &mut Sampler,
+        mi: &mut Option<MediumInteraction>,
+    ) -> Color {
+        //随机选一个通道按其消光系数采样距离
+        let channel = sampler.sample_index(3);
+        let dist = -(1.0 - sampler.sample_1d_d()).ln() / self.sigma_t[channel];
+        let t = dist.min(ray.t_max);
+        let sampled = t < ray.t_max;
+        *mi = sampled.then(|| MediumInteraction {
+            p: ray.at(t),
+            wo: -ray.dir,
+            phase: HenyeyGreenstein::new(self.g),
+        });
+        let tr = (-self.sigma_t * t).exp();
+        //三个通道的采样pdf取平均
+        let density = if sampled { self.sigma_t * tr } else { tr };
+        let pdf = density.dot(Vec3::ONE) / 3.0;
+        let pdf = if pdf == 0.0 { 1.0 } else { pdf };
+        if sampled {
+            tr * self.sigma_s / pdf
+        } else {
+            tr / pdf
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use self::{grid::GridDensityMedium, homogeneous::HomogeneousMedium};
+use super::{
+    sampler::Sampler,
+    tool::{color::Color, func, Ray},
+};
+
+pub mod grid;
+pub mod homogeneous;
+
+pub trait MediumAble {
+    //光线从起点传播到t_max处的透射率，光线方向为单位向量
+    fn tr(&self, ray: &Ray, sampler: &mut Sampler) -> Color;
+    //在[0,t_max)内按透射率采样散射点，返回路径吞吐量的权重，未散射时mi为None
+    fn sample(&self, ray: &Ray, sampler: &mut Sampler, mi: &mut Option<MediumInteraction>)
+        -> Color;
+    //光线从起点到t_max之间介质自发光的估计，已计入透射率
+    fn le(&self, _ray: &Ray, _sampler: &mut Sampler) -> Color {
+        Color::ZERO
+    }
+}
+
+pub enum Medium {
+    Homogeneous(HomogeneousMedium),
+    Grid(Box<GridDensityMedium>),
+}
+impl MediumAble for Medium {
+    fn tr(&self, ray: &Ray, sampler: &mut Sampler) -> Color {
+        match self {
+            Medium::Homogeneous(medium) => medium.tr(ray, sampler),
+            Medium::Grid(medium) => medium.tr(ray, sampler),
+        }
+    }
+    fn sample(
+        &self,
+        ray: &Ray,
+        sampler: &mut Sampler,
+        mi: &mut Option<MediumInteraction>,
+    ) -> Color {
+        match self {
+            Medium::Homogeneous(medium) => medium.sample(ray, sampler, mi),
+            Medium::Grid(medium) => medium.sample(ray, sampler, mi),
+        }
+    }
+    fn le(&self, ray: &Ray, sampler: &mut Sampler) -> Color {
+        match self {
+            Medium::Homogeneous(medium) => medium.le(ray, sampler),
+            Medium::Grid(medium) => medium.le(ray, sampler),
+        }
+    }
+}
+
+//表面两侧的介质，值为场景介质列表的下标，outside为几何法线所指的一侧
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MediumInterface {
+    #[serde(default)]
+    pub inside: Option<usize>,
+    #[serde(default)]
+    pub outside: Option<usize>,
+}
+impl MediumInterface {
+    pub fn new(inside: Option<usize>, outside: Option<usize>) -> Self {
+        Self { inside, outside }
+    }
+    pub fn is_transition(&self) -> bool {
+        self.inside != self.outside
+    }
+}
+
+//介质中的散射点，所在介质由积分器记录
+#[derive(Debug, Clone, Copy)]
+pub struct MediumInteraction {
+    pub p: Vec3,
+    //指向光线来的方向
+    pub wo: Vec3,
+    pub phase: HenyeyGreenstein,
+}
+
+//Henyey-Greenstein相函数，g>0时偏向前向散射
+#[derive(Debug, Clone, Copy)]
+pub struct HenyeyGreenstein {
+    g: f32,
+}
+impl HenyeyGreenstein {
+    pub fn new(g: f32) -> Self {
+        Self { g }
+    }
+    //wo、wi均背离散射点
+    pub fn p(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        phase_hg(wo.dot(*wi), self.g)
+    }
+    //按相函数采样wi，返回的pdf与相函数值相等
+    pub fn sample_p(&self, wo: &Vec3, wi: &mut Vec3, u: Vec2) -> f32 {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u.x
+        } else {
+            let sqr = (1.0 - g * g) / (1.0 + g - 2.0 * g * u.x);
+            -(1.0 + g * g - sqr * sqr) / (2.0 * g)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.y;
+        let (mut v1, mut v2) = (Vec3::ZERO, Vec3::ZERO);
+        func::vec3_coordinate_system(*wo, &mut v1, &mut v2);
+        let local = func::spherical_direction(sin_theta, cos_theta, phi);
+        *wi = v1 * local.x + v2 * local.y + *wo * local.z;
+        phase_hg(cos_theta, g)
+    }
+}
+pub fn phase_hg(cos_theta: f32, g: f32) -> f32 {
+    let denom = 1.0 + g * g + 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denom * denom.max(0.0).sqrt())
+}
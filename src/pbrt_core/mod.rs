@@ -28,4 +28,7 @@ pub mod light;
 
 pub mod filter;
 
-pub mod bssrdf;
\ No newline at end of file
+pub mod bssrdf;
+
+//参与介质
+pub mod medium;
\ No newline at end of file
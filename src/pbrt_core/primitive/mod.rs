@@ -1,11 +1,13 @@
 use std::fmt::Debug;
 
 use ::bvh::{aabb::Bounded, bounding_hierarchy::BHShape};
+use glam::Vec3;
 
 use super::{
     bxdf::TransportMode,
     light::LightAble,
-    tool::{Bound, RayDiff, SurfaceInteraction},
+    medium::MediumInterface,
+    tool::{Bound, InteractionCommon, RayDiff, SurfaceInteraction},
 };
 // use ::bvh::{aabb::Bounded, bounding_hierarchy::BHShape};
 
@@ -125,6 +127,10 @@ pub trait Primitive: Debug {
     }
     //材质计算
     fn compute_scattering(&self, _isct: &mut SurfaceInteraction, _mode: TransportMode) {}
+    //表面自发光
+    fn emission(&self, _common: &InteractionCommon) -> Vec3 {
+        Vec3::ZERO
+    }
     //获取光源
     fn get_light(&self) -> Option<&dyn LightAble> {
         None
@@ -133,6 +139,10 @@ pub trait Primitive: Debug {
         1.0
     }
     fn hit_p(&self, ray: &RayDiff) -> bool;
+    //表面两侧的介质，None表示不是介质边界
+    fn medium_interface(&self) -> Option<MediumInterface> {
+        None
+    }
 }
 pub trait Aggregate: Sync {
     fn interacect(&self, ray: &RayDiff) -> Option<SurfaceInteraction>;
@@ -202,4 +212,7 @@ impl<'a> Primitive for GeometricePrimitive<'a> {
     fn hit_p(&self, ray: &RayDiff) -> bool {
         self.primitive.hit_p(ray)
     }
+    fn medium_interface(&self) -> Option<MediumInterface> {
+        self.primitive.medium_interface()
+    }
 }
@@ -6,6 +6,7 @@ use gltf::mesh::util::tex_coords;
 use crate::pbrt_core::{
     bxdf::TransportMode,
     material::Material,
+    medium::MediumInterface,
     primitive::{mesh::Mesh, Primitive},
     tool::{Bound, RayDiff, Shading, SurfaceInteraction, InteractionCommon},
 };
@@ -17,6 +18,7 @@ pub struct Triangle<'a> {
     mesh: Arc<Mesh>,
     obj_to_world: Mat4,
     materail: Option<&'a Box<dyn Material + 'a>>,
+    medium_interface: Option<MediumInterface>,
 }
 #[allow(unused)]
 impl<'a> Triangle<'a> {
@@ -27,6 +29,7 @@ impl<'a> Triangle<'a> {
         mesh: Arc<Mesh>,
         obj_to_world: Mat4,
         materail: Option<&'a Box<dyn Material + 'a>>,
+        medium_interface: Option<MediumInterface>,
     ) -> Self {
         Self {
             point_index: [
@@ -46,6 +49,7 @@ impl<'a> Triangle<'a> {
             ],
             mesh,
             materail,
+            medium_interface,
             obj_to_world,
         }
     }
@@ -179,7 +183,17 @@ impl<'a> Primitive for Triangle<'a> {
             None => (),
         }
     }
-    fn hit_p(&self, _ray: &RayDiff) -> bool {
-        false
+    fn emission(&self, common: &InteractionCommon) -> Vec3 {
+        match &self.materail {
+            Some(material) => material.emission(common),
+            None => Vec3::ZERO,
+        }
+    }
+    fn medium_interface(&self) -> Option<MediumInterface> {
+        self.medium_interface
+    }
+    fn hit_p(&self, ray: &RayDiff) -> bool {
+        self.interacect(*ray)
+            .is_some_and(|item| item.common.time > ray.o.t_min && item.common.time < ray.o.t_max)
     }
 }
@@ -1,5 +1,5 @@
-use std::cmp::Ordering;
-
+//分段常数一维分布
+#[derive(Debug, Clone)]
 pub struct Distribution1D {
     func: Vec<f32>,
     cdf: Vec<f32>,
@@ -7,7 +7,7 @@ pub struct Distribution1D {
 }
 impl Distribution1D {
     pub fn new(func: &[f32], n: usize) -> Self {
-        let func: Vec<f32> = func.to_vec();
+        let func: Vec<f32> = func.iter().take(n).map(|x| x.abs()).collect();
         let mut cdf = vec![];
         cdf.push(0.0);
         for i in 1..n + 1 {
@@ -15,12 +15,12 @@ impl Distribution1D {
         }
         let func_int = cdf[n];
         if func_int == 0.0 {
-            for i in 1..n + 1 {
-                cdf[i] = i as f32 / n as f32;
+            for (i, item) in cdf.iter_mut().enumerate().skip(1) {
+                *item = i as f32 / n as f32;
             }
         } else {
-            for i in 1..n + 1 {
-                cdf[i] /= func_int;
+            for item in cdf.iter_mut().skip(1) {
+                *item /= func_int;
             }
         };
         Self {
@@ -32,26 +32,46 @@ impl Distribution1D {
     pub fn count(&self) -> usize {
         self.func.len()
     }
-    //二分查找
-    pub fn sample_continuous(&self, u: f32, _pdf: &[f32], off: Option<&mut i32>) -> f32 {
-        let ans = self.cdf.binary_search_by(|x| {
-            if *x <= u {
-                Ordering::Less
-            } else {
-                Ordering::Greater
-            }
-        });
-        let index = match ans {
-            Ok(index) => index,
-            Err(index) => index,
+    //函数在[0,1]上的积分
+    pub fn func_int(&self) -> f32 {
+        self.func_int
+    }
+    pub fn func(&self) -> &[f32] {
+        &self.func
+    }
+    //二分查找满足 cdf[i] <= u 的最大区间
+    fn find_interval(&self, u: f32) -> usize {
+        let index = self.cdf.partition_point(|x| *x <= u);
+        index.saturating_sub(1).min(self.count() - 1)
+    }
+    //连续采样，返回[0,1)内的值，pdf为对应密度
+    pub fn sample_continuous(&self, u: f32, pdf: &mut f32, off: Option<&mut usize>) -> f32 {
+        let index = self.find_interval(u);
+        if let Some(off) = off {
+            *off = index;
         };
-        if let Some( off)=off{
-            *off=self.cdf[index] as i32;
+        let mut du = u - self.cdf[index];
+        if self.cdf[index + 1] - self.cdf[index] > 0.0 {
+            du /= self.cdf[index + 1] - self.cdf[index];
         };
-        let mut  du=u-self.cdf[index];
-        if self.cdf[index+1]-self.cdf[index]>0.0{
-            du/=self.cdf[index+1]-self.cdf[index];
+        *pdf = if self.func_int > 0.0 {
+            self.func[index] / self.func_int
+        } else {
+            0.0
         };
-        unimplemented!()
+        (index as f32 + du) / self.count() as f32
+    }
+    //离散采样，返回区间下标
+    pub fn sample_discrete(&self, u: f32, pdf: &mut f32) -> usize {
+        let index = self.find_interval(u);
+        *pdf = self.discrete_pdf(index);
+        index
+    }
+    pub fn discrete_pdf(&self, index: usize) -> f32 {
+        if self.func_int > 0.0 {
+            self.func[index] / (self.func_int * self.count() as f32)
+        } else {
+            0.0
+        }
     }
 }
@@ -0,0 +1,45 @@
+use glam::Vec2;
+
+use super::distribution_1d::Distribution1D;
+
+//分段常数二维分布，先按边缘分布采样v，再按条件分布采样u
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+impl Distribution2D {
+    //func按行存储，共nv行，每行nu个值
+    pub fn new(func: &[f32], nu: usize, nv: usize) -> Self {
+        let conditional = (0..nv)
+            .map(|v| Distribution1D::new(&func[v * nu..(v + 1) * nu], nu))
+            .collect::<Vec<_>>();
+        let marginal_func = conditional
+            .iter()
+            .map(|item| item.func_int())
+            .collect::<Vec<_>>();
+        let marginal = Distribution1D::new(&marginal_func, nv);
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+    pub fn sample_continuous(&self, u: Vec2, pdf: &mut f32) -> Vec2 {
+        let mut pdfs = [0.0; 2];
+        let mut v = 0;
+        let d1 = self.marginal.sample_continuous(u.y, &mut pdfs[1], Some(&mut v));
+        let d0 = self.conditional[v].sample_continuous(u.x, &mut pdfs[0], None);
+        *pdf = pdfs[0] * pdfs[1];
+        Vec2::new(d0, d1)
+    }
+    pub fn pdf(&self, p: Vec2) -> f32 {
+        let nu = self.conditional[0].count();
+        let nv = self.marginal.count();
+        let iu = ((p.x * nu as f32) as usize).min(nu - 1);
+        let iv = ((p.y * nv as f32) as usize).min(nv - 1);
+        if self.marginal.func_int() == 0.0 {
+            return 0.0;
+        }
+        self.conditional[iv].func()[iu] / self.marginal.func_int()
+    }
+}
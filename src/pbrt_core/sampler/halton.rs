@@ -0,0 +1,68 @@
+use glam::{UVec2, Vec2};
+
+use super::{
+    hash_float, mix_bits, permutation_element, PixelSample, SamplerAble, ONE_MINUS_EPSILON,
+};
+
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+//Halton序列，每个维度用像素哈希做Owen扰乱，超出素数表的维度退化为独立随机
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    spp: usize,
+    state: PixelSample,
+}
+impl HaltonSampler {
+    pub fn new(spp: usize, seed: u64) -> Self {
+        Self {
+            spp,
+            state: PixelSample::new(seed),
+        }
+    }
+}
+impl SamplerAble for HaltonSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.spp
+    }
+    fn start_pixel_sample(&mut self, pixel: UVec2, index: usize) {
+        self.state.start(pixel, index);
+    }
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.state.dimension as usize;
+        let u = if dimension < PRIMES.len() {
+            owen_scrambled_radical_inverse(
+                PRIMES[dimension],
+                self.state.index as u64,
+                self.state.dimension_hash(),
+            )
+        } else {
+            hash_float(self.state.sample_hash())
+        };
+        self.state.dimension += 1;
+        u
+    }
+    fn get_2d(&mut self) -> Vec2 {
+        Vec2::new(self.get_1d(), self.get_1d())
+    }
+}
+//以base为底的逆序数，每一位按前缀哈希随机置换
+pub fn owen_scrambled_radical_inverse(base: u64, mut a: u64, hash: u64) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut reversed_digits: u64 = 0;
+    let mut inv_base_m = 1.0;
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_hash = mix_bits(hash ^ reversed_digits) as u32;
+        let digit = permutation_element(digit as u32, base as u32, digit_hash) as u64;
+        reversed_digits = reversed_digits * base + digit;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+    ((inv_base_m * reversed_digits as f64) as f32).min(ONE_MINUS_EPSILON)
+}
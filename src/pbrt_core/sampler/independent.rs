@@ -0,0 +1,34 @@
+use glam::{UVec2, Vec2};
+
+use super::{hash_float, PixelSample, SamplerAble};
+
+//独立均匀随机采样，每个维度由像素、样本下标与维度哈希得到
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    spp: usize,
+    state: PixelSample,
+}
+impl IndependentSampler {
+    pub fn new(spp: usize, seed: u64) -> Self {
+        Self {
+            spp,
+            state: PixelSample::new(seed),
+        }
+    }
+}
+impl SamplerAble for IndependentSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.spp
+    }
+    fn start_pixel_sample(&mut self, pixel: UVec2, index: usize) {
+        self.state.start(pixel, index);
+    }
+    fn get_1d(&mut self) -> f32 {
+        let u = hash_float(self.state.sample_hash());
+        self.state.dimension += 1;
+        u
+    }
+    fn get_2d(&mut self) -> Vec2 {
+        Vec2::new(self.get_1d(), self.get_1d())
+    }
+}
@@ -1,91 +1,229 @@
-use std::{time::SystemTime, f32::consts::{FRAC_PI_4, FRAC_PI_2}};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
 
-use glam::{Vec2, Vec3};
-use rand::{rngs::StdRng, SeedableRng, Rng};
+use glam::{UVec2, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use self::{
+    halton::HaltonSampler, independent::IndependentSampler, sobol::SobolSampler,
+    stratified::StratifiedSampler,
+};
 pub mod distribution_1d;
-pub struct Sampler {
-   pub  rand: StdRng,
-   pub  num:usize
+pub mod distribution_2d;
+pub mod halton;
+pub mod independent;
+pub mod sobol;
+pub mod stratified;
+
+//小于1的最大浮点数
+pub const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+//采样器类型
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerMode {
+    #[default]
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+pub trait SamplerAble {
+    //每个像素的样本数
+    fn samples_per_pixel(&self) -> usize;
+    //开始像素中的第index个样本，维度从0重新计数
+    fn start_pixel_sample(&mut self, pixel: UVec2, index: usize);
+    fn get_1d(&mut self) -> f32;
+    fn get_2d(&mut self) -> Vec2;
+}
+
+#[derive(Debug, Clone)]
+pub enum Sampler {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
 }
 impl Default for Sampler {
     fn default() -> Self {
-        unsafe {
-            Sampler {
-                rand: SeedableRng::seed_from_u64(
-                    SystemTime::now().elapsed().unwrap_unchecked().as_secs(),
-                ),
-                num:1
-            }
-        }
+        Self::new(SamplerMode::Independent, 1, 0)
     }
 }
-impl Clone for Sampler{
-    fn clone(&self) -> Self {
-        unsafe {
-            Sampler {
-                rand: SeedableRng::seed_from_u64(
-                    SystemTime::now().elapsed().unwrap_unchecked().as_secs(),
-                ),
-                num:self.num
-            }
+impl SamplerAble for Sampler {
+    fn samples_per_pixel(&self) -> usize {
+        match self {
+            Sampler::Independent(sampler) => sampler.samples_per_pixel(),
+            Sampler::Stratified(sampler) => sampler.samples_per_pixel(),
+            Sampler::Halton(sampler) => sampler.samples_per_pixel(),
+            Sampler::Sobol(sampler) => sampler.samples_per_pixel(),
+        }
+    }
+    fn start_pixel_sample(&mut self, pixel: UVec2, index: usize) {
+        match self {
+            Sampler::Independent(sampler) => sampler.start_pixel_sample(pixel, index),
+            Sampler::Stratified(sampler) => sampler.start_pixel_sample(pixel, index),
+            Sampler::Halton(sampler) => sampler.start_pixel_sample(pixel, index),
+            Sampler::Sobol(sampler) => sampler.start_pixel_sample(pixel, index),
+        }
+    }
+    fn get_1d(&mut self) -> f32 {
+        match self {
+            Sampler::Independent(sampler) => sampler.get_1d(),
+            Sampler::Stratified(sampler) => sampler.get_1d(),
+            Sampler::Halton(sampler) => sampler.get_1d(),
+            Sampler::Sobol(sampler) => sampler.get_1d(),
+        }
+    }
+    fn get_2d(&mut self) -> Vec2 {
+        match self {
+            Sampler::Independent(sampler) => sampler.get_2d(),
+            Sampler::Stratified(sampler) => sampler.get_2d(),
+            Sampler::Halton(sampler) => sampler.get_2d(),
+            Sampler::Sobol(sampler) => sampler.get_2d(),
         }
     }
 }
 impl Sampler {
-    pub fn new(num:usize)->Self{
-        unsafe {
-            Sampler {
-                rand: SeedableRng::seed_from_u64(
-                    SystemTime::now().elapsed().unwrap_unchecked().as_secs(),
-                ),
-                num
-            }
+    //seed为场景种子，所有随机数由种子、像素坐标、样本下标与维度决定
+    pub fn new(mode: SamplerMode, num: usize, seed: u64) -> Self {
+        let num = num.max(1);
+        match mode {
+            SamplerMode::Independent => Self::Independent(IndependentSampler::new(num, seed)),
+            SamplerMode::Stratified => Self::Stratified(StratifiedSampler::new(num, seed)),
+            SamplerMode::Halton => Self::Halton(HaltonSampler::new(num, seed)),
+            SamplerMode::Sobol => Self::Sobol(SobolSampler::new(num, seed)),
         }
     }
-    pub fn sample_1d_d(&mut self)->f32{
-        self.rand.gen_range(0.0..1.0)
+    pub fn sample_1d_d(&mut self) -> f32 {
+        self.get_1d()
+    }
+    pub fn sample_2d_d(&mut self) -> Vec2 {
+        self.get_2d()
     }
-    pub fn sample_2d_d(&mut self)->Vec2{
-        let x=self.sample_1d_d();
-        let y=self.sample_1d_d();
-        Vec2{x,y }
+    pub fn sample_2d(&mut self) -> Vec2 {
+        self.get_2d()
     }
-    pub fn sample_2d(&mut self)->Vec2{
-        self.sample_2d_d()
+    pub fn sample_d(&mut self) -> f32 {
+        self.get_1d()
     }
-    pub fn sample_d(&mut self)->f32{
-        self.sample_1d_d()
+    pub fn smapel_dir(&mut self) -> Vec3 {
+        let x = self.sample_1d_d();
+        let y = self.sample_1d_d();
+        let z = self.sample_1d_d();
+        Vec3 { x, y, z }.normalize()
     }
-    pub fn smapel_dir(&mut self)->Vec3{
-        let x=self.sample_1d_d();
-        let y=self.sample_1d_d();
-        let z=self.sample_1d_d();
-        Vec3 { x,y, z }.normalize()
+    //从[0,len)中均匀选取一个下标
+    pub fn sample_index(&mut self, len: usize) -> usize {
+        ((self.get_1d() * len as f32) as usize).min(len.saturating_sub(1))
     }
 }
-pub fn cosine_sample_hemisphere(u:Vec2)->Vec3{
-    let d=concentric_sample_disk(u);
-    let z=0.0_f32.max(1.0-d.length_squared()).sqrt();
+
+//像素样本的状态，各采样器共用
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PixelSample {
+    pub pixel: UVec2,
+    pub index: usize,
+    pub dimension: u32,
+    pub seed: u64,
+}
+impl PixelSample {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+    pub fn start(&mut self, pixel: UVec2, index: usize) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+    //当前像素与维度的哈希，不含样本下标
+    pub fn dimension_hash(&self) -> u64 {
+        hash(&[
+            self.seed,
+            self.pixel.x as u64,
+            self.pixel.y as u64,
+            self.dimension as u64,
+        ])
+    }
+    //当前像素、样本与维度的哈希
+    pub fn sample_hash(&self) -> u64 {
+        hash(&[self.dimension_hash(), self.index as u64])
+    }
+}
+
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+    v
+}
+pub fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x9e3779b97f4a7c15, |h, v| mix_bits(h ^ mix_bits(*v)))
+}
+//哈希值转为[0,1)的浮点数
+pub fn hash_float(h: u64) -> f32 {
+    ((h >> 40) as f32 * (1.0 / (1u64 << 24) as f32)).min(ONE_MINUS_EPSILON)
+}
+//返回[0,l)的随机排列中第i个元素(Kensler)
+pub fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+pub fn cosine_sample_hemisphere(u: Vec2) -> Vec3 {
+    let d = concentric_sample_disk(u);
+    let z = 0.0_f32.max(1.0 - d.length_squared()).sqrt();
     d.extend(z)
 }
 
-pub fn concentric_sample_disk(u:Vec2)->Vec2{
-    let offset=u*2.0-Vec2::ONE;
-    if offset.x==0.0&&offset.y==0.0{
+pub fn concentric_sample_disk(u: Vec2) -> Vec2 {
+    let offset = u * 2.0 - Vec2::ONE;
+    if offset.x == 0.0 && offset.y == 0.0 {
         return Vec2::ZERO;
     }
     let theta;
     let r;
-    if offset.x.abs()>offset.y.abs(){
-        r=offset.x;
-        theta=FRAC_PI_4* (offset.y/offset.x);
-    }else{
-        r=offset.y;
-        theta=FRAC_PI_2-FRAC_PI_4*(offset.x/offset.y)
-    }
-    Vec2{
-        x:theta.cos(),
-        y:theta.sin(),
-    }*r
-
-}
\ No newline at end of file
+    if offset.x.abs() > offset.y.abs() {
+        r = offset.x;
+        theta = FRAC_PI_4 * (offset.y / offset.x);
+    } else {
+        r = offset.y;
+        theta = FRAC_PI_2 - FRAC_PI_4 * (offset.x / offset.y)
+    }
+    Vec2 {
+        x: theta.cos(),
+        y: theta.sin(),
+    } * r
+}
@@ -0,0 +1,81 @@
+use glam::{UVec2, Vec2};
+
+use super::{hash, permutation_element, PixelSample, SamplerAble, ONE_MINUS_EPSILON};
+
+//Owen扰乱的Sobol采样，高维由前两维(0,2)序列按维度随机排列样本下标填充
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    spp: usize,
+    state: PixelSample,
+}
+impl SobolSampler {
+    pub fn new(spp: usize, seed: u64) -> Self {
+        Self {
+            spp,
+            state: PixelSample::new(seed),
+        }
+    }
+    //本维度中打乱后的样本下标
+    fn sample_index(&self) -> u32 {
+        let p = self.state.dimension_hash() as u32;
+        permutation_element(
+            self.state.index as u32 % self.spp as u32,
+            self.spp as u32,
+            p,
+        )
+    }
+}
+impl SamplerAble for SobolSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.spp
+    }
+    fn start_pixel_sample(&mut self, pixel: UVec2, index: usize) {
+        self.state.start(pixel, index);
+    }
+    fn get_1d(&mut self) -> f32 {
+        let index = self.sample_index();
+        let seed = hash(&[self.state.dimension_hash(), 0]) as u32;
+        self.state.dimension += 1;
+        to_float(owen_scramble(sobol_0(index), seed))
+    }
+    fn get_2d(&mut self) -> Vec2 {
+        let index = self.sample_index();
+        let h = self.state.dimension_hash();
+        let (seed_x, seed_y) = (hash(&[h, 1]) as u32, hash(&[h, 2]) as u32);
+        self.state.dimension += 2;
+        Vec2::new(
+            to_float(owen_scramble(sobol_0(index), seed_x)),
+            to_float(owen_scramble(sobol_1(index), seed_y)),
+        )
+    }
+}
+fn to_float(v: u32) -> f32 {
+    (v as f32 * (1.0 / (1u64 << 32) as f32)).min(ONE_MINUS_EPSILON)
+}
+//第一维即以2为底的逆序数
+fn sobol_0(a: u32) -> u32 {
+    a.reverse_bits()
+}
+//第二维，生成矩阵的列满足 v_i = v_{i-1} ^ (v_{i-1} >> 1)
+fn sobol_1(mut a: u32) -> u32 {
+    let mut v: u32 = 1 << 31;
+    let mut x = 0;
+    while a != 0 {
+        if a & 1 == 1 {
+            x ^= v;
+        }
+        a >>= 1;
+        v ^= v >> 1;
+    }
+    x
+}
+//Laine-Karras风格的快速Owen扰乱
+fn owen_scramble(mut v: u32, seed: u32) -> u32 {
+    v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x05526c56);
+    v ^= v.wrapping_mul(0x53a22864);
+    v.reverse_bits()
+}
@@ -0,0 +1,58 @@
+use glam::{UVec2, Vec2};
+
+use super::{hash, hash_float, permutation_element, PixelSample, SamplerAble, ONE_MINUS_EPSILON};
+
+//分层抖动采样，每个维度对像素内的样本做随机排列后落入不同的层
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    spp: usize,
+    //二维分层的格子数
+    x_strata: u32,
+    y_strata: u32,
+    state: PixelSample,
+}
+impl StratifiedSampler {
+    pub fn new(spp: usize, seed: u64) -> Self {
+        let x_strata = (spp as f32).sqrt().ceil() as u32;
+        let y_strata = (spp as u32).div_ceil(x_strata);
+        Self {
+            spp,
+            x_strata,
+            y_strata,
+            state: PixelSample::new(seed),
+        }
+    }
+    //当前样本在本维度中所在的层
+    fn stratum(&self, count: u32) -> u32 {
+        let p = self.state.dimension_hash() as u32;
+        permutation_element(self.state.index as u32 % count, count, p)
+    }
+    fn jitter(&self, salt: u64) -> f32 {
+        hash_float(hash(&[self.state.sample_hash(), salt]))
+    }
+}
+impl SamplerAble for StratifiedSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.spp
+    }
+    fn start_pixel_sample(&mut self, pixel: UVec2, index: usize) {
+        self.state.start(pixel, index);
+    }
+    fn get_1d(&mut self) -> f32 {
+        let count = self.spp as u32;
+        let u = (self.stratum(count) as f32 + self.jitter(0)) / count as f32;
+        self.state.dimension += 1;
+        u.min(ONE_MINUS_EPSILON)
+    }
+    fn get_2d(&mut self) -> Vec2 {
+        let count = self.x_strata * self.y_strata;
+        let stratum = self.stratum(count);
+        let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+        let u = Vec2::new(
+            (x as f32 + self.jitter(0)) / self.x_strata as f32,
+            (y as f32 + self.jitter(1)) / self.y_strata as f32,
+        );
+        self.state.dimension += 2;
+        u.min(Vec2::splat(ONE_MINUS_EPSILON))
+    }
+}
@@ -1,9 +1,8 @@
-use std::fmt::Debug;
-
 use glam::Vec3;
 
 use super::Texture;
 
+#[derive(Debug)]
 pub struct ConstantTexture{
     value:Vec3
 }
@@ -17,8 +16,3 @@ impl ConstantTexture{
         Self { value }
     }
 }
-impl Debug for ConstantTexture{
-    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        unimplemented!()
-    }
-}
\ No newline at end of file
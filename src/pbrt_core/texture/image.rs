@@ -1,4 +1,4 @@
-use glam::{Vec3, Vec2};
+use glam::{UVec2, Vec2, Vec3};
 
 
 use crate::pbrt_core::{filter::Filter, tool::mipmap::MipMap};
@@ -20,7 +20,11 @@ impl ImageTexture {
 
 impl Texture for ImageTexture {
     fn evaluate(&self, inter: &crate::pbrt_core::tool::InteractionCommon) -> Vec3 {
-        self.mipmap.lookup(inter.uv,Vec2::ZERO,Vec2::ZERO)
+        let uv = self.filter.filter_uv(&inter.uv);
+        self.mipmap.lookup(uv, Vec2::ZERO, Vec2::ZERO)
         
     }
+    fn resolution(&self) -> Option<UVec2> {
+        Some(self.mipmap.resolution())
+    }
 }
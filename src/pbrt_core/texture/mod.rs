@@ -1,12 +1,17 @@
 use std::fmt::Debug;
 
-use glam::Vec3;
+use glam::{UVec2, Vec3};
 
 use super::tool::InteractionCommon;
 pub mod constant;
 pub mod scale;
 pub mod mix;
 pub mod image;
-pub trait Texture: Debug {
+//纹理在渲染线程间共享
+pub trait Texture: Debug + Send + Sync {
     fn  evaluate(&self,inter:&InteractionCommon)->Vec3;
+    //图像纹理的分辨率，其余纹理没有固定分辨率
+    fn resolution(&self) -> Option<UVec2> {
+        None
+    }
 }
\ No newline at end of file
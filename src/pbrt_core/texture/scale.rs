@@ -1,17 +1,18 @@
 use std::sync::Arc;
 use super::Texture;
+#[derive(Debug)]
 pub struct ScaleTexture{
     tex1:Arc<dyn Texture>,
     tex2:Arc<dyn Texture>
 }
+impl ScaleTexture {
+    pub fn new(tex1: Arc<dyn Texture>, tex2: Arc<dyn Texture>) -> Self {
+        Self { tex1, tex2 }
+    }
+}
 impl Texture for ScaleTexture
 {
     fn  evaluate(&self,inter:&crate::pbrt_core::tool::InteractionCommon)->glam::Vec3{
         self.tex1.evaluate(inter)*self.tex2.evaluate(inter)        
     }
 }
-impl std::fmt::Debug for ScaleTexture{
-    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        unimplemented!()
-    }
-}
\ No newline at end of file
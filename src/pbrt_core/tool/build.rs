@@ -11,8 +11,7 @@ pub struct Context {
 }
 impl Context {
     pub fn render(self) {
-        self.intergator
-            .render_process(&self.setting.name, &self.sence, self.setting.size)
+        self.intergator.render_process(&self.sence, &self.setting)
     }
     pub fn new(sence: Sence, intergator: Integrator, setting: Setting) -> Self {
         Self {
@@ -16,19 +16,21 @@ impl Film {
     pub fn block_size()->u32{
         Self::BLOCK_SIZE.x * Self::BLOCK_SIZE.y
     }
+    //瓦片总数，边缘不足BLOCK_SIZE的部分也算一块
     pub fn render_size(&self) -> u32 {
-        self.size() / Self::BLOCK_SIZE.x * Self::BLOCK_SIZE.y
+        self.max_index.0 * self.max_index.1
+    }
+    pub fn resolution(&self) -> UVec2 {
+        UVec2::new(self.size.0, self.size.1)
     }
     pub fn size(&self) -> u32 {
         self.size.0 * self.size.1
     }
     pub fn new(size: UVec2) -> Self {
-        assert!(size.x % Self::BLOCK_SIZE.x == 0);
-        assert!(size.y % Self::BLOCK_SIZE.y == 0);
-
+        assert!(size.x > 0 && size.y > 0);
         let (x_size, y_size) = (size.x, size.y);
-        let index_x = x_size / Self::BLOCK_SIZE.x;
-        let index_y = y_size / Self::BLOCK_SIZE.y;
+        let index_x = x_size.div_ceil(Self::BLOCK_SIZE.x);
+        let index_y = y_size.div_ceil(Self::BLOCK_SIZE.y);
         Self {
             size: (x_size, y_size),
             max_index: (index_x, index_y),
@@ -39,24 +41,17 @@ impl Film {
         let index = self
             .atom_count
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        // index=x*size.x+size.y;
-        //[64*64] -> [0,0,0],[0,1,1],[0,2,3],[0,3,4],[1,0,5],[1,1,6],[1,2,7][1,3,8];
-        if index > self.max_index.0 * self.max_index.1 {
+        //[x_index,y_index]按列优先编号，边缘瓦片裁剪到图像范围
+        if index >= self.render_size() {
             return None;
         }
-        let x_index = index / self.max_index.0;
-        let y_index = index % self.max_index.0;
+        let x_index = index / self.max_index.1;
+        let y_index = index % self.max_index.1;
         let left_up = (x_index * (Self::BLOCK_SIZE.x), y_index * (Self::BLOCK_SIZE.y));
         let right_down = (
-            (x_index+1) * (Self::BLOCK_SIZE.x) ,
-            (y_index+1) * (Self::BLOCK_SIZE.y) ,
+            ((x_index + 1) * (Self::BLOCK_SIZE.x)).min(self.size.0),
+            ((y_index + 1) * (Self::BLOCK_SIZE.y)).min(self.size.1),
         );
-        if left_up.0 >= self.size.0 {
-            return None;
-        }
-        if left_up.1 >= self.size.1 {
-            return None;
-        }
         Some(FilmIter::new(left_up, right_down, (x_index, y_index)))
     }
 }
@@ -14,7 +14,7 @@ pub fn vec3_coordinate_system(v1: Vec3, v2: &mut Vec3, v3: &mut Vec3) {
     if v1.x.abs() > v1.y.abs() {
         *v2 = Vec3::new(-v1.z, 0.0, v1.x) / (v1.x * v1.x + v1.z * v1.z).sqrt();
     } else {
-        *v2 = Vec3::new(0.0, v1.z, -v1.y) / (v1.y * v1.y + v1.z + v1.z).sqrt();
+        *v2 = Vec3::new(0.0, v1.z, -v1.y) / (v1.y * v1.y + v1.z * v1.z).sqrt();
     }
     *v3 = v1.cross(*v2);
 }
@@ -183,6 +183,14 @@ pub fn compute_d2(
 pub fn lerp(t: f32, a: f32, b: f32) -> f32 {
     a + (b - a) * t
 }
+//在[a,b]区间内平滑过渡
+pub fn smoothstep(x: f32, a: f32, b: f32) -> f32 {
+    if a == b {
+        return if x < a { 0.0 } else { 1.0 };
+    }
+    let t = ((x - a) / (b - a)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
 pub fn unifrom_sample_sphere(u: Vec2) -> Vec3 {
     let phi = 2.0 * PI * u.x;
     let theta = (2.0 * u.y - 1.0).acos();
@@ -5,8 +5,10 @@ use std::{
 };
 
 use glam::{u32::UVec2, Vec2, Vec3, Vec4};
-use gltf::image::Data;
+use gltf::image::{Data, Format};
 use image::DynamicImage;
+
+use super::tonemap::srgb_to_linear;
 #[derive(Default, Clone)]
 pub struct MipMap {
     //图像大小
@@ -14,9 +16,13 @@ pub struct MipMap {
 
     mapping: Box<HashMap<Level, Vec<Vec<Pixel>>>>,
 }
+//只输出分辨率与层数，不打印像素
 impl Debug for MipMap {
-    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        unimplemented!()
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MipMap")
+            .field("resolution", &self.resolution)
+            .field("levels", &self.mapping.len())
+            .finish()
     }
 }
 #[derive(Default)]
@@ -26,41 +32,113 @@ pub struct ImageData {
     pixels: Vec<Vec<Pixel>>,
 }
 impl ImageData {
+    //8位与16位按位宽归一化，浮点图像保留原始的高动态范围
     pub fn new_dynimage(image: DynamicImage) -> Self {
-        let mut image_data = ImageData::default();
-        image_data.pixels = vec![];
-        image_data.width = image.width();
-        image_data.height = image.height();
+        let (width, height) = (image.width(), image.height());
         match image {
             DynamicImage::ImageRgb8(image) => {
-                for i in 0..image_data.width {
-                    let mut vec = vec![];
-                    for j in 0..image_data.height {
-                        let pixel = image.get_pixel(i, j);
-                        vec.push(Pixel::new(pixel.0));
-                    }
-                    image_data.pixels.push(vec);
-                }
+                Self::from_fn(width, height, |i, j| Pixel::new(image.get_pixel(i, j).0))
             }
-            DynamicImage::ImageRgba8(image) => {
-                for i in 0..image_data.width {
-                    let mut vec = vec![];
-                    for j in 0..image_data.height {
-                        let pixel = image.get_pixel(i, j);
-                        vec.push(Pixel::from_sclie(&pixel.0));
-                    }
-                    image_data.pixels.push(vec);
-                }
+            DynamicImage::ImageRgba8(image) => Self::from_fn(width, height, |i, j| {
+                Pixel::from_sclie(&image.get_pixel(i, j).0)
+            }),
+            DynamicImage::ImageRgb16(image) => Self::from_fn(width, height, |i, j| {
+                let [r, g, b] = image.get_pixel(i, j).0;
+                Pixel::from(
+                    Vec4::new(r as f32, g as f32, b as f32, u16::MAX as f32) / u16::MAX as f32,
+                )
+            }),
+            DynamicImage::ImageRgba16(image) => Self::from_fn(width, height, |i, j| {
+                let [r, g, b, a] = image.get_pixel(i, j).0;
+                Pixel::from(Vec4::new(r as f32, g as f32, b as f32, a as f32) / u16::MAX as f32)
+            }),
+            DynamicImage::ImageRgb32F(image) => Self::from_fn(width, height, |i, j| {
+                Pixel::from(Vec3::from_array(image.get_pixel(i, j).0).extend(1.0))
+            }),
+            DynamicImage::ImageRgba32F(image) => Self::from_fn(width, height, |i, j| {
+                Pixel::from(Vec4::from_array(image.get_pixel(i, j).0))
+            }),
+            //灰度等其他格式统一转换为浮点RGBA
+            image => {
+                let image = image.into_rgba32f();
+                Self::from_fn(width, height, |i, j| {
+                    Pixel::from(Vec4::from_array(image.get_pixel(i, j).0))
+                })
             }
-            DynamicImage::ImageRgb16(_image) => {
-                unimplemented!()
-            }
-            _ => todo!(),
         }
-        image_data
     }
-    pub fn new(_data: &Data) -> Self {
-        unimplemented!()
+    //按列存储像素，pixels[x][y]
+    fn from_fn(width: u32, height: u32, f: impl Fn(u32, u32) -> Pixel) -> Self {
+        let pixels = (0..width)
+            .map(|i| (0..height).map(|j| f(i, j)).collect())
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+    //解码glTF图像数据，按格式换算到[0,1]浮点，srgb为真时颜色通道解码到线性空间
+    pub fn new(data: &Data, srgb: bool) -> Self {
+        let (channels, size) = match data.format {
+            Format::R8 => (1, 1),
+            Format::R8G8 => (2, 1),
+            Format::R8G8B8 => (3, 1),
+            Format::R8G8B8A8 => (4, 1),
+            Format::R16 => (1, 2),
+            Format::R16G16 => (2, 2),
+            Format::R16G16B16 => (3, 2),
+            Format::R16G16B16A16 => (4, 2),
+            Format::R32G32B32FLOAT => (3, 4),
+            Format::R32G32B32A32FLOAT => (4, 4),
+        };
+        //按通道位宽解码单个通道，不做颜色空间转换
+        let decode = |bytes: &[u8]| -> f32 {
+            match size {
+                1 => bytes[0] as f32 / 255.0,
+                2 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.0,
+                _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            }
+        };
+        //浮点图像本身就是线性的
+        let value = |bytes: &[u8]| -> f32 {
+            if srgb && size < 4 {
+                srgb_to_linear(decode(bytes))
+            } else {
+                decode(bytes)
+            }
+        };
+        let stride = channels * size;
+        let mut pixels = vec![];
+        for i in 0..data.width {
+            let mut vec = vec![];
+            for j in 0..data.height {
+                let offset = (j * data.width + i) as usize * stride;
+                let texel = &data.pixels[offset..offset + stride];
+                let r = value(&texel[0..size]);
+                //单通道图像视为灰度
+                let (g, b) = match channels {
+                    1 => (r, r),
+                    2 => (value(&texel[size..2 * size]), 0.0),
+                    _ => (
+                        value(&texel[size..2 * size]),
+                        value(&texel[2 * size..3 * size]),
+                    ),
+                };
+                let a = if channels == 4 {
+                    decode(&texel[3 * size..4 * size])
+                } else {
+                    1.0
+                };
+                vec.push(Pixel::from(Vec4::new(r, g, b, a)));
+            }
+            pixels.push(vec);
+        }
+        Self {
+            width: data.width,
+            height: data.height,
+            pixels,
+        }
     }
 }
 
@@ -90,7 +168,7 @@ impl Pixel {
             x: arr[0] as f32 / 255.0,
             y: arr[1] as f32 / 255.0,
             z: arr[2] as f32 / 255.0,
-            w: 255.0,
+            w: 1.0,
         }
     }
 }
@@ -169,13 +247,16 @@ impl MipMap {
         mipmap.mapping = data;
         mipmap
     }
+    pub fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
     pub fn lookup(&self, uv: Vec2, _duvdx: Vec2, _duvdy: Vec2) -> Vec3 {
         // let x_level = duvdx.x.max(duvdy.x).sqrt().log2().floor() as usize;
         // let y_level = duvdx.x.max(duvdy.x).sqrt().log2().floor() as usize;
         let level = Level { x: 0, y: 0 };
         let pixel = self.mapping.get(&level).expect("获取MipMap失败");
-        let x = uv.x * (self.resolution.x as f32 - 1.0);
-        let y = uv.y * (self.resolution.y as f32 - 1.0);
+        let x = uv.x.clamp(0.0, 1.0) * (self.resolution.x as f32 - 1.0);
+        let y = uv.y.clamp(0.0, 1.0) * (self.resolution.y as f32 - 1.0);
         let pixel = pixel.get(x as usize).unwrap().get(y as usize).unwrap();
         Vec4::from(*pixel).truncate()
     }
@@ -6,7 +6,15 @@ use glam::{Vec2, Vec3};
 
 use self::sence::Sence;
 
-use super::{bxdf::TransportMode, light::LightAble, material::BSDF, primitive::Primitive};
+use super::{
+    bssrdf::BSSRDF,
+    bxdf::TransportMode,
+    light::LightAble,
+    material::BSDF,
+    medium::MediumInterface,
+    primitive::Primitive,
+    sampler::Sampler,
+};
 
 pub mod build;
 pub mod color;
@@ -15,9 +23,11 @@ pub mod film;
 pub mod func;
 pub mod log;
 pub mod mipmap;
+pub mod pixel_filter;
 pub mod sence;
 pub mod setting;
 pub mod tile;
+pub mod tonemap;
 /// 光线
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Ray {
@@ -189,6 +199,10 @@ pub struct SurfaceInteraction<'a> {
     pub bsdf: Option<BSDF>,
     //该交点是不是光源。
     pub light: Option<&'a dyn LightAble>,
+    //表面两侧的介质，只在处理介质时设置
+    pub medium_interface: Option<MediumInterface>,
+    //次表面散射
+    pub bssrdf: Option<BSSRDF>,
 }
 impl<'a> SurfaceInteraction<'a> {
     pub fn new(
@@ -203,18 +217,53 @@ impl<'a> SurfaceInteraction<'a> {
             shading,
             bsdf: None,
             light,
+            medium_interface: None,
+            bssrdf: None,
         }
     }
-    pub fn compute_scattering(&mut self, _ray: RayDiff, _mode: TransportMode) {
+    //表面不是介质边界时，两侧都取光线所在的介质
+    pub fn set_medium(&mut self, medium: Option<usize>) {
+        let interface = self
+            .shape
+            .and_then(|shape| shape.medium_interface())
+            .filter(|interface| interface.is_transition())
+            .unwrap_or(MediumInterface::new(medium, medium));
+        self.medium_interface = Some(interface);
+    }
+    //沿w离开表面时所在的介质
+    pub fn get_medium(&self, w: &Vec3) -> Option<usize> {
+        let interface = self.medium_interface?;
+        if w.dot(self.common.normal) > 0.0 {
+            interface.outside
+        } else {
+            interface.inside
+        }
+    }
+    pub fn compute_scattering(&mut self, _ray: RayDiff, mode: TransportMode) {
         if let Some(shape) = self.shape {
-            shape.compute_scattering(self, TransportMode::Importance);
+            shape.compute_scattering(self, mode);
         }
     }
+    //沿法线偏移起点，避免折射光线与自身表面再次相交
     #[inline]
     pub fn spawn_ray(&self, wi: &Vec3) -> RayDiff {
-        let ray = Ray::new(self.common.p, *wi);
+        let n = self.common.normal;
+        let origin = if n.is_finite() {
+            let sign = if n.dot(*wi) < 0.0 { -1.0 } else { 1.0 };
+            self.common.p + n * sign * Visibility::DET
+        } else {
+            self.common.p
+        };
+        let ray = Ray::new(origin, *wi);
         RayDiff::new(ray)
     }
+    //交点所在表面的自发光
+    pub fn emission(&self) -> Vec3 {
+        match self.shape {
+            Some(shape) => shape.emission(&self.common),
+            None => Vec3::ZERO,
+        }
+    }
     pub fn le(&self, ray: RayDiff) -> Vec3 {
         if let Some(light) = self.light {
             light.le(&ray)
@@ -266,11 +315,23 @@ impl Visibility {
             self.b.p+sign* self.b.normal*Self::DET
         };
         let dir=a-b;
+        //方向归一化，t即为距离
         let ray_diff = RayDiff::new(
-            Ray::from_with_t(b, dir,0.0001,dir.length()-0.0001)
+            Ray::from_with_t(b, dir.normalize(),0.0001,dir.length()-0.0001)
         );
         !sence.hit_p(&ray_diff)
     }
+    //b处沿a方向所在介质为medium，穿过无材质的介质边界，被不透明表面遮挡时为零
+    pub fn tr(&self, sence: &Sence, medium: Option<usize>, sampler: &mut Sampler) -> Vec3 {
+        let a = self.a.p + self.a.normal * Self::DET * self.a.normal.dot(self.b.p - self.a.p).signum();
+        let b = self.b.p + self.b.normal * Self::DET * self.b.normal.dot(self.a.p - self.b.p).signum();
+        let dir = a - b;
+        let ray = RayDiff::new(Ray::from_with_t(b, dir.normalize(), 0.0001, dir.length() - 0.0001));
+        match sence.intersect_tr(ray, medium, sampler) {
+            (None, tr) => tr,
+            (Some(_), _) => Vec3::ZERO,
+        }
+    }
     #[inline]
     pub fn g(&self, sence: &Sence) -> f32 {
         let vis = if self.is_vis(sence) { 1.0 } else { 0.0 };
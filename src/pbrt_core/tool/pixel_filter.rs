@@ -0,0 +1,81 @@
+use std::f32::consts::PI;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+//像素重建滤波器，半径以像素为单位
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "mode")]
+pub enum PixelFilter {
+    Box { radius: f32 },
+    Triangle { radius: f32 },
+    Gaussian { radius: f32, alpha: f32 },
+    Mitchell { radius: f32, b: f32, c: f32 },
+    Lanczos { radius: f32, tau: f32 },
+}
+impl Default for PixelFilter {
+    //与原先逐像素求和等价
+    fn default() -> Self {
+        Self::Box { radius: 0.5 }
+    }
+}
+impl PixelFilter {
+    pub fn radius(&self) -> f32 {
+        match *self {
+            PixelFilter::Box { radius }
+            | PixelFilter::Triangle { radius }
+            | PixelFilter::Gaussian { radius, .. }
+            | PixelFilter::Mitchell { radius, .. }
+            | PixelFilter::Lanczos { radius, .. } => radius,
+        }
+    }
+    //p为相对像素中心的偏移
+    pub fn evaluate(&self, p: Vec2) -> f32 {
+        let radius = self.radius();
+        if p.x.abs() > radius || p.y.abs() > radius {
+            return 0.0;
+        }
+        self.evaluate_1d(p.x) * self.evaluate_1d(p.y)
+    }
+    //各滤波器均可分离
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match *self {
+            PixelFilter::Box { .. } => 1.0,
+            PixelFilter::Triangle { radius } => (radius - x).max(0.0),
+            PixelFilter::Gaussian { radius, alpha } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            PixelFilter::Mitchell { radius, b, c } => mitchell_1d(2.0 * x / radius, b, c),
+            PixelFilter::Lanczos { radius, tau } => windowed_sinc(x, radius, tau),
+        }
+    }
+}
+fn mitchell_1d(x: f32, b: f32, c: f32) -> f32 {
+    if x > 1.0 {
+        ((-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b))
+            / 6.0
+    }
+}
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+fn windowed_sinc(x: f32, radius: f32, tau: f32) -> f32 {
+    if x > radius {
+        0.0
+    } else {
+        sinc(x) * sinc(x / tau)
+    }
+}
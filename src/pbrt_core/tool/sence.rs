@@ -3,18 +3,23 @@ use std::fmt::Debug;
 
 use crate::pbrt_core::light::{Light, LightAble};
 use crate::pbrt_core::{
+    bxdf::TransportMode,
     camera::Camera,
+    medium::{Medium, MediumAble},
     primitive::{bvh::BVH, Aggregate, GeometricePrimitive, Primitive},
+    sampler::Sampler,
 };
 
 use super::color::Color;
-use super::{Bound, RayDiff};
+use super::{Bound, Ray, RayDiff, SurfaceInteraction};
 
 pub struct Sence {
     shape: &'static [Box<dyn Primitive>],
     pub camera: Camera,
     pub light: &'static [Light],
     pub env: Vec<&'static Light>,
+    //参与介质，图元与相机按下标引用
+    pub media: Vec<Medium>,
     bound: Bound<3>,
     // material: Vec<Box<dyn Material>>,
     accel: Option<Box<dyn Aggregate>>,
@@ -23,8 +28,16 @@ pub struct Sence {
 unsafe impl Sync for Sence {}
 
 impl Sence {
-    pub fn new(primitive: Vec<Box<dyn Primitive>>, camera: Camera, light: Vec<Light>) -> Self {
+    pub fn new(primitive: Vec<Box<dyn Primitive>>, camera: Camera, mut light: Vec<Light>) -> Self {
         let primitive = primitive.leak();
+        //光源预处理只依赖几何体包围盒
+        let geometry_bound = primitive
+            .iter()
+            .map(|ele| ele.world_bound())
+            .fold(Bound::<3>::default(), |a, b| a.merage(b));
+        light
+            .iter_mut()
+            .for_each(|item| item.preprocess(&geometry_bound));
 
         //场景集合
         let light = light.leak();
@@ -63,6 +76,7 @@ impl Sence {
             bound,
             light,
             env,
+            media: vec![],
             accel: Some(accel),
         }
     }
@@ -79,6 +93,38 @@ impl Sence {
         }
         ans
     }
+    //光线起点所在介质为medium，穿过无材质的介质边界并累积透射率，返回第一个不透明交点
+    pub fn intersect_tr(
+        &self,
+        mut ray: RayDiff,
+        mut medium: Option<usize>,
+        sampler: &mut Sampler,
+    ) -> (Option<SurfaceInteraction<'_>>, Color) {
+        let mut tr = Color::ONE;
+        loop {
+            let hit = self.interacect(ray);
+            let t = hit.as_ref().map_or(ray.o.t_max, |item| item.common.time);
+            if let Some(medium) = medium.and_then(|index| self.media.get(index)) {
+                tr *= medium.tr(&Ray { t_max: t, ..ray.o }, sampler);
+            }
+            let Some(mut item) = hit else {
+                return (None, tr);
+            };
+            item.compute_scattering(ray, TransportMode::Radiance);
+            if item.bsdf.is_some() || item.light.is_some() {
+                return (Some(item), tr);
+            }
+            if tr.max_element() <= 0.0 {
+                return (None, Color::ZERO);
+            }
+            item.set_medium(medium);
+            medium = item.get_medium(&ray.o.dir);
+            //剩余的距离，起点已沿法线偏移
+            let t_max = ray.o.t_max - t;
+            ray = item.spawn_ray(&ray.o.dir);
+            ray.o.t_max = t_max;
+        }
+    }
 }
 
 impl Debug for Sence {
@@ -1,8 +1,60 @@
 use glam::UVec2;
+use serde::{Deserialize, Serialize};
 
+use super::{pixel_filter::PixelFilter, tonemap::ToneMap};
 
+//图像输出格式，Exr与Pfm保存线性辐射度
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Exr,
+    Pfm,
+}
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Exr => "exr",
+            OutputFormat::Pfm => "pfm",
+        }
+    }
+    pub fn default_output() -> Vec<Self> {
+        vec![Self::Png]
+    }
+}
 
+//渐进式渲染，每轮结束写出中间图像与检查点
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Progressive {
+    //每轮的样本数，0表示一轮渲染完全部样本
+    pub pass_spp: usize,
+    //检查点文件，保存累积的浮点图像与已完成的样本数
+    pub checkpoint: Option<String>,
+    //检查点存在时从中继续渲染
+    pub resume: bool,
+}
 
+//自适应采样：达到min_spp后只对相对误差大于threshold的像素继续采样，上限为积分器的sample_num
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Adaptive {
+    pub enable: bool,
+    pub threshold: f32,
+    pub min_spp: usize,
+}
+impl Default for Adaptive {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            threshold: 0.05,
+            min_spp: 16,
+        }
+    }
+}
 
 pub struct Setting {
     pub core_num: u64,
@@ -11,6 +63,12 @@ pub struct Setting {
     pub sample_num: u64,
     pub path: String,
     pub inter_mode: String,
+    //同时输出的图像格式
+    pub output: Vec<OutputFormat>,
+    pub tone_map: ToneMap,
+    pub filter: PixelFilter,
+    pub progressive: Progressive,
+    pub adaptive: Adaptive,
 }
 impl Setting {
     pub fn new(core: usize, name: String, size: UVec2, inter_mode: String) -> Self {
@@ -21,6 +79,11 @@ impl Setting {
             sample_num: 1,
             path: "".to_owned(),
             inter_mode,
+            output: OutputFormat::default_output(),
+            tone_map: ToneMap::default(),
+            filter: PixelFilter::default(),
+            progressive: Progressive::default(),
+            adaptive: Adaptive::default(),
         }
     }
 }
\ No newline at end of file
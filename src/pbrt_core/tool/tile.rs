@@ -1,58 +1,274 @@
-use glam::UVec2;
-use image::{ImageFormat, Rgb, RgbImage};
-use log::info;
-use std::path::Path;
+use anyhow::Result;
+use glam::{UVec2, Vec2, Vec3};
+use image::{GrayImage, ImageFormat, Rgb32FImage, RgbImage};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
 
-use super::{color::Color, film::Film};
+use super::{color::Color, pixel_filter::PixelFilter, setting::OutputFormat, tonemap::ToneMap};
 
+//像素亮度的在线均值与方差(Welford)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PixelStat {
+    pub n: u32,
+    pub mean: f32,
+    pub m2: f32,
+}
+impl PixelStat {
+    pub fn add(&mut self, x: f32) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f32;
+        self.m2 += delta * (x - self.mean);
+    }
+    //合并两组统计(Chan)
+    pub fn merge(&mut self, other: &PixelStat) {
+        if other.n == 0 {
+            return;
+        }
+        let n = self.n + other.n;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.n as f32 / n as f32;
+        self.m2 += other.m2 + delta * delta * self.n as f32 * other.n as f32 / n as f32;
+        self.n = n;
+    }
+    pub fn variance(&self) -> f32 {
+        if self.n < 2 {
+            0.0
+        } else {
+            self.m2 / (self.n - 1) as f32
+        }
+    }
+    //均值的相对标准误差，样本不足时视为无穷大
+    pub fn relative_error(&self) -> f32 {
+        if self.n < 2 {
+            return f32::INFINITY;
+        }
+        (self.variance() / self.n as f32).sqrt() / self.mean.max(1e-3)
+    }
+}
 ///
 /// 多线程合并
-/// 用于存储渲染数据
+/// 用于存储渲染数据，范围包含滤波半径覆盖到的相邻像素
 pub struct Tile {
     buffer: Vec<Color>,
+    weight: Vec<f32>,
+    //只统计瓦片本身像素的样本，不含滤波扩展的部分
+    stat: Vec<PixelStat>,
+    //光线追踪泼溅到任意像素的贡献，按下标记录
+    splat: Vec<(u32, Color)>,
     index: (u32, u32),
+    left_up: UVec2,
+    right_down: UVec2,
 }
 unsafe impl Send for Tile {}
 pub struct Buffer {
-    buffer: Vec<Tile>,
+    buffer: Vec<Color>,
+    weight: Vec<f32>,
+    stat: Vec<PixelStat>,
+    splat: Vec<Color>,
     width: u32,
     height: u32,
 }
 impl Buffer {
+    const CHECKPOINT_MAGIC: &'static [u8; 8] = b"RAYCKPT3";
+    const CHECKPOINT_PIXEL: usize = 40;
     pub fn new(size: UVec2) -> Self {
+        let len = (size.x * size.y) as usize;
         Self {
-            buffer: vec![],
+            buffer: vec![Color::ZERO; len],
+            weight: vec![0.0; len],
+            stat: vec![PixelStat::default(); len],
+            splat: vec![Color::ZERO; len],
             width: size.x,
             height: size.y,
         }
     }
-    pub fn write(self, format: ImageFormat, ssp: f32, name: impl AsRef<Path>) {
-        let mut rbg_buffer = RgbImage::new(self.width, self.height);
-        for (_, tile) in self.buffer.into_iter().enumerate() {
-            let x_start = tile.index.0 * Film::BLOCK_SIZE.x;
-            let y_start = tile.index.1 * Film::BLOCK_SIZE.y;
-            for i in 0..Film::BLOCK_SIZE.x {
-                let x = x_start + i;
-                for j in 0..Film::BLOCK_SIZE.y {
-                    let y = y_start + j;
-                    let index = j + i * Film::BLOCK_SIZE.x;
-                    info!("{} {}", x_start, y_start);
-                    let color = unsafe { tile.buffer.get_unchecked(index as usize) };
-                    rbg_buffer.put_pixel(x, y, Self::to_color(*color, ssp))
+    //由已经求得的像素值构造，每个像素权重为1
+    pub fn from_pixels(size: UVec2, pixels: Vec<Color>) -> Self {
+        let mut buffer = Self::new(size);
+        buffer.weight.fill(1.0);
+        buffer.buffer = pixels;
+        buffer
+    }
+    //累加瓦片，边界处的像素由相邻瓦片共同贡献
+    pub fn merge(&mut self, tile: &Tile) {
+        let width = tile.right_down.x - tile.left_up.x;
+        for (i, (color, weight)) in tile.buffer.iter().zip(tile.weight.iter()).enumerate() {
+            let x = tile.left_up.x + i as u32 % width;
+            let y = tile.left_up.y + i as u32 / width;
+            if x < self.width && y < self.height {
+                let index = (y * self.width + x) as usize;
+                self.buffer[index] += *color;
+                self.weight[index] += *weight;
+                self.stat[index].merge(&tile.stat[i]);
+            }
+        }
+        for (index, color) in tile.splat.iter() {
+            self.splat[*index as usize] += *color;
+        }
+    }
+    //按瓦片下标排序后累加，结果与线程数和调度顺序无关
+    pub fn merge_tiles(&mut self, list: Vec<Vec<Tile>>) {
+        let mut list = list.into_iter().flatten().collect::<Vec<_>>();
+        list.sort();
+        for tile in list.iter() {
+            self.merge(tile);
+        }
+    }
+    pub fn size(&self) -> UVec2 {
+        UVec2::new(self.width, self.height)
+    }
+    //检查点：魔数、宽高、样本数，随后逐像素为颜色、权重、亮度统计与泼溅，均为小端
+    pub fn save_checkpoint(&self, spp: usize, name: impl AsRef<Path>) -> Result<()> {
+        let name = name.as_ref();
+        let tmp = name.with_extension("tmp");
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        file.write_all(Self::CHECKPOINT_MAGIC)?;
+        file.write_all(&self.width.to_le_bytes())?;
+        file.write_all(&self.height.to_le_bytes())?;
+        file.write_all(&(spp as u64).to_le_bytes())?;
+        for (((color, weight), stat), splat) in self
+            .buffer
+            .iter()
+            .zip(self.weight.iter())
+            .zip(self.stat.iter())
+            .zip(self.splat.iter())
+        {
+            for value in color.to_array() {
+                file.write_all(&value.to_le_bytes())?;
+            }
+            file.write_all(&weight.to_le_bytes())?;
+            file.write_all(&stat.n.to_le_bytes())?;
+            file.write_all(&stat.mean.to_le_bytes())?;
+            file.write_all(&stat.m2.to_le_bytes())?;
+            for value in splat.to_array() {
+                file.write_all(&value.to_le_bytes())?;
+            }
+        }
+        file.into_inner()?.sync_all()?;
+        //先写临时文件再替换，中断时不会损坏已有检查点
+        std::fs::rename(tmp, name)?;
+        Ok(())
+    }
+    pub fn load_checkpoint(name: impl AsRef<Path>) -> Result<(Self, usize)> {
+        let data = std::fs::read(name)?;
+        let magic = Self::CHECKPOINT_MAGIC.len();
+        if data.len() < magic + 16 || &data[..magic] != Self::CHECKPOINT_MAGIC {
+            anyhow::bail!("不是有效的检查点文件");
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+        let (width, height) = (u32_at(magic), u32_at(magic + 4));
+        let spp = u64::from_le_bytes(data[magic + 8..magic + 16].try_into().unwrap());
+        let values = &data[magic + 16..];
+        if values.len() != (width * height) as usize * Self::CHECKPOINT_PIXEL {
+            anyhow::bail!("检查点数据长度不匹配");
+        }
+        let mut buffer = Self::new(UVec2::new(width, height));
+        for (i, pixel) in values.chunks(Self::CHECKPOINT_PIXEL).enumerate() {
+            let bytes = |j: usize| pixel[j * 4..j * 4 + 4].try_into().unwrap();
+            let f = |j: usize| f32::from_le_bytes(bytes(j));
+            buffer.buffer[i] = Color::new(f(0), f(1), f(2));
+            buffer.weight[i] = f(3);
+            buffer.stat[i] = PixelStat {
+                n: u32::from_le_bytes(bytes(4)),
+                mean: f(5),
+                m2: f(6),
+            };
+            buffer.splat[i] = Color::new(f(7), f(8), f(9));
+        }
+        Ok((buffer, spp as usize))
+    }
+    //相对误差仍大于阈值的像素
+    pub fn active_pixels(&self, threshold: f32) -> Vec<bool> {
+        self.stat
+            .iter()
+            .map(|stat| stat.relative_error() > threshold)
+            .collect()
+    }
+    //每个像素的样本数，按max_spp归一化为灰度图
+    pub fn write_sample_map(&self, max_spp: usize, name: impl AsRef<Path>) -> Result<()> {
+        let scale = 255.0 / max_spp.max(1) as f32;
+        let data = self
+            .stat
+            .iter()
+            .map(|stat| (stat.n as f32 * scale).clamp(0.0, 255.0) as u8)
+            .collect();
+        let image = GrayImage::from_raw(self.width, self.height, data)
+            .ok_or_else(|| anyhow::anyhow!("图像大小不匹配"))?;
+        image.save_with_format(name, ImageFormat::Png)?;
+        Ok(())
+    }
+    //按行展开为加权平均后的线性辐射度
+    pub fn pixels(&self) -> Vec<Color> {
+        //每个相机样本对应一条光路径，泼溅按平均每像素样本数归一化
+        let samples = self.stat.iter().map(|stat| stat.n as f32).sum::<f32>();
+        let splat_scale = if samples > 0.0 {
+            self.splat.len() as f32 / samples
+        } else {
+            0.0
+        };
+        self.buffer
+            .iter()
+            .zip(self.weight.iter())
+            .zip(self.splat.iter())
+            .map(|((color, weight), splat)| {
+                let color = if *weight != 0.0 {
+                    *color / *weight
+                } else {
+                    Color::ZERO
+                };
+                (color + *splat * splat_scale).max(Color::ZERO)
+            })
+            .collect()
+    }
+    pub fn write(
+        &self,
+        format: OutputFormat,
+        tone_map: &ToneMap,
+        name: impl AsRef<Path>,
+    ) -> Result<()> {
+        let pixels = self.pixels();
+        match format {
+            OutputFormat::Png | OutputFormat::Jpeg => {
+                let mut rbg_buffer = RgbImage::new(self.width, self.height);
+                for (index, color) in pixels.iter().enumerate() {
+                    let (x, y) = (index as u32 % self.width, index as u32 / self.width);
+                    rbg_buffer.put_pixel(x, y, tone_map.to_color(*color))
                 }
+                let format = if format == OutputFormat::Png {
+                    ImageFormat::Png
+                } else {
+                    ImageFormat::Jpeg
+                };
+                rbg_buffer.save_with_format(name, format)?;
             }
+            OutputFormat::Exr => {
+                let data = pixels.iter().flat_map(|x| x.to_array()).collect();
+                let image = Rgb32FImage::from_raw(self.width, self.height, data)
+                    .ok_or_else(|| anyhow::anyhow!("图像大小不匹配"))?;
+                image.save_with_format(name, ImageFormat::OpenExr)?;
+            }
+            OutputFormat::Pfm => Self::write_pfm(&pixels, self.width, self.height, name)?,
         }
-        let _ = rbg_buffer.save_with_format(name, format);
+        Ok(())
     }
-    pub fn to_color(color: Color, ssp: f32) -> Rgb<u8> {
-        let vec = (color / ssp).powf(0.5);
-        let rgb = vec * 255.0;
-        
-        Rgb([
-            rgb.x.clamp(0.0, 255.0) as u8,
-            rgb.y.clamp(0.0, 255.0) as u8,
-            rgb.z.clamp(0.0, 255.0) as u8,
-        ])
+    //PFM：文本头加小端浮点，行序自下而上
+    fn write_pfm(pixels: &[Color], width: u32, height: u32, name: impl AsRef<Path>) -> Result<()> {
+        let mut file = BufWriter::new(File::create(name)?);
+        write!(file, "PF\n{} {}\n-1.0\n", width, height)?;
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let color = pixels[(y * width + x) as usize];
+                for value in color.to_array() {
+                    file.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        file.flush()?;
+        Ok(())
     }
 }
 impl PartialEq for Tile {
@@ -72,23 +288,64 @@ impl Ord for Tile {
     }
 }
 impl Tile {
-    pub fn new(index: (u32, u32)) -> Self {
+    //left_up与right_down为瓦片本身的像素范围，按滤波半径向外扩展并裁剪到图像内
+    pub fn new(
+        index: (u32, u32),
+        left_up: UVec2,
+        right_down: UVec2,
+        radius: f32,
+        size: UVec2,
+    ) -> Self {
+        let expand = (radius - 0.5).ceil().max(0.0) as u32;
+        let left_up = UVec2::new(
+            left_up.x.saturating_sub(expand),
+            left_up.y.saturating_sub(expand),
+        );
+        let right_down = (right_down + expand).min(size);
+        let len = ((right_down.x - left_up.x) * (right_down.y - left_up.y)) as usize;
         Self {
-            buffer: vec![],
+            buffer: vec![Color::ZERO; len],
+            weight: vec![0.0; len],
+            stat: vec![PixelStat::default(); len],
+            splat: vec![],
             index,
+            left_up,
+            right_down,
         }
     }
-    pub fn push(&mut self, color: Color) {
-        self.buffer.push(color);
+    //记录样本亮度，用于估计像素的方差
+    pub fn add_stat(&mut self, pixel: UVec2, color: Color) {
+        let width = self.right_down.x - self.left_up.x;
+        let index = ((pixel.y - self.left_up.y) * width + pixel.x - self.left_up.x) as usize;
+        self.stat[index].add(color.dot(Vec3::new(0.212_671, 0.715_160, 0.072_169)));
+    }
+    //光线追踪的贡献直接累加到胶片点所在像素，不经过滤波器
+    pub fn add_splat(&mut self, film_point: Vec2, color: Color, size: UVec2) {
+        let pixel = film_point.floor().as_uvec2().min(size - 1);
+        self.splat.push((pixel.y * size.x + pixel.x, color));
+    }
+    //将胶片上的样本按滤波器权重泼溅到附近像素
+    pub fn add_sample(&mut self, film_point: Vec2, color: Color, filter: &PixelFilter) {
+        let radius = filter.radius();
+        let p = film_point - 0.5;
+        let min = (p - radius).ceil().max(self.left_up.as_vec2());
+        let max = (p + radius).floor().min(self.right_down.as_vec2() - 1.0);
+        if min.x > max.x || min.y > max.y {
+            return;
+        }
+        let width = self.right_down.x - self.left_up.x;
+        for y in min.y as u32..=max.y as u32 {
+            for x in min.x as u32..=max.x as u32 {
+                let weight = filter.evaluate(Vec2::new(x as f32, y as f32) - p);
+                let index = ((y - self.left_up.y) * width + x - self.left_up.x) as usize;
+                self.buffer[index] += color * weight;
+                self.weight[index] += weight;
+            }
+        }
     }
 }
 pub fn merage_tile(list: Vec<Vec<Tile>>, size: UVec2) -> Buffer {
-    let mut list = list
-        .into_iter()
-        .flat_map(|item| item.into_iter())
-        .collect::<Vec<_>>();
-    list.sort();
     let mut buffer = Buffer::new(size);
-    buffer.buffer = list;
+    buffer.merge_tiles(list);
     buffer
 }
@@ -0,0 +1,87 @@
+use glam::Vec3;
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+
+use super::color::Color;
+
+//色调映射曲线
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ToneCurve {
+    //直接截断到[0,1]
+    #[default]
+    Linear,
+    Reinhard,
+    //ACES filmic 拟合曲线(Narkowicz)
+    Aces,
+}
+impl ToneCurve {
+    pub fn apply(&self, color: Color) -> Color {
+        let color = color.max(Vec3::ZERO);
+        match self {
+            ToneCurve::Linear => color,
+            ToneCurve::Reinhard => color / (Vec3::ONE + color),
+            ToneCurve::Aces => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                (color * (a * color + b)) / (color * (c * color + d) + e)
+            }
+        }
+    }
+}
+
+//LDR输出前的色调映射，只在写出Buffer时执行一次
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ToneMap {
+    pub curve: ToneCurve,
+    //曝光，单位为档(EV)
+    pub exposure: f32,
+    pub srgb: bool,
+}
+impl Default for ToneMap {
+    fn default() -> Self {
+        Self {
+            curve: ToneCurve::default(),
+            exposure: 0.0,
+            srgb: true,
+        }
+    }
+}
+impl ToneMap {
+    pub fn map(&self, color: Color) -> Color {
+        let color = self.curve.apply(color * 2.0_f32.powf(self.exposure));
+        let color = color.clamp(Vec3::ZERO, Vec3::ONE);
+        if self.srgb {
+            Vec3::new(
+                linear_to_srgb(color.x),
+                linear_to_srgb(color.y),
+                linear_to_srgb(color.z),
+            )
+        } else {
+            color
+        }
+    }
+    pub fn to_color(&self, color: Color) -> Rgb<u8> {
+        let rgb = self.map(color) * 255.0 + 0.5;
+        Rgb([
+            rgb.x.clamp(0.0, 255.0) as u8,
+            rgb.y.clamp(0.0, 255.0) as u8,
+            rgb.z.clamp(0.0, 255.0) as u8,
+        ])
+    }
+}
+//sRGB OETF
+pub fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+//sRGB EOTF，8位颜色贴图解码到线性空间
+pub fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
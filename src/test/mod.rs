@@ -1,10 +1,45 @@
 #[cfg(test)]
 pub mod test {
-    use std::path::Path;
+    use std::{path::Path, sync::Arc};
 
-    use glam::Mat4;
+    use glam::{Mat4, Vec2, Vec3};
 
-    use crate::pbrt_core::load::objload::ObjLoad;
+    use crate::pbrt_core::bxdf::{
+        microfacet::MicrofacetTransmission,
+        microfacet_distribution::{DistributionType, TrowbridgeReitzDistribution},
+        disney::{DisneyClearcoat, DisneyDiffuse, DisneyFakeSS, DisneyRetro, DisneySheen},
+        reflection::LambertianReflection, specular::SpecularTransmission, BxDF, BxDFAble,
+        BxDFType, TransportMode,
+    };
+    use crate::pbrt_core::material::{
+        disney::DisneyMaterial, rough_glass::RoughGlassMaterial, Material,
+    };
+    use crate::pbrt_core::texture::{constant::ConstantTexture, Texture};
+    use crate::pbrt_core::integrator::{
+        path::PathIntegrator, power_heuristic, sppm::shrink_radius, IntegratorAble,
+    };
+    use crate::pbrt_core::bssrdf::{SubsurfaceAdapter, BSSRDF};
+    use crate::pbrt_core::camera::{Camera, CameraMode, CameraSample};
+    use crate::pbrt_core::medium::{
+        grid::{DensityGrid, GridDensityMedium, MajorantGrid},
+        homogeneous::HomogeneousMedium,
+        HenyeyGreenstein, MediumAble,
+    };
+    use crate::pbrt_core::sampler::{Sampler, SamplerAble, SamplerMode};
+    use crate::pbrt_core::light::{distant::DistantLight, spot::SpotLight, LightAble, LightType};
+    use crate::pbrt_core::load::{gltfload::GltfLoad, objload::ObjLoad, tomlload::TomlLoader};
+    use crate::pbrt_core::sampler::{
+        distribution_1d::Distribution1D, distribution_2d::Distribution2D,
+    };
+    use crate::pbrt_core::tool::{
+        film::Film,
+        mipmap::{ImageData, MipMap},
+        setting::OutputFormat,
+        pixel_filter::PixelFilter,
+        tile::{merage_tile, Buffer, PixelStat, Tile},
+        tonemap::{ToneCurve, ToneMap},
+        Bound, InteractionCommon, Ray, Shading, SurfaceInteraction, Visibility,
+    };
     
 
 
@@ -20,8 +55,776 @@ pub mod test {
         
     }
     #[test]
+    fn glass_refract() {
+        let bxdf = SpecularTransmission::new(Vec3::ONE, 1.0, 1.5, TransportMode::Importance);
+        let w_out = Vec3::new(0.6, 0.0, 0.8);
+        let mut w_in = Vec3::ZERO;
+        let mut pdf = 0.0;
+        let f = bxdf.sample_f(&mut w_in, &w_out, Vec2::ZERO, &mut pdf);
+        assert_eq!(pdf, 1.0);
+        assert!(w_in.z < 0.0);
+        //斯涅尔定律 sin_i * eta_i = sin_t * eta_t
+        assert!((w_out.x - w_in.x.abs() * 1.5).abs() < 1e-4);
+        assert!(f.x > 0.0 && f.x * w_in.z.abs() < 1.0);
+    }
+    #[test]
+    fn rough_glass_transmission() {
+        let glass = |mode| {
+            BxDF::MicrofacetTransmission(MicrofacetTransmission::new(
+                Vec3::ONE,
+                Box::new(TrowbridgeReitzDistribution::new(0.3, 0.3, false)),
+                1.0,
+                1.5,
+                mode,
+            ))
+        };
+        let (bxdf, radiance) = (glass(TransportMode::Importance), glass(TransportMode::Radiance));
+        let mut sampler = Sampler::default();
+        let n = 20000;
+        //从外侧与内侧入射
+        for (wo, eta) in [
+            (Vec3::new(0.3, 0.2, 0.9).normalize(), 1.5),
+            (Vec3::new(-0.2, 0.1, -0.8).normalize(), 1.0 / 1.5),
+        ] {
+            let mut energy = Vec3::ZERO;
+            for _ in 0..n {
+                let mut wi = Vec3::ZERO;
+                let mut pdf = 0.0;
+                let f = bxdf.sample_f(&wo, &mut wi, sampler.sample_2d_d(), &mut pdf);
+                if pdf <= 0.0 {
+                    continue;
+                }
+                //采样结果位于另一侧，且与直接求值一致
+                assert!(wo.z * wi.z < 0.0);
+                assert!((pdf - bxdf.pdf(&wo, &wi)).abs() < 1e-3 * pdf.max(1.0));
+                assert!(f.abs_diff_eq(bxdf.f(&wo, &wi), 1e-3 * f.x.max(1.0)));
+                //辐射度模式多出折射率平方的缩放
+                assert!((radiance.f(&wo, &wi) * eta * eta).abs_diff_eq(f, 1e-3 * f.x.max(1.0)));
+                energy += f * wi.z.abs() / pdf;
+            }
+            //透射部分不超过1减去菲涅尔反射
+            let energy = energy / n as f32;
+            assert!(energy.x > 0.8 && energy.x < 1.0, "{}", energy);
+        }
+    }
+    #[test]
+    fn rough_glass_bsdf_sides() {
+        let constant =
+            |x: f32| -> Arc<dyn Texture> { Arc::new(ConstantTexture::new(Vec3::splat(x))) };
+        let glass = RoughGlassMaterial::new(
+            constant(1.0),
+            constant(1.0),
+            constant(1.5),
+            constant(0.3),
+            DistributionType::TrowbridgeReitz,
+        );
+        let mut si = SurfaceInteraction {
+            shading: Shading::new(Vec3::X, Vec3::Y, Vec3::ZERO, Vec3::ZERO),
+            ..Default::default()
+        };
+        si.common.normal = Vec3::Z;
+        glass.compute_scattering_functions(&mut si, TransportMode::Importance);
+        let bsdf = si.bsdf.unwrap();
+        let all = BxDFType::All as u32;
+        let wo = Vec3::new(0.3, 0.2, 0.9).normalize();
+        let mut sampler = Sampler::default();
+        let n = 20000;
+        let mut energy = Vec3::ZERO;
+        for _ in 0..n {
+            //任意方向上pdf为0时f也为0，透射方向不混入反射分量
+            let u = sampler.sample_2d_d();
+            let z = 1.0 - 2.0 * u.x;
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let (sin, cos) = (2.0 * std::f32::consts::PI * u.y).sin_cos();
+            let wi = Vec3::new(r * cos, r * sin, z);
+            if bsdf.pdf(&wo, &wi, all) == 0.0 {
+                assert_eq!(bsdf.f(&wo, &wi, all), Vec3::ZERO, "{}", wi);
+            }
+            let (mut wi, mut pdf, mut sampled) = (Vec3::ZERO, 0.0, 0);
+            let f = bsdf.sample_f(&wo, &mut wi, sampler.sample_2d_d(), &mut pdf, all, &mut sampled);
+            if pdf <= 0.0 {
+                continue;
+            }
+            assert!((pdf - bsdf.pdf(&wo, &wi, all)).abs() < 1e-3 * pdf.max(1.0));
+            assert!(f.abs_diff_eq(bsdf.f(&wo, &wi, all), 1e-3 * f.max_element().max(1.0)));
+            energy += f * wi.z.abs() / pdf;
+        }
+        //反射与透射合计不超过1
+        let energy = energy / n as f32;
+        assert!(energy.x > 0.85 && energy.x < 1.02, "{}", energy);
+    }
+    #[test]
+    fn disney_white_furnace() {
+        let constant =
+            |x: f32| -> Arc<dyn Texture> { Arc::new(ConstantTexture::new(Vec3::splat(x))) };
+        let bsdf_of = |material: DisneyMaterial| {
+            let mut si = SurfaceInteraction {
+                shading: Shading::new(Vec3::X, Vec3::Y, Vec3::ZERO, Vec3::ZERO),
+                ..Default::default()
+            };
+            si.common.normal = Vec3::Z;
+            material.compute_scattering_functions(&mut si, TransportMode::Radiance);
+            si.bsdf.unwrap()
+        };
+        let furnace = |material: DisneyMaterial| {
+            let bsdf = bsdf_of(material);
+            let wo = Vec3::new(0.4, -0.3, 0.8).normalize();
+            let mut sampler = Sampler::default();
+            let n = 20000;
+            let mut energy = Vec3::ZERO;
+            for _ in 0..n {
+                let (mut wi, mut pdf, mut sampled) = (Vec3::ZERO, 0.0, 0);
+                let all = BxDFType::All as u32;
+                let u = sampler.sample_2d_d();
+                let f = bsdf.sample_f(&wo, &mut wi, u, &mut pdf, all, &mut sampled);
+                if pdf <= 0.0 {
+                    continue;
+                }
+                //混合后的pdf与f与直接求值一致
+                assert!((pdf - bsdf.pdf(&wo, &wi, all)).abs() < 1e-3 * pdf.max(1.0));
+                assert!(f.abs_diff_eq(bsdf.f(&wo, &wi, all), 1e-3 * f.max_element().max(1.0)));
+                energy += f * wi.z.abs() / pdf;
+            }
+            energy / n as f32
+        };
+        //白色金属不吸收，单次散射损失的能量有限
+        let mut metal = DisneyMaterial::new(constant(1.0), false);
+        metal.metallic = Some(constant(1.0));
+        metal.roughness = Some(constant(0.3));
+        let energy = furnace(metal);
+        assert!(energy.x > 0.9 && energy.x < 1.02, "{}", energy);
+        //白色电介质含漫反射、光泽、清漆与透射，总能量不应明显超过1
+        let mut dielectric = DisneyMaterial::new(constant(1.0), false);
+        dielectric.clearcoat = Some(constant(1.0));
+        dielectric.spec_trans = Some(constant(0.5));
+        let energy = furnace(dielectric);
+        assert!(energy.x > 0.7 && energy.x < 1.05, "{}", energy);
+        //反射分量在另一侧为0
+        let wo = Vec3::new(0.3, 0.1, 0.9).normalize();
+        let wt = Vec3::new(-0.2, 0.3, -0.9).normalize();
+        let lobes: [Box<dyn BxDFAble>; 5] = [
+            Box::new(DisneyDiffuse::new(Vec3::ONE)),
+            Box::new(DisneyRetro::new(Vec3::ONE, 0.5)),
+            Box::new(DisneyFakeSS::new(Vec3::ONE, 0.5)),
+            Box::new(DisneySheen::new(Vec3::ONE)),
+            Box::new(DisneyClearcoat::new(1.0, 0.1)),
+        ];
+        for lobe in &lobes {
+            assert_eq!(lobe.f(&wt, &wo), Vec3::ZERO);
+        }
+        //带透射的材质经BSDF求值时，透射方向不受光泽与清漆影响
+        let layered = |x: f32| {
+            let mut material = DisneyMaterial::new(constant(1.0), false);
+            material.sheen = Some(constant(x));
+            material.clearcoat = Some(constant(x));
+            material.spec_trans = Some(constant(0.5));
+            bsdf_of(material)
+        };
+        let (plain, coated) = (layered(0.0), layered(1.0));
+        let all = BxDFType::All as u32;
+        let f = plain.f(&wo, &wt, all);
+        assert!(f.max_element() > 0.0);
+        assert!(coated.f(&wo, &wt, all).abs_diff_eq(f, 1e-6));
+        //同侧的反射方向上清漆仍然生效
+        let wr = Vec3::new(-wo.x, -wo.y, wo.z);
+        assert!(coated.f(&wo, &wr, all).x > plain.f(&wo, &wr, all).x);
+    }
+    #[test]
     fn obj_load(){
         let path=Path::new("./object/box.obj");
         ObjLoad::load(path.display().to_string().as_str());
     }
+    #[test]
+    fn gltf_material_load() {
+        let (_, index, material) = GltfLoad::load("./object/cube/Cube.gltf").unwrap();
+        assert_eq!(material.materials.len(), 1);
+        assert_eq!(material.triangle.len(), index[0].len());
+        assert!(material.triangle.iter().all(|x| *x == Some(0)));
+        //带贴图的材质可以调试输出
+        assert!(format!("{:?}", material.materials[0]).contains("MipMap"));
+        //颜色贴图按sRGB解码，数据贴图保持线性，alpha不做转换
+        let data = gltf::image::Data {
+            pixels: vec![188, 0, 255, 128],
+            format: gltf::image::Format::R8G8B8A8,
+            width: 1,
+            height: 1,
+        };
+        let color = MipMap::new(ImageData::new(&data, true));
+        let color = color.lookup(Vec2::ZERO, Vec2::ZERO, Vec2::ZERO);
+        assert!(color.abs_diff_eq(Vec3::new(0.503, 0.0, 1.0), 1e-3));
+        let linear = MipMap::new(ImageData::new(&data, false));
+        let linear = linear.lookup(Vec2::ZERO, Vec2::ZERO, Vec2::ZERO);
+        assert!((linear.x - 188.0 / 255.0).abs() < 1e-6);
+    }
+    #[test]
+    fn gltf_node_hierarchy() {
+        //父节点TRS平移(10,0,0)缩放2，子节点矩阵平移(0,5,0)，网格含两个图元
+        let (mesh, index, material) = GltfLoad::load("./object/hierarchy.gltf").unwrap();
+        assert_eq!(mesh.point.len(), 6);
+        assert_eq!(index[0].len(), 2);
+        let (min, max) = mesh
+            .point
+            .iter()
+            .fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(min, max), p| {
+                (min.min(*p), max.max(*p))
+            });
+        assert!(min.abs_diff_eq(Vec3::new(10.0, 10.0, 0.0), 1e-5));
+        assert!(max.abs_diff_eq(Vec3::new(12.0, 12.0, 2.0), 1e-5));
+        //每个图元保留自己的材质，顶点下标按图元偏移
+        assert_eq!(material.materials.len(), 2);
+        assert_eq!(material.triangle, vec![Some(0), Some(1)]);
+        assert_eq!(index[0][1], glam::UVec3::new(3, 4, 5));
+        assert!(mesh.point[3..].iter().all(|p| (p.z - 2.0).abs() < 1e-5));
+    }
+    #[test]
+    fn gltf_camera_load() {
+        //正交相机位于(0,0,5)，朝向-z，视口半宽2半高1
+        let size = Vec2::new(200.0, 100.0);
+        let ray = |camera: &Camera, x: f32, y: f32| {
+            let mut sampler = Sampler::default();
+            let mut sample = CameraSample::new(0.0, 0.0, &mut sampler);
+            sample.film_point = Vec2::new(x, y);
+            camera.generate_ray(sample).o
+        };
+        let camera = GltfLoad::load_camera("./object/camera.gltf", size, Mat4::IDENTITY)
+            .unwrap()
+            .unwrap();
+        let center = ray(&camera, 100.0, 50.0);
+        assert!(center.dir.normalize().abs_diff_eq(Vec3::NEG_Z, 1e-4));
+        assert!(center.origin.truncate().abs_diff_eq(Vec2::ZERO, 1e-4));
+        let corner = ray(&camera, 0.0, 0.0);
+        assert!(corner.origin.truncate().abs().abs_diff_eq(Vec2::new(2.0, 1.0), 1e-4));
+        //物体变换同样作用于相机，缩放同时放大视口
+        let transform = Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            glam::Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            Vec3::new(1.0, 2.0, 3.0),
+        );
+        let camera = GltfLoad::load_camera("./object/camera.gltf", size, transform)
+            .unwrap()
+            .unwrap();
+        assert!(camera.position().abs_diff_eq(Vec3::new(11.0, 2.0, 3.0), 1e-4));
+        let center = ray(&camera, 100.0, 50.0);
+        assert!(center.dir.normalize().abs_diff_eq(Vec3::NEG_X, 1e-4));
+        let corner = ray(&camera, 0.0, 0.0);
+        let offset = (corner.origin - center.origin).abs();
+        assert!(offset.abs_diff_eq(Vec3::new(0.0, 2.0, 4.0), 1e-3));
+    }
+    #[test]
+    fn spot_falloff() {
+        //沿+z轴，光锥30度，20度开始衰减
+        let spot = SpotLight::new(Vec3::ONE, Mat4::IDENTITY, 30.0, 20.0, 0);
+        let at = |dir: Vec3| {
+            let common = InteractionCommon {
+                p: dir.normalize() * 2.0,
+                ..Default::default()
+            };
+            spot.li(&common, &Vec3::ZERO).x
+        };
+        assert!((at(Vec3::Z) - 0.25).abs() < 1e-5);
+        assert_eq!(at(Vec3::X), 0.0);
+        let edge = at(Vec3::new(25f32.to_radians().tan(), 0.0, 1.0));
+        assert!(edge > 0.0 && edge < 0.25);
+    }
+    #[test]
+    fn distant_light_bound() {
+        let dir = Vec3::new(1.0, -2.0, 0.5).normalize();
+        let mut light = DistantLight::new(Vec3::splat(3.0), dir, 0);
+        light.preprocess(&Bound::<3>::new(Vec3::new(-1.0, 0.0, -1.0), Vec3::new(3.0, 2.0, 1.0)));
+        assert!(LightType::is_delta(light.get_type()));
+        let surface = InteractionCommon {
+            p: Vec3::new(2.5, 0.2, -0.8),
+            ..Default::default()
+        };
+        let mut light_common = InteractionCommon::default();
+        let (mut wi, mut pdf, mut vis) = (Vec3::ZERO, 0.0, Visibility::default());
+        let li =
+            light.sample_li(&surface, &mut light_common, Vec2::ZERO, &mut wi, &mut pdf, &mut vis);
+        assert_eq!(li, Vec3::splat(3.0));
+        assert_eq!(pdf, 1.0);
+        assert!(wi.abs_diff_eq(dir, 1e-6));
+        //阴影光线逆着传播方向伸出场景包围球
+        let to_light = light_common.p - surface.p;
+        assert!(to_light.normalize().abs_diff_eq(-dir, 1e-5));
+        let radius = (Vec3::new(3.0, 2.0, 1.0) - Vec3::new(1.0, 1.0, 0.0)).length();
+        assert!((light_common.p - Vec3::new(1.0, 1.0, 0.0)).length() > radius);
+        assert!(vis.a.p.abs_diff_eq(light_common.p, 1e-6));
+        //发射光线从包围球外的圆盘出发，沿传播方向穿过场景
+        let (mut dir_le, mut pdf_pos, mut pdf_dir) = (Vec3::ZERO, 0.0, 0.0);
+        let le = light.sample_le(
+            Vec2::new(0.3, 0.8),
+            Vec2::ZERO,
+            &mut light_common,
+            &mut dir_le,
+            &mut pdf_pos,
+            &mut pdf_dir,
+        );
+        assert_eq!(le, Vec3::splat(3.0));
+        assert!(dir_le.abs_diff_eq(dir, 1e-6));
+        assert_eq!(pdf_dir, 1.0);
+        assert!((pdf_pos - 1.0 / (std::f32::consts::PI * radius * radius)).abs() < 1e-6);
+        let center = Vec3::new(1.0, 1.0, 0.0);
+        let offset = center - light_common.p;
+        assert!((offset.dot(dir) - radius).abs() < 1e-4);
+        assert!((offset - dir * radius).length() <= radius + 1e-4);
+        let (mut pos, mut pdf) = (0.0, 0.0);
+        light.pdf_le(&light_common, &dir, &mut pos, &mut pdf);
+        assert_eq!((pos, pdf), (pdf_pos, 0.0));
+    }
+    #[test]
+    fn distribution_sample() {
+        let d = Distribution1D::new(&[1.0, 3.0], 2);
+        let mut pdf = 0.0;
+        let mut off = 0;
+        let x = d.sample_continuous(0.5, &mut pdf, Some(&mut off));
+        assert_eq!(off, 1);
+        assert!((x - 2.0 / 3.0).abs() < 1e-5);
+        assert!((pdf - 1.5).abs() < 1e-5);
+        //二维分布采样点的pdf应与查询结果一致
+        let d = Distribution2D::new(&[1.0, 2.0, 3.0, 4.0, 0.0, 6.0], 3, 2);
+        for u in [Vec2::new(0.1, 0.2), Vec2::new(0.7, 0.9), Vec2::new(0.5, 0.5)] {
+            let p = d.sample_continuous(u, &mut pdf);
+            assert!(pdf > 0.0);
+            assert!((d.pdf(p) - pdf).abs() < 1e-4);
+        }
+    }
+    #[test]
+    fn hdr_image_range() {
+        //浮点图像不应被截断到[0,1]
+        let image = image::Rgb32FImage::from_pixel(4, 4, image::Rgb([4.0, 0.5, 16.0]));
+        let mipmap = MipMap::new(ImageData::new_dynimage(image.into()));
+        let color = mipmap.lookup(Vec2::splat(0.5), Vec2::ZERO, Vec2::ZERO);
+        assert!((color - Vec3::new(4.0, 0.5, 16.0)).length() < 1e-4);
+        let image =
+            image::ImageBuffer::<image::Rgb<u16>, _>::from_pixel(4, 4, image::Rgb([65535, 0, 32768]));
+        let mipmap = MipMap::new(ImageData::new_dynimage(image.into()));
+        let color = mipmap.lookup(Vec2::splat(0.5), Vec2::ZERO, Vec2::ZERO);
+        assert!((color - Vec3::new(1.0, 0.0, 0.5)).length() < 1e-3);
+    }
+    #[test]
+    fn pfm_output() {
+        let size = glam::UVec2::new(2, 3);
+        let mut tile = Tile::new((0, 0), glam::UVec2::ZERO, size, 0.5, size);
+        let filter = PixelFilter::default();
+        for x in 0..2 {
+            for y in 0..3 {
+                let p = Vec2::new(x as f32, y as f32) + 0.5;
+                tile.add_sample(p, Vec3::splat((x * 10 + y) as f32), &filter);
+            }
+        }
+        let buffer = merage_tile(vec![vec![tile]], size);
+        let path = std::env::temp_dir().join("ray_v2_pfm_output.pfm");
+        buffer.write(OutputFormat::Pfm, &Default::default(), &path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        let header = b"PF\n2 3\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        let values: Vec<f32> = data[header.len()..]
+            .chunks(4)
+            .map(|x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+            .collect();
+        assert_eq!(values.len(), 2 * 3 * 3);
+        //首行为图像最底行(y=2)
+        assert_eq!(values[0], 2.0);
+        assert_eq!(values[3], 12.0);
+    }
+    #[test]
+    fn filter_splat_across_tiles() {
+        let size = glam::UVec2::new(4, 1);
+        let filter = PixelFilter::Triangle { radius: 1.5 };
+        let left = glam::UVec2::new(2, 1);
+        let mut tile_a = Tile::new((0, 0), glam::UVec2::ZERO, left, 1.5, size);
+        let mut tile_b = Tile::new((1, 0), glam::UVec2::new(2, 0), size, 1.5, size);
+        //左侧瓦片中靠近边界的样本应贡献到右侧瓦片的像素
+        tile_a.add_sample(Vec2::new(1.9, 0.5), Vec3::ONE, &filter);
+        tile_b.add_sample(Vec2::new(3.5, 0.5), Vec3::ZERO, &filter);
+        let pixels = merage_tile(vec![vec![tile_a], vec![tile_b]], size).pixels();
+        assert_eq!(pixels[0], Vec3::ONE);
+        let w1 = filter.evaluate(Vec2::new(0.6, 0.0));
+        let w3 = filter.evaluate(Vec2::new(1.0, 0.0));
+        assert!((pixels[2].x - w1 / (w1 + w3)).abs() < 1e-5);
+        assert_eq!(pixels[3], Vec3::ZERO);
+        assert!(PixelFilter::Mitchell { radius: 2.0, b: 1.0 / 3.0, c: 1.0 / 3.0 }
+            .evaluate(Vec2::new(2.5, 0.0))
+            == 0.0);
+    }
+    #[test]
+    fn tone_map_curve() {
+        let map = ToneMap {
+            curve: ToneCurve::Reinhard,
+            exposure: 1.0,
+            srgb: false,
+        };
+        //曝光+1档后1.0变为2.0，Reinhard映射到2/3
+        assert!((map.map(Vec3::ONE).x - 2.0 / 3.0).abs() < 1e-5);
+        let srgb = ToneMap::default();
+        assert_eq!(srgb.to_color(Vec3::new(0.0, 0.5, 4.0)).0, [0, 188, 255]);
+        let aces = ToneMap {
+            curve: ToneCurve::Aces,
+            ..Default::default()
+        };
+        assert!(aces.map(Vec3::splat(100.0)).x <= 1.0);
+    }
+    #[test]
+    fn film_partial_tiles() {
+        let film = Film::new(glam::UVec2::new(40, 20));
+        let mut count = vec![0; 40 * 20];
+        while let Some(item) = film.iter() {
+            for (x, y) in item {
+                count[y as usize * 40 + x as usize] += 1;
+            }
+        }
+        assert!(count.iter().all(|x| *x == 1));
+        //水平视角按宽高比展开
+        let camera = Camera::new(
+            Vec3::ZERO,
+            Vec3::Z,
+            Vec3::Y,
+            Vec2::new(200.0, 100.0),
+            CameraMode::P,
+            90.0,
+        );
+        let mut sampler = Sampler::default();
+        let mut sample = CameraSample::new(0.0, 0.0, &mut sampler);
+        sample.film_point = Vec2::new(200.0, 50.0);
+        let dir = camera.generate_ray(sample).o.dir;
+        assert!((dir.x.abs() / dir.z - 2.0).abs() < 1e-3);
+    }
+    #[test]
+    fn sampler_stratification() {
+        for mode in [SamplerMode::Stratified, SamplerMode::Halton, SamplerMode::Sobol] {
+            let mut sampler = Sampler::new(mode, 16, 0);
+            let mut strata_1d = [0; 16];
+            let mut strata_2d = [0; 16];
+            for index in 0..16 {
+                sampler.start_pixel_sample(glam::UVec2::new(3, 7), index);
+                let u = sampler.get_1d();
+                let v = sampler.get_2d();
+                assert!((0.0..1.0).contains(&u) && v.cmpge(Vec2::ZERO).all() && v.cmplt(Vec2::ONE).all());
+                strata_1d[(u * 16.0) as usize] += 1;
+                strata_2d[(v.x * 4.0) as usize + (v.y * 4.0) as usize * 4] += 1;
+            }
+            //每个像素内的样本在一维上各占一层
+            assert!(strata_1d.iter().all(|x| *x == 1), "{:?}", mode);
+            if mode != SamplerMode::Halton {
+                assert!(strata_2d.iter().all(|x| *x == 1), "{:?}", mode);
+            }
+        }
+    }
+    #[test]
+    fn deterministic_seed() {
+        let sample = |sampler: &mut Sampler, x: u32| {
+            sampler.start_pixel_sample(glam::UVec2::new(x, 2), 5);
+            (sampler.get_2d(), sampler.get_1d())
+        };
+        //克隆出的采样器与调用顺序无关，只由种子、像素与样本下标决定
+        let mut a = Sampler::new(SamplerMode::Independent, 8, 42);
+        let mut b = a.clone();
+        sample(&mut b, 9);
+        assert_eq!(sample(&mut a, 1), sample(&mut b, 1));
+        let mut c = Sampler::new(SamplerMode::Independent, 8, 43);
+        assert_ne!(sample(&mut a, 1), sample(&mut c, 1));
+        //瓦片合并顺序不影响结果
+        let size = glam::UVec2::new(4, 1);
+        let filter = PixelFilter::Gaussian {
+            radius: 2.0,
+            alpha: 1.0,
+        };
+        let tiles = || {
+            let mut tile_a = Tile::new((0, 0), glam::UVec2::ZERO, glam::UVec2::new(2, 1), 2.0, size);
+            let mut tile_b = Tile::new((1, 0), glam::UVec2::new(2, 0), size, 2.0, size);
+            tile_a.add_sample(Vec2::new(1.7, 0.3), Vec3::new(0.1, 0.7, 3.0), &filter);
+            tile_b.add_sample(Vec2::new(2.2, 0.6), Vec3::new(0.3, 0.9, 1e-3), &filter);
+            (tile_a, tile_b)
+        };
+        let (a, b) = tiles();
+        let first = merage_tile(vec![vec![a], vec![b]], size).pixels();
+        let (a, b) = tiles();
+        let second = merage_tile(vec![vec![b], vec![a]], size).pixels();
+        assert_eq!(first, second);
+    }
+    #[test]
+    fn checkpoint_round_trip() {
+        let size = glam::UVec2::new(3, 2);
+        let mut tile = Tile::new((0, 0), glam::UVec2::ZERO, size, 0.5, size);
+        tile.add_sample(Vec2::new(2.5, 1.5), Vec3::new(0.25, 8.0, 1e-4), &PixelFilter::default());
+        let buffer = merage_tile(vec![vec![tile]], size);
+        let path = std::env::temp_dir().join("ray_v2_checkpoint.bin");
+        buffer.save_checkpoint(12, &path).unwrap();
+        let (load, spp) = Buffer::load_checkpoint(&path).unwrap();
+        assert_eq!(spp, 12);
+        assert_eq!(load.size(), size);
+        assert_eq!(load.pixels(), buffer.pixels());
+        std::fs::write(&path, b"RAYCKPT1").unwrap();
+        assert!(Buffer::load_checkpoint(&path).is_err());
+    }
+    #[test]
+    fn adaptive_pixel_stat() {
+        let values = [0.5, 2.0, 1.0, 4.0, 0.25, 3.0];
+        let mut all = PixelStat::default();
+        let (mut a, mut b) = (PixelStat::default(), PixelStat::default());
+        for (i, x) in values.iter().enumerate() {
+            all.add(*x);
+            if i < 2 { a.add(*x) } else { b.add(*x) }
+        }
+        a.merge(&b);
+        assert_eq!(a.n, 6);
+        assert!((a.mean - all.mean).abs() < 1e-5);
+        assert!((a.variance() - all.variance()).abs() < 1e-4);
+        //常数像素收敛，噪声像素继续采样
+        let size = glam::UVec2::new(2, 1);
+        let mut tile = Tile::new((0, 0), glam::UVec2::ZERO, size, 0.5, size);
+        for x in values {
+            tile.add_stat(glam::UVec2::new(0, 0), Vec3::ONE);
+            tile.add_stat(glam::UVec2::new(1, 0), Vec3::splat(x));
+        }
+        let buffer = merage_tile(vec![vec![tile]], size);
+        assert_eq!(buffer.active_pixels(0.05), vec![false, true]);
+    }
+    #[test]
+    fn mis_lambert_weight() {
+        //余弦采样的pdf与f一致时，单次估计恰好等于反照率
+        let r = Vec3::new(0.2, 0.5, 0.8);
+        let bxdf = BxDF::LambertianReflection(LambertianReflection::new(r));
+        let wo = Vec3::new(0.3, 0.2, 0.9).normalize();
+        let mut wi = Vec3::ZERO;
+        let mut pdf = 0.0;
+        let f = bxdf.sample_f(&wo, &mut wi, Vec2::new(0.3, 0.7), &mut pdf);
+        assert!((pdf - bxdf.pdf(&wo, &wi)).abs() < 1e-5);
+        assert!((f * wi.z.abs() / pdf).abs_diff_eq(r, 1e-4));
+        //两种策略的权重之和为1
+        let (a, b) = (0.7, 2.5);
+        assert!((power_heuristic(1.0, a, 1.0, b) + power_heuristic(1.0, b, 1.0, a) - 1.0).abs() < 1e-6);
+    }
+    #[test]
+    fn path_roulette_depth() {
+        let path = PathIntegrator::new(0.9, 6, 2);
+        let mut sampler = Sampler::new(SamplerMode::Independent, 1, 0);
+        let mut dept = 0;
+        //rr_depth之前必定存活且不加权
+        assert_eq!(path.is_next(&mut dept, Vec3::splat(0.01), &mut sampler), Some(1.0));
+        //之后存活概率由吞吐量决定，并受q限制
+        for _ in 0..64 {
+            let mut dept = 2;
+            let p = path.is_next(&mut dept, Vec3::new(0.3, 0.1, 0.2), &mut sampler);
+            assert!(p.is_none() || p == Some(0.3));
+            let mut dept = 2;
+            let p = path.is_next(&mut dept, Vec3::splat(4.0), &mut sampler);
+            assert!(p.is_none() || p == Some(0.9));
+        }
+        //达到最大深度后终止
+        let mut dept = 5;
+        assert_eq!(path.is_next(&mut dept, Vec3::ONE, &mut sampler), None);
+    }
+    #[test]
+    fn toml_medium_index() {
+        let camera = || Camera::new(Vec3::ZERO, Vec3::Z, Vec3::Y, Vec2::ONE, CameraMode::P, 60.0);
+        let medium = "
+            object = []
+            material = []
+            texture = []
+            [[medium]]
+            mode = \"Homogeneous\"
+            sigma_a = [0.1, 0.1, 0.1]
+            sigma_s = [0.1, 0.1, 0.1]
+        ";
+        //相机所在介质越界
+        let loader: TomlLoader = toml::from_str(&format!("camera_medium = 1\n{}", medium)).unwrap();
+        assert!(loader.load_sence(camera()).is_err());
+        //物体的介质边界越界
+        let object = "
+            [[object]]
+            transform = { t = [0.0, 0.0, 0.0], r = [0.0, 1.0, 0.0, 0.0], s = [1.0, 1.0, 1.0] }
+            objtype = \"obj\"
+            path = \"none.obj\"
+            medium_interface = { inside = 0, outside = 2 }
+        ";
+        let loader: TomlLoader =
+            toml::from_str(&format!("{}{}", medium.replace("object = []", ""), object)).unwrap();
+        let err = loader.load_sence(camera()).err().unwrap();
+        assert!(err.to_string().contains("none.obj"));
+    }
+    #[test]
+    fn bdpt_camera_splat() {
+        let camera = Camera::new(
+            Vec3::ZERO,
+            Vec3::Z,
+            Vec3::Y,
+            Vec2::new(200.0, 100.0),
+            CameraMode::P,
+            60.0,
+        );
+        let mut sampler = Sampler::default();
+        let mut sample = CameraSample::new(0.0, 0.0, &mut sampler);
+        sample.film_point = Vec2::new(150.0, 30.0);
+        let dir = camera.generate_ray(sample).o.dir;
+        //光线方向与胶片坐标互逆
+        let film = camera.film_point(dir).unwrap();
+        assert!(film.abs_diff_eq(Vec2::new(150.0, 30.0), 1e-2));
+        let (_, wi, _, point) = camera.sample_wi(dir * 3.0).unwrap();
+        assert!(wi.normalize().abs_diff_eq(-dir.normalize(), 1e-4));
+        assert!(point.abs_diff_eq(film, 1e-2));
+        assert!(camera.sample_wi(-dir).is_none());
+        //泼溅按平均每像素样本数归一化
+        let size = glam::UVec2::new(2, 1);
+        let mut tile = Tile::new((0, 0), glam::UVec2::ZERO, size, 0.5, size);
+        for _ in 0..4 {
+            tile.add_stat(glam::UVec2::new(0, 0), Vec3::ZERO);
+            tile.add_stat(glam::UVec2::new(1, 0), Vec3::ZERO);
+        }
+        tile.add_splat(Vec2::new(1.5, 0.5), Vec3::splat(8.0), size);
+        let buffer = merage_tile(vec![vec![tile]], size);
+        let pixels = buffer.pixels();
+        assert!(pixels[0].abs_diff_eq(Vec3::ZERO, 1e-6));
+        assert!(pixels[1].abs_diff_eq(Vec3::splat(2.0), 1e-6));
+    }
+    #[test]
+    fn sppm_radius_shrink() {
+        //没有收集到光子时保持不变
+        assert_eq!(shrink_radius(3.0, 0, 0.5), (3.0, 0.5));
+        //首轮只保留2/3的光子，面积按同样比例缩小
+        let (n, r) = shrink_radius(0.0, 30, 0.1);
+        assert!((n - 20.0).abs() < 1e-4);
+        assert!((r * r / 0.01 - 2.0 / 3.0).abs() < 1e-4);
+        //半径单调减小
+        let (mut n, mut r) = (0.0, 0.1);
+        for _ in 0..100 {
+            let (n_new, r_new) = shrink_radius(n, 50, r);
+            assert!(r_new < r && n_new > n);
+            (n, r) = (n_new, r_new);
+        }
+        //像素值原样写出
+        let size = glam::UVec2::new(2, 1);
+        let colors = vec![Vec3::new(0.1, 0.2, 0.3), Vec3::splat(4.0)];
+        assert_eq!(Buffer::from_pixels(size, colors.clone()).pixels(), colors);
+    }
+    #[test]
+    fn medium_homogeneous_hg() {
+        let mut sampler = Sampler::default();
+        let wo = Vec3::new(0.3, -0.4, 0.5).normalize();
+        let phase = HenyeyGreenstein::new(0.6);
+        let n = 20000;
+        let (mut cos, mut integral) = (0.0, 0.0);
+        for _ in 0..n {
+            //采样返回的pdf与相函数值相等
+            let mut wi = Vec3::ZERO;
+            let pdf = phase.sample_p(&wo, &mut wi, sampler.sample_2d_d());
+            assert!((pdf - phase.p(&wo, &wi)).abs() < 1e-3 * pdf.max(1.0));
+            assert!((wi.length() - 1.0).abs() < 1e-4);
+            cos += wo.dot(wi);
+            //均匀球面采样估计相函数的积分
+            let u = sampler.sample_2d_d();
+            let z = 1.0 - 2.0 * u.x;
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * std::f32::consts::PI * u.y;
+            let w = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+            integral += phase.p(&wo, &w) * 4.0 * std::f32::consts::PI;
+        }
+        //前向散射时wi与wo夹角余弦的均值为-g
+        assert!((cos / n as f32 + 0.6).abs() < 0.02);
+        assert!((integral / n as f32 - 1.0).abs() < 0.05);
+        //均匀介质的透射率
+        let medium = HomogeneousMedium::new(Vec3::new(0.1, 0.2, 0.3), Vec3::splat(0.5), 0.0);
+        let ray = Ray::from_with_t(Vec3::ZERO, Vec3::X, 0.0, 2.0);
+        let tr = medium.tr(&ray, &mut sampler);
+        assert!(tr.abs_diff_eq((-Vec3::new(0.6, 0.7, 0.8) * 2.0).exp(), 1e-6));
+        //距离采样的权重期望等于透射率
+        let mut mean = Vec3::ZERO;
+        for _ in 0..n {
+            let mut mi = None;
+            let weight = medium.sample(&ray, &mut sampler, &mut mi);
+            if mi.is_none() {
+                mean += weight;
+            }
+        }
+        assert!((mean / n as f32).abs_diff_eq(tr, 0.02));
+    }
+    #[test]
+    fn medium_grid_tracking() {
+        //三线性插值，网格外为零
+        let res = glam::UVec3::new(2, 1, 1);
+        let grid = DensityGrid::new(res, vec![1.0, 3.0]);
+        assert!((grid.lookup(Vec3::new(0.25, 0.5, 0.5)) - 1.0).abs() < 1e-6);
+        assert!((grid.lookup(Vec3::new(0.5, 0.5, 0.5)) - 2.0).abs() < 1e-6);
+        assert!(grid.lookup(Vec3::new(2.0, 0.5, 0.5)).abs() < 1e-6);
+        //粗网格覆盖整条光线，且不小于沿途的密度
+        let density = DensityGrid::new(glam::UVec3::splat(8), (0..512).map(|i| (i % 7) as f32).collect());
+        let majorant = MajorantGrid::new(&density, glam::UVec3::splat(4));
+        let (o, d) = (Vec3::new(-0.5, 0.3, 0.2), Vec3::new(1.0, 0.2, 0.3).normalize());
+        let segments = majorant.segments(o, d, 0.0, f32::MAX);
+        assert!(segments.windows(2).all(|w| (w[0].1 - w[1].0).abs() < 1e-5));
+        for (t0, t1, max) in segments {
+            for i in 0..=10 {
+                let t = t0 + (t1 - t0) * i as f32 / 10.0;
+                assert!(density.lookup(o + d * t) <= max + 1e-5);
+            }
+        }
+        //密度为1时与均匀介质一致
+        let mut sampler = Sampler::default();
+        let medium = GridDensityMedium::new(
+            Vec3::splat(0.5),
+            Vec3::splat(1.0),
+            0.0,
+            DensityGrid::new(glam::UVec3::splat(4), vec![1.0; 64]),
+            Mat4::from_scale(Vec3::splat(2.0)),
+        );
+        //边缘处与网格外插值，只取内部一段
+        let ray = Ray::from_with_t(Vec3::new(0.5, 1.0, 1.0), Vec3::X, 0.0, 1.0);
+        let n = 20000;
+        let (mut tr, mut mean) = (Vec3::ZERO, Vec3::ZERO);
+        for _ in 0..n {
+            tr += medium.tr(&ray, &mut sampler);
+            let mut mi = None;
+            let weight = medium.sample(&ray, &mut sampler, &mut mi);
+            if mi.is_none() {
+                mean += weight;
+            }
+        }
+        let expect = Vec3::splat((-1.5_f32).exp());
+        assert!((tr / n as f32).abs_diff_eq(expect, 0.02));
+        assert!((mean / n as f32).abs_diff_eq(expect, 0.02));
+    }
+    #[test]
+    fn bssrdf_profile() {
+        let albedo = Vec3::new(0.8, 0.5, 0.3);
+        let bssrdf = BSSRDF::new(
+            &InteractionCommon::default(),
+            Vec3::Z,
+            1.33,
+            TransportMode::Radiance,
+            Vec3::new(0.1, 0.2, 0.3),
+            albedo,
+            0,
+        );
+        //剖面在平面上的积分为反照率，采样pdf的积分为1
+        let dr = 1e-4;
+        let (mut sr, mut pdf) = (Vec3::ZERO, Vec3::ZERO);
+        for i in 0..100000 {
+            let r = (i as f32 + 0.5) * dr;
+            let ring = 2.0 * std::f32::consts::PI * r * dr;
+            sr += bssrdf.sr(r) * ring;
+            pdf += Vec3::new(bssrdf.pdf_sr(0, r), bssrdf.pdf_sr(1, r), bssrdf.pdf_sr(2, r)) * ring;
+        }
+        assert!(sr.abs_diff_eq(albedo, 1e-2));
+        assert!(pdf.abs_diff_eq(Vec3::ONE, 1e-2));
+        //采样半径落在x以内的比例与pdf的累积分布一致
+        let (x, steps) = (0.1, 10000);
+        let mut cdf = 0.0;
+        for i in 0..steps {
+            let r = (i as f32 + 0.5) * x / steps as f32;
+            cdf += bssrdf.pdf_sr(1, r) * 2.0 * std::f32::consts::PI * r * x / steps as f32;
+        }
+        let inside = (0..steps)
+            .filter(|i| bssrdf.sample_sr(1, (*i as f32 + 0.5) / steps as f32) < x)
+            .count();
+        assert!((cdf - inside as f32 / steps as f32).abs() < 1e-2);
+        //方向项的余弦加权积分为1
+        let adapter = SubsurfaceAdapter::new(1.33, TransportMode::Importance);
+        let mut integral = 0.0;
+        for i in 0..steps {
+            let cos = (i as f32 + 0.5) / steps as f32;
+            let w = Vec3::new((1.0 - cos * cos).sqrt(), 0.0, cos);
+            integral += adapter.f(&w, &Vec3::Z).x * cos * 2.0 * std::f32::consts::PI / steps as f32;
+        }
+        assert!((integral - 1.0).abs() < 2e-2, "{}", integral);
+    }
 }